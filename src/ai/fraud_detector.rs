@@ -1,9 +1,10 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
 use crate::ai::UserBehaviorPattern;
 use crate::console_log;
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct FraudDetector {
     pub min_action_interval: f64,
@@ -12,9 +13,15 @@ pub struct FraudDetector {
     pub timing_variance_threshold: f64,
 }
 
-#[wasm_bindgen]
+impl Default for FraudDetector {
+    fn default() -> Self {
+        FraudDetector::new()
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl FraudDetector {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new() -> FraudDetector {
         FraudDetector {
             min_action_interval: 50.0, // 50ms minimum between actions
@@ -23,7 +30,10 @@ impl FraudDetector {
             timing_variance_threshold: 10.0, // Variance threshold for bot detection
         }
     }
+}
 
+// Takes the node's own behavior records, which have no JS representation
+impl FraudDetector {
     pub fn analyze_action(&self, 
         user_pattern: &UserBehaviorPattern,
         action_type: &str,
@@ -50,7 +60,7 @@ impl FraudDetector {
         authenticity_score.clamp(0.0, 1.0)
     }
 
-    fn analyze_timing_patterns(&self, timing_patterns: &[f64], current_timestamp: f64) -> f64 {
+    fn analyze_timing_patterns(&self, timing_patterns: &[f64], _current_timestamp: f64) -> f64 {
         if timing_patterns.is_empty() {
             return 1.0;
         }
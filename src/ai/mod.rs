@@ -1,3 +1,4 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -9,12 +10,14 @@ pub mod reward_optimizer;
 pub use fraud_detector::*;
 pub use reward_optimizer::*;
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen(getter_with_clone))]
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AIConsensus {
     pub fraud_detector: FraudDetector,
     pub reward_optimizer: RewardOptimizer,
+    #[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
     pub action_patterns: HashMap<String, UserBehaviorPattern>,
+    #[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
     pub global_stats: GlobalBehaviorStats,
 }
 
@@ -46,9 +49,15 @@ pub struct GlobalBehaviorStats {
     pub peak_activity_times: Vec<f64>,
 }
 
-#[wasm_bindgen]
+impl Default for AIConsensus {
+    fn default() -> Self {
+        AIConsensus::new()
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl AIConsensus {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new() -> AIConsensus {
         AIConsensus {
             fraud_detector: FraudDetector::new(),
@@ -64,7 +73,7 @@ impl AIConsensus {
         }
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn analyze_action(&mut self, 
         user_id: &str, 
         action_type: &str, 
@@ -133,6 +142,7 @@ impl AIConsensus {
         authenticity_score
     }
 
+    #[cfg(feature = "wasm")]
     #[wasm_bindgen]
     pub fn optimize_rewards(&mut self) -> JsValue {
         let optimization = self.reward_optimizer.calculate_optimal_rewards(
@@ -143,7 +153,7 @@ impl AIConsensus {
         serde_wasm_bindgen::to_value(&optimization).unwrap()
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn get_user_reputation(&self, user_id: &str) -> f64 {
         self.action_patterns
             .get(user_id)
@@ -151,7 +161,7 @@ impl AIConsensus {
             .unwrap_or(1.0)
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn is_user_suspicious(&self, user_id: &str) -> bool {
         if let Some(pattern) = self.action_patterns.get(user_id) {
             pattern.reputation_score < 0.6 || 
@@ -162,22 +172,13 @@ impl AIConsensus {
         }
     }
 
+    #[cfg(feature = "wasm")]
     #[wasm_bindgen]
     pub fn get_ai_stats(&self) -> JsValue {
-        let stats = AIStats {
-            total_users_analyzed: self.action_patterns.len(),
-            total_actions_processed: self.global_stats.total_actions,
-            current_fraud_rate: self.global_stats.fraud_rate,
-            average_authenticity: self.global_stats.average_authenticity,
-            suspicious_users: self.action_patterns.values()
-                .filter(|p| p.reputation_score < 0.6)
-                .count(),
-        };
-
-        serde_wasm_bindgen::to_value(&stats).unwrap()
+        serde_wasm_bindgen::to_value(&self.ai_stats()).unwrap()
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn reset_user_reputation(&mut self, user_id: &str) {
         if let Some(pattern) = self.action_patterns.get_mut(user_id) {
             pattern.reputation_score = 1.0;
@@ -187,11 +188,25 @@ impl AIConsensus {
     }
 }
 
-#[derive(Serialize, Deserialize)]
-struct AIStats {
-    total_users_analyzed: usize,
-    total_actions_processed: u64,
-    current_fraud_rate: f64,
-    average_authenticity: f64,
-    suspicious_users: usize,
+impl AIConsensus {
+    pub fn ai_stats(&self) -> AIStats {
+        AIStats {
+            total_users_analyzed: self.action_patterns.len(),
+            total_actions_processed: self.global_stats.total_actions,
+            current_fraud_rate: self.global_stats.fraud_rate,
+            average_authenticity: self.global_stats.average_authenticity,
+            suspicious_users: self.action_patterns.values()
+                .filter(|p| p.reputation_score < 0.6)
+                .count(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AIStats {
+    pub total_users_analyzed: usize,
+    pub total_actions_processed: u64,
+    pub current_fraud_rate: f64,
+    pub average_authenticity: f64,
+    pub suspicious_users: usize,
 }
\ No newline at end of file
@@ -1,12 +1,14 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use crate::ai::{UserBehaviorPattern, GlobalBehaviorStats};
 use crate::console_log;
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RewardOptimizer {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
     pub base_rewards: HashMap<String, u64>,
     pub difficulty_multiplier: f64,
     pub economy_balance: f64,
@@ -21,9 +23,15 @@ pub struct RewardOptimization {
     pub recommended_changes: Vec<String>,
 }
 
-#[wasm_bindgen]
+impl Default for RewardOptimizer {
+    fn default() -> Self {
+        RewardOptimizer::new()
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl RewardOptimizer {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new() -> RewardOptimizer {
         let mut base_rewards = HashMap::new();
         base_rewards.insert("click".to_string(), 5);
@@ -41,6 +49,21 @@ impl RewardOptimizer {
         }
     }
 
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen]
+    pub fn get_current_rewards(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.base_rewards).unwrap()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn update_base_reward(&mut self, action_type: &str, reward: u64) {
+        self.base_rewards.insert(action_type.to_string(), reward);
+        console_log!("Updated base reward for {}: {} BTN", action_type, reward);
+    }
+}
+
+// Takes the node's own behavior records, which have no JS representation
+impl RewardOptimizer {
     pub fn calculate_optimal_rewards(
         &mut self,
         user_patterns: &HashMap<String, UserBehaviorPattern>,
@@ -80,7 +103,7 @@ impl RewardOptimizer {
         // Calculate total BTN being distributed
         let total_actions = global_stats.total_actions as f64;
         let average_reward = self.base_rewards.values().sum::<u64>() as f64 / self.base_rewards.len() as f64;
-        let estimated_total_rewards = total_actions * average_reward;
+        let _estimated_total_rewards = total_actions * average_reward;
 
         // Check for inflation pressure
         let inflation_pressure = if total_actions > 1000000.0 {
@@ -199,15 +222,4 @@ impl RewardOptimizer {
 
         recommendations
     }
-
-    #[wasm_bindgen]
-    pub fn get_current_rewards(&self) -> JsValue {
-        serde_wasm_bindgen::to_value(&self.base_rewards).unwrap()
-    }
-
-    #[wasm_bindgen]
-    pub fn update_base_reward(&mut self, action_type: &str, reward: u64) {
-        self.base_rewards.insert(action_type.to_string(), reward);
-        console_log!("Updated base reward for {}: {} BTN", action_type, reward);
-    }
-}
\ No newline at end of file
+}
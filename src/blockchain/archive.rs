@@ -0,0 +1,491 @@
+use std::io::{ErrorKind, Read, Write};
+use crate::blockchain::{
+    AccountState, AccountStateIndex, BitnunBlockchain, Block, BlockHeader, ChainIndex, DifficultyConfig, EmissionSchedule,
+    GenesisAllocation, GenesisConfig, GenesisValidator, TransactionKind,
+};
+use crate::blockchain::encoding::{DecodeError, Decoder, Encoder, KIND_CHAIN_EXPORT, KIND_STATE_SNAPSHOT};
+use crate::console_log;
+use crate::error::{BitnunResult, ChainError};
+
+// A chain export is a stream of records, each a u32 length followed by a
+// canonical encoding: one export header, then one block per record. Blocks
+// can be written and verified one at a time without buffering the chain.
+pub const CHAIN_EXPORT_VERSION: u32 = 1;
+
+const MAX_RECORD_LEN: u32 = 64 * 1024 * 1024;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChainExportHeader {
+    pub format_version: u32,
+    pub chain_id: String,
+    pub mining_reward: u64,
+    pub emission: EmissionSchedule,
+    pub difficulty: DifficultyConfig,
+    pub genesis_validators: Vec<GenesisValidator>,
+    pub start_height: u64, // Index of the first exported block
+    pub block_count: u64,
+}
+
+// Account state at a height plus every header up to it, so a node can check
+// proof-of-work from genesis and then only replay the blocks that follow
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateSnapshot {
+    pub format_version: u32,
+    pub chain_id: String,
+    pub mining_reward: u64,
+    pub emission: EmissionSchedule,
+    pub difficulty: DifficultyConfig,
+    pub genesis_validators: Vec<GenesisValidator>,
+    pub genesis_allocations: Vec<GenesisAllocation>, // Headers don't carry the genesis body
+    pub headers: Vec<BlockHeader>,
+    pub state: AccountStateIndex,
+}
+
+impl ChainExportHeader {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new(KIND_CHAIN_EXPORT);
+        encoder.put_u32(self.format_version);
+        encoder.put_str(&self.chain_id);
+        encoder.put_u64(self.mining_reward);
+        self.emission.encode_fields(&mut encoder);
+        self.difficulty.encode_fields(&mut encoder);
+        encode_validators(&mut encoder, &self.genesis_validators);
+        encoder.put_u64(self.start_height);
+        encoder.put_u64(self.block_count);
+        encoder.finish()
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<ChainExportHeader, DecodeError> {
+        let mut decoder = Decoder::new(bytes, KIND_CHAIN_EXPORT)?;
+        let header = ChainExportHeader {
+            format_version: get_format_version(&mut decoder)?,
+            chain_id: decoder.get_string()?,
+            mining_reward: decoder.get_u64()?,
+            emission: EmissionSchedule::decode_fields(&mut decoder)?,
+            difficulty: DifficultyConfig::decode_fields(&mut decoder)?,
+            genesis_validators: decode_validators(&mut decoder)?,
+            start_height: decoder.get_u64()?,
+            block_count: decoder.get_u64()?,
+        };
+        decoder.finish()?;
+        Ok(header)
+    }
+
+    // The config these parameters and the exported genesis block describe;
+    // allocations are the only part the header doesn't carry
+    pub fn genesis_config(&self, genesis_block: &Block) -> GenesisConfig {
+        let allocations = genesis_block.transactions
+            .iter()
+            .filter(|transaction| transaction.kind == TransactionKind::Genesis)
+            .map(|transaction| GenesisAllocation {
+                address: transaction.to_address.clone(),
+                amount: transaction.amount,
+            })
+            .collect();
+
+        GenesisConfig {
+            chain_id: self.chain_id.clone(),
+            timestamp: genesis_block.timestamp.clone(),
+            allocations,
+            validators: self.genesis_validators.clone(),
+            mining_reward: self.mining_reward,
+            difficulty: self.difficulty.clone(),
+            emission: self.emission.clone(),
+        }
+    }
+}
+
+impl StateSnapshot {
+    pub fn height(&self) -> u64 {
+        self.headers.len() as u64
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new(KIND_STATE_SNAPSHOT);
+        encoder.put_u32(self.format_version);
+        encoder.put_str(&self.chain_id);
+        encoder.put_u64(self.mining_reward);
+        self.emission.encode_fields(&mut encoder);
+        self.difficulty.encode_fields(&mut encoder);
+        encode_validators(&mut encoder, &self.genesis_validators);
+        encoder.put_u32(self.genesis_allocations.len() as u32);
+        for allocation in &self.genesis_allocations {
+            encoder.put_str(&allocation.address);
+            encoder.put_u64(allocation.amount);
+        }
+
+        encoder.put_u32(self.headers.len() as u32);
+        for header in &self.headers {
+            encoder.put_bytes(&header.to_bytes());
+        }
+
+        // Sorted so the same state always encodes to the same bytes
+        let mut accounts: Vec<(&String, &AccountState)> = self.state.accounts.iter().collect();
+        accounts.sort_by(|a, b| a.0.cmp(b.0));
+        encoder.put_u64(self.state.height);
+        encoder.put_u32(accounts.len() as u32);
+        for (address, account) in accounts {
+            encoder.put_str(address);
+            encoder.put_bytes(&account.to_bytes());
+        }
+        encoder.put_u32(self.state.spent_action_proofs.len() as u32);
+        for action_id in &self.state.spent_action_proofs {
+            encoder.put_str(action_id);
+        }
+        encoder.put_u64(self.state.supply);
+        encoder.put_u64(self.state.action_epoch);
+        encoder.put_u64(self.state.epoch_action_minted);
+        encoder.finish()
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<StateSnapshot, DecodeError> {
+        let mut decoder = Decoder::new(bytes, KIND_STATE_SNAPSHOT)?;
+        let format_version = get_format_version(&mut decoder)?;
+        let chain_id = decoder.get_string()?;
+        let mining_reward = decoder.get_u64()?;
+        let emission = EmissionSchedule::decode_fields(&mut decoder)?;
+        let difficulty = DifficultyConfig::decode_fields(&mut decoder)?;
+        let genesis_validators = decode_validators(&mut decoder)?;
+        let mut genesis_allocations = Vec::new();
+        for _ in 0..decoder.get_u32()? {
+            genesis_allocations.push(GenesisAllocation {
+                address: decoder.get_string()?,
+                amount: decoder.get_u64()?,
+            });
+        }
+
+        let header_count = decoder.get_u32()?;
+        let mut headers = Vec::new();
+        for _ in 0..header_count {
+            headers.push(BlockHeader::decode(decoder.get_bytes()?)?);
+        }
+
+        let mut state = AccountStateIndex::new();
+        state.height = decoder.get_u64()?;
+        let account_count = decoder.get_u32()?;
+        for _ in 0..account_count {
+            let address = decoder.get_string()?;
+            state.insert_account(address, AccountState::decode(decoder.get_bytes()?)?);
+        }
+        for _ in 0..decoder.get_u32()? {
+            state.spent_action_proofs.insert(decoder.get_string()?);
+        }
+        state.supply = decoder.get_u64()?;
+        state.action_epoch = decoder.get_u64()?;
+        state.epoch_action_minted = decoder.get_u64()?;
+        decoder.finish()?;
+
+        Ok(StateSnapshot {
+            format_version,
+            chain_id,
+            mining_reward,
+            emission,
+            difficulty,
+            genesis_validators,
+            genesis_allocations,
+            headers,
+            state,
+        })
+    }
+
+    // The config the snapshot's genesis header was built from, if the
+    // snapshot is honest; its hash is the header's previous_hash
+    pub fn genesis_config(&self) -> Option<GenesisConfig> {
+        let genesis = self.headers.first()?;
+        Some(GenesisConfig {
+            chain_id: self.chain_id.clone(),
+            timestamp: genesis.timestamp.clone(),
+            allocations: self.genesis_allocations.clone(),
+            validators: self.genesis_validators.clone(),
+            mining_reward: self.mining_reward,
+            difficulty: self.difficulty.clone(),
+            emission: self.emission.clone(),
+        })
+    }
+}
+
+impl BitnunBlockchain {
+    // Streams blocks from `from_height` to the tip, reading bodies held only
+    // in the store back from it; blocks that were loaded from a snapshot have
+    // no bodies and are never exported
+    pub fn write_chain<W: Write>(&self, writer: &mut W, from_height: u64) -> std::io::Result<()> {
+        let first_body = if self.store.is_some() { 0 } else { self.pruned_height };
+        let start = (from_height.max(first_body) as usize).min(self.blocks.len());
+        let header = self.export_header(start as u64, (self.blocks.len() - start) as u64);
+        write_record(writer, &header.to_bytes())?;
+
+        for height in start..self.blocks.len() {
+            let block = self.block_at(height as u64)
+                .map_err(|error| std::io::Error::other(error.to_string()))?
+                .ok_or_else(|| std::io::Error::other(format!("no body stored for block {}", height)))?;
+            write_record(writer, &block.to_bytes())?;
+        }
+        writer.flush()
+    }
+
+    // Rebuilds a chain from a full export, validating each block as it is read
+    pub fn read_chain<R: Read>(reader: &mut R) -> BitnunResult<BitnunBlockchain> {
+        let header = ChainExportHeader::decode(&read_record(reader)?)?;
+        let genesis_block = Block::decode(&read_record(reader)?)?;
+        if header.start_height != 0 {
+            return Err(ChainError::UnknownParent(genesis_block.previous_hash).into());
+        }
+
+        // The header's consensus parameters must be the ones the genesis
+        // block commits to, so the block is rebuilt from them and compared
+        let config = header.genesis_config(&genesis_block);
+        let expected = config.genesis_block()?;
+        if expected.hash != genesis_block.hash {
+            return Err(ChainError::GenesisMismatch {
+                expected: expected.hash,
+                found: genesis_block.hash,
+            }.into());
+        }
+
+        let mut chain = BitnunBlockchain::from_genesis_config(&config)?;
+
+        chain.read_blocks(reader, header.block_count.saturating_sub(1))?;
+        console_log!("Imported {} blocks for chain {}", chain.blocks.len(), chain.chain_id);
+        Ok(chain)
+    }
+
+    pub fn snapshot_at(&self, height: u64) -> BitnunResult<StateSnapshot> {
+        if height == 0 || height < self.pruned_height || height > self.blocks.len() as u64 {
+            return Err(ChainError::SnapshotUnavailable(height).into());
+        }
+
+        let height = height as usize;
+        Ok(StateSnapshot {
+            format_version: CHAIN_EXPORT_VERSION,
+            chain_id: self.chain_id.clone(),
+            mining_reward: self.mining_reward,
+            emission: self.emission.clone(),
+            difficulty: self.difficulty_config.clone(),
+            genesis_validators: self.genesis_validators.clone(),
+            genesis_allocations: self.genesis_allocations.clone(),
+            headers: self.blocks[..height].iter().map(|block| block.header()).collect(),
+            state: self.state_at(height),
+        })
+    }
+
+    // Starts from a snapshot, then applies an export of the blocks after it.
+    // Snapshot headers are checked for linkage and proof-of-work, and the
+    // consensus parameters against the genesis header; the state is checked
+    // only against the last header's state root.
+    pub fn from_snapshot<R: Read>(snapshot: &StateSnapshot, recent_blocks: &mut R) -> BitnunResult<BitnunBlockchain> {
+        let config = match snapshot.genesis_config() {
+            Some(config) if snapshot.state.height == snapshot.height() => config,
+            _ => return Err(ChainError::SnapshotMismatch {
+                height: snapshot.height(),
+                state_height: snapshot.state.height,
+            }.into()),
+        };
+
+        // The genesis header commits to the config through its previous_hash,
+        // so parameters that differ from the ones it was mined under are caught
+        let config_hash = config.config_hash()?;
+        if config_hash != snapshot.headers[0].previous_hash {
+            return Err(ChainError::GenesisMismatch {
+                expected: config_hash,
+                found: snapshot.headers[0].previous_hash.clone(),
+            }.into());
+        }
+
+        // The state must be the one the last snapshot header commits to
+        let last_header = &snapshot.headers[snapshot.headers.len() - 1];
+        let state_root = snapshot.state.state_root();
+        if state_root != last_header.state_root {
+            return Err(ChainError::StateRootMismatch {
+                height: last_header.index,
+                expected: last_header.state_root.clone(),
+                found: state_root,
+            }.into());
+        }
+
+        let blocks: Vec<Block> = snapshot.headers.iter().map(Block::from_header).collect();
+        let mut chain = BitnunBlockchain::from_genesis(&config.chain_id, blocks[0].clone());
+        chain.configure(&config.difficulty, config.mining_reward, &config.emission);
+        chain.genesis_validators = config.validators;
+        chain.genesis_allocations = config.allocations;
+        chain.pruned_height = snapshot.height();
+        chain.base_state = snapshot.state.clone();
+        chain.state = snapshot.state.clone();
+
+        for position in 0..blocks.len() {
+            chain.check_block_header(&blocks[..position], &blocks[position])
+                .map_err(ChainError::InvalidBlock)?;
+        }
+        chain.index = ChainIndex::build(&blocks);
+        chain.blocks = blocks;
+
+        let header = ChainExportHeader::decode(&read_record(recent_blocks)?)?;
+        if header.chain_id != chain.chain_id {
+            return Err(ChainError::WrongChain {
+                expected: chain.chain_id.clone(),
+                found: header.chain_id,
+            }.into());
+        }
+        chain.read_blocks(recent_blocks, header.block_count)?;
+        chain.mining_bits = chain.difficulty_config.next_bits(&chain.blocks);
+
+        console_log!("Loaded snapshot at height {} plus {} blocks", snapshot.height(), header.block_count);
+        Ok(chain)
+    }
+
+    fn export_header(&self, start_height: u64, block_count: u64) -> ChainExportHeader {
+        ChainExportHeader {
+            format_version: CHAIN_EXPORT_VERSION,
+            chain_id: self.chain_id.clone(),
+            mining_reward: self.mining_reward,
+            emission: self.emission.clone(),
+            difficulty: self.difficulty_config.clone(),
+            genesis_validators: self.genesis_validators.clone(),
+            start_height,
+            block_count,
+        }
+    }
+
+    pub(crate) fn configure(&mut self, difficulty: &DifficultyConfig, mining_reward: u64, emission: &EmissionSchedule) {
+        self.difficulty_config = difficulty.clone();
+        self.mining_reward = mining_reward;
+        self.emission = emission.clone();
+        self.mining_bits = self.difficulty_config.next_bits(&self.blocks);
+    }
+
+    fn read_blocks<R: Read>(&mut self, reader: &mut R, count: u64) -> BitnunResult<()> {
+        for _ in 0..count {
+            let block = Block::decode(&read_record(reader)?)?;
+            self.extend_chain(block)?;
+        }
+
+        // Anything after the announced blocks means the export is corrupt
+        let mut extra = [0u8; 1];
+        match reader.read(&mut extra) {
+            Ok(0) => Ok(()),
+            Ok(_) => Err(DecodeError::TrailingBytes(1).into()),
+            Err(error) => Err(DecodeError::Io(error.to_string()).into()),
+        }
+    }
+}
+
+fn write_record<W: Write>(writer: &mut W, record: &[u8]) -> std::io::Result<()> {
+    writer.write_all(&(record.len() as u32).to_be_bytes())?;
+    writer.write_all(record)
+}
+
+fn read_record<R: Read>(reader: &mut R) -> Result<Vec<u8>, DecodeError> {
+    let mut len = [0u8; 4];
+    read_exact(reader, &mut len)?;
+
+    let len = u32::from_be_bytes(len);
+    if len > MAX_RECORD_LEN {
+        return Err(DecodeError::RecordTooLarge(len));
+    }
+
+    let mut record = vec![0u8; len as usize];
+    read_exact(reader, &mut record)?;
+    Ok(record)
+}
+
+fn read_exact<R: Read>(reader: &mut R, buffer: &mut [u8]) -> Result<(), DecodeError> {
+    reader.read_exact(buffer).map_err(|error| match error.kind() {
+        ErrorKind::UnexpectedEof => DecodeError::UnexpectedEnd,
+        _ => DecodeError::Io(error.to_string()),
+    })
+}
+
+fn encode_validators(encoder: &mut Encoder, validators: &[GenesisValidator]) {
+    encoder.put_u32(validators.len() as u32);
+    for validator in validators {
+        encoder.put_str(&validator.address);
+        encoder.put_u64(validator.stake);
+    }
+}
+
+fn decode_validators(decoder: &mut Decoder) -> Result<Vec<GenesisValidator>, DecodeError> {
+    let mut validators = Vec::new();
+    for _ in 0..decoder.get_u32()? {
+        validators.push(GenesisValidator {
+            address: decoder.get_string()?,
+            stake: decoder.get_u64()?,
+        });
+    }
+    Ok(validators)
+}
+
+fn get_format_version(decoder: &mut Decoder) -> Result<u32, DecodeError> {
+    let version = decoder.get_u32()?;
+    if version != CHAIN_EXPORT_VERSION {
+        return Err(DecodeError::UnsupportedExportVersion(version));
+    }
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::BitnunError;
+
+    #[test]
+    fn import_keeps_genesis_validators() {
+        let mut chain = BitnunBlockchain::from_preset("dev").unwrap();
+        chain.mine_pending_transactions("btnminer");
+
+        let imported = BitnunBlockchain::import_chain(&chain.export_chain()).unwrap();
+        assert_eq!(imported.genesis_validators(), chain.genesis_validators());
+        assert_eq!(imported.get_genesis_hash(), chain.get_genesis_hash());
+    }
+
+    #[test]
+    fn import_rejects_parameters_genesis_does_not_commit_to() {
+        let mut chain = BitnunBlockchain::from_preset("dev").unwrap();
+        chain.mining_reward = 1_000_000;
+
+        match BitnunBlockchain::import_chain(&chain.export_chain()) {
+            Err(BitnunError::Chain(ChainError::GenesisMismatch { found, .. })) => assert_eq!(found, chain.get_genesis_hash()),
+            other => panic!("expected a genesis mismatch, got {:?}", other.map(|chain| chain.get_genesis_hash())),
+        }
+
+        let mut chain = BitnunBlockchain::from_preset("dev").unwrap();
+        chain.genesis_validators.clear();
+        assert!(BitnunBlockchain::import_chain(&chain.export_chain()).is_err());
+    }
+
+    #[test]
+    fn snapshot_import_keeps_the_genesis_config() {
+        let mut chain = BitnunBlockchain::from_preset("dev").unwrap();
+        chain.mine_pending_transactions("btnminer");
+        chain.mine_pending_transactions("btnminer");
+
+        let imported = BitnunBlockchain::import_snapshot(&chain.export_snapshot(2).unwrap(), &chain.export_chain_from(2)).unwrap();
+        assert_eq!(imported.snapshot_at(2).unwrap(), chain.snapshot_at(2).unwrap());
+        assert_eq!(imported.snapshot_at(2).unwrap().genesis_config().unwrap(), GenesisConfig::dev());
+    }
+
+    #[test]
+    fn snapshot_import_rejects_parameters_genesis_does_not_commit_to() {
+        let mut chain = BitnunBlockchain::from_preset("dev").unwrap();
+        chain.mine_pending_transactions("btnminer");
+        let recent_blocks = chain.export_chain_from(2);
+
+        let mut raised_reward = chain.snapshot_at(2).unwrap();
+        raised_reward.mining_reward = 1_000_000;
+        let mut extra_allocation = chain.snapshot_at(2).unwrap();
+        extra_allocation.genesis_allocations.push(GenesisAllocation { address: "btnforger".to_string(), amount: 1 });
+
+        for snapshot in [raised_reward, extra_allocation] {
+            match BitnunBlockchain::from_snapshot(&snapshot, &mut &recent_blocks[..]) {
+                Err(BitnunError::Chain(ChainError::GenesisMismatch { found, .. })) => assert_eq!(found, snapshot.headers[0].previous_hash),
+                other => panic!("expected a genesis mismatch, got {:?}", other.map(|chain| chain.get_genesis_hash())),
+            }
+        }
+    }
+
+    #[test]
+    fn unsupported_export_versions_are_reported_in_full() {
+        let chain = BitnunBlockchain::from_preset("dev").unwrap();
+        let mut snapshot = chain.snapshot_at(1).unwrap();
+        snapshot.format_version = 0x0100_0001;
+        assert_eq!(StateSnapshot::decode(&snapshot.to_bytes()), Err(DecodeError::UnsupportedExportVersion(0x0100_0001)));
+    }
+}
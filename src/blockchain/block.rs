@@ -1,12 +1,15 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
-use sha2::{Sha256, Digest};
-use chrono::{DateTime, Utc};
-use crate::blockchain::Transaction;
+use crate::blockchain::{BlockHeader, GenesisConfig, MerkleProof, MerkleTree, Transaction};
+use crate::blockchain::encoding::{DecodeError, Decoder, Encoder, KIND_BLOCK};
+use crate::blockchain::pow::{meets_target, work_from_compact};
 use crate::console_log;
+use crate::error::BitnunResult;
+use crate::runtime;
 
-#[wasm_bindgen]
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen(getter_with_clone))]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Block {
     pub index: u64,
     pub timestamp: String,
@@ -15,13 +18,21 @@ pub struct Block {
     pub hash: String,
     pub nonce: u64,
     pub merkle_root: String,
+    #[serde(default)]
+    pub bits: u32, // Compact proof-of-work target, 0 for genesis
+    #[serde(default = "empty_state_root")]
+    pub state_root: String,
 }
 
-#[wasm_bindgen]
+fn empty_state_root() -> String {
+    "0".to_string()
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl Block {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new(index: u64, previous_hash: String, transactions: Vec<Transaction>) -> Block {
-        let timestamp = Utc::now().to_rfc3339();
+        let timestamp = runtime::now_rfc3339();
         let merkle_root = Self::calculate_merkle_root(&transactions);
         
         let mut block = Block {
@@ -32,80 +43,206 @@ impl Block {
             hash: String::new(),
             nonce: 0,
             merkle_root,
+            bits: 0,
+            state_root: empty_state_root(),
         };
 
         block.hash = block.calculate_hash();
         block
     }
 
+    // Mainnet genesis; see GenesisConfig for other networks
     pub fn genesis() -> Block {
-        let genesis_tx = Transaction::new_genesis("genesis", 1000000000); // 10M BTN initial supply
-        Block::new(0, "0".to_string(), vec![genesis_tx])
+        GenesisConfig::mainnet().genesis_block().expect("mainnet preset is valid")
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            index: self.index,
+            timestamp: self.timestamp.clone(),
+            previous_hash: self.previous_hash.clone(),
+            merkle_root: self.merkle_root.clone(),
+            bits: self.bits,
+            nonce: self.nonce,
+            state_root: self.state_root.clone(),
+        }
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn calculate_hash(&self) -> String {
-        let data = format!(
-            "{}{}{}{}{}{}",
-            self.index,
-            self.timestamp,
-            self.previous_hash,
-            self.merkle_root,
-            self.nonce,
-            serde_json::to_string(&self.transactions).unwrap_or_default()
-        );
+        self.header().calculate_hash()
+    }
 
-        let mut hasher = Sha256::new();
-        hasher.update(data.as_bytes());
-        format!("{:x}", hasher.finalize())
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn header_bytes(&self) -> Vec<u8> {
+        self.header().to_bytes()
     }
 
-    #[wasm_bindgen]
-    pub fn mine_block(&mut self, difficulty: u32) {
-        let target = "0".repeat(difficulty as usize);
-        let start_time = js_sys::Date::now();
+    // The hash is not part of the encoding; it is recomputed on decode
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new(KIND_BLOCK);
+        encoder.put_bytes(&self.header_bytes());
+        encoder.put_u32(self.transactions.len() as u32);
+        for transaction in &self.transactions {
+            encoder.put_bytes(&transaction.to_bytes());
+        }
+        encoder.finish()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn from_bytes(bytes: &[u8]) -> BitnunResult<Block> {
+        Ok(Block::decode(bytes)?)
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn mine_block(&mut self, bits: u32) {
+        let start_time = runtime::now_millis();
 
-        while !self.hash.starts_with(&target) {
-            self.nonce += 1;
-            self.hash = self.calculate_hash();
+        // Only the header is rehashed per attempt
+        let mut header = self.header();
+        header.bits = bits;
+        let mut digest = header.hash_digest();
+
+        while !meets_target(&digest, bits) {
+            header.nonce += 1;
+            digest = header.hash_digest();
         }
 
-        let mining_time = js_sys::Date::now() - start_time;
+        self.bits = bits;
+        self.nonce = header.nonce;
+        self.hash = hex::encode(digest);
+
+        let mining_time = runtime::now_millis() - start_time;
         console_log!("Block mined in {}ms with nonce: {}", mining_time, self.nonce);
     }
 
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn meets_target(&self) -> bool {
+        self.header().meets_target()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn has_valid_merkle_root(&self) -> bool {
+        self.merkle_root == Self::calculate_merkle_root(&self.transactions)
+    }
+
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen]
+    pub fn get_transaction_proof(&self, tx_id: &str) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.transaction_proof(tx_id)).unwrap()
+    }
+
+    // Commits to each transaction's full canonical encoding, signature included
     fn calculate_merkle_root(transactions: &[Transaction]) -> String {
-        if transactions.is_empty() {
-            return "0".to_string();
+        Self::merkle_tree(transactions).root_hex()
+    }
+}
+
+impl Block {
+    // Body-less block standing in for a header loaded from a snapshot
+    pub fn from_header(header: &BlockHeader) -> Block {
+        Block {
+            index: header.index,
+            timestamp: header.timestamp.clone(),
+            transactions: Vec::new(),
+            previous_hash: header.previous_hash.clone(),
+            hash: header.calculate_hash(),
+            nonce: header.nonce,
+            merkle_root: header.merkle_root.clone(),
+            bits: header.bits,
+            state_root: header.state_root.clone(),
         }
+    }
 
-        let mut hashes: Vec<String> = transactions
-            .iter()
-            .map(|tx| {
-                let mut hasher = Sha256::new();
-                hasher.update(tx.id.as_bytes());
-                format!("{:x}", hasher.finalize())
-            })
-            .collect();
-
-        while hashes.len() > 1 {
-            let mut next_level = Vec::new();
-            
-            for chunk in hashes.chunks(2) {
-                let combined = if chunk.len() == 2 {
-                    format!("{}{}", chunk[0], chunk[1])
-                } else {
-                    format!("{}{}", chunk[0], chunk[0])
-                };
+    // Held without its body, as below a chain's pruned height; every full
+    // block carries at least its genesis allocation or mining reward
+    pub fn is_header_only(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    // Proof that the transaction is committed to by this block's merkle_root
+    pub fn transaction_proof(&self, tx_id: &str) -> Option<MerkleProof> {
+        let position = self.transactions.iter().position(|tx| tx.id == tx_id)?;
+        Self::merkle_tree(&self.transactions).generate_proof(position)
+    }
+
+    pub fn merkle_tree(transactions: &[Transaction]) -> MerkleTree {
+        let leaves: Vec<Vec<u8>> = transactions.iter().map(|tx| tx.to_bytes()).collect();
+        MerkleTree::new(&leaves)
+    }
+
+    pub fn work(&self) -> u128 {
+        work_from_compact(self.bits)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Block, DecodeError> {
+        let mut decoder = Decoder::new(bytes, KIND_BLOCK)?;
+        let header = BlockHeader::decode(decoder.get_bytes()?)?;
+
+        let count = decoder.get_u32()?;
+        let mut transactions = Vec::new();
+        for _ in 0..count {
+            transactions.push(Transaction::decode(decoder.get_bytes()?)?);
+        }
+        decoder.finish()?;
 
-                let mut hasher = Sha256::new();
-                hasher.update(combined.as_bytes());
-                next_level.push(format!("{:x}", hasher.finalize()));
+        Ok(Block {
+            index: header.index,
+            timestamp: header.timestamp.clone(),
+            transactions,
+            previous_hash: header.previous_hash.clone(),
+            hash: header.calculate_hash(),
+            nonce: header.nonce,
+            merkle_root: header.merkle_root,
+            bits: header.bits,
+            state_root: header.state_root,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::SiblingSide;
+
+    fn block_of(size: u64) -> Block {
+        let transactions = (1..=size).map(|amount| Transaction::new("btnsender", "btnrecipient", amount)).collect();
+        Block::new(1, "00".repeat(32), transactions)
+    }
+
+    #[test]
+    fn every_transaction_proves_inclusion_against_the_header() {
+        for size in [1, 2, 3, 5, 8] {
+            let block = block_of(size);
+            let header = block.header();
+            for transaction in &block.transactions {
+                let proof = block.transaction_proof(&transaction.id).unwrap();
+                assert!(header.includes_transaction(transaction, &proof), "leaf {} of {}", proof.leaf_index, size);
             }
 
-            hashes = next_level;
+            let outsider = Transaction::new("btnsender", "btnrecipient", size + 1);
+            let proof = block.transaction_proof(&block.transactions[0].id).unwrap();
+            assert!(!header.includes_transaction(&outsider, &proof));
+            assert!(block.transaction_proof(&outsider.id).is_none());
         }
+    }
 
-        hashes[0].clone()
+    #[test]
+    fn proofs_with_a_sibling_on_the_wrong_side_fail() {
+        let block = block_of(5);
+        let header = block.header();
+        for transaction in &block.transactions {
+            let proof = block.transaction_proof(&transaction.id).unwrap();
+            for step in 0..proof.steps.len() {
+                let mut flipped = proof.clone();
+                flipped.steps[step].side = match flipped.steps[step].side {
+                    SiblingSide::Left => SiblingSide::Right,
+                    SiblingSide::Right => SiblingSide::Left,
+                };
+                assert!(!header.includes_transaction(transaction, &flipped));
+            }
+        }
     }
-}
\ No newline at end of file
+}
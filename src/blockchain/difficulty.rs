@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, TimeZone, Utc};
+use crate::blockchain::Block;
+use crate::blockchain::encoding::{DecodeError, Decoder, Encoder};
+use crate::blockchain::pow::{is_easier_than, scale_compact, EASIEST_BITS};
+
+// A block's timestamp must be later than the median of this many blocks
+// before it, and at most MAX_FUTURE_DRIFT_MS ahead of the local clock, so a
+// miner can neither skew a retarget window nor run the clock ahead much
+pub const MEDIAN_TIME_SPAN: usize = 11;
+pub const MAX_FUTURE_DRIFT_MS: i64 = 2 * 60 * 1000; // 2 minutes
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DifficultyConfig {
+    pub initial_bits: u32, // Target of the first block after genesis
+    pub target_block_time_ms: u64,
+    pub retarget_interval: u64, // Blocks per adjustment window
+    pub max_adjustment_factor: u64, // Largest change in target per window
+    pub pow_limit_bits: u32, // Easiest target ever allowed
+    pub action_bits: u32, // Target every proof of action must meet
+}
+
+impl Default for DifficultyConfig {
+    fn default() -> Self {
+        DifficultyConfig {
+            initial_bits: 0x2000ffff, // One leading zero byte
+            target_block_time_ms: 10_000, // 10 seconds
+            retarget_interval: 10,
+            max_adjustment_factor: 4,
+            pow_limit_bits: EASIEST_BITS,
+            action_bits: 0x1f00ffff, // Two leading zero bytes
+        }
+    }
+}
+
+impl DifficultyConfig {
+    // Target the block at `blocks.len()` must carry, given the chain before it
+    pub fn next_bits(&self, blocks: &[Block]) -> u32 {
+        let height = blocks.len() as u64;
+        let previous = match blocks.last() {
+            Some(previous) => previous,
+            None => return 0, // Genesis is not mined
+        };
+
+        if height == 1 {
+            return self.limit(self.initial_bits);
+        }
+
+        let interval = self.retarget_interval.max(2);
+        if !height.is_multiple_of(interval) {
+            return previous.bits;
+        }
+
+        // Genesis carries a fixed timestamp from the config, so it never opens a window
+        let window_start = &blocks[(height - interval).max(1) as usize];
+        let actual_ms = match (window_start.timestamp_millis(), previous.timestamp_millis()) {
+            (Some(start), Some(end)) => (end - start).max(1) as u64,
+            _ => return previous.bits,
+        };
+        let gaps = previous.index.saturating_sub(window_start.index).max(1);
+        let expected_ms = (self.target_block_time_ms * gaps).max(1);
+
+        // Slow windows raise the target (less work), fast windows lower it
+        let factor = self.max_adjustment_factor.max(1);
+        let actual_ms = actual_ms.clamp(expected_ms / factor, expected_ms.saturating_mul(factor));
+
+        self.limit(scale_compact(previous.bits, actual_ms, expected_ms))
+    }
+
+    pub fn encode_fields(&self, encoder: &mut Encoder) {
+        encoder.put_u32(self.initial_bits);
+        encoder.put_u64(self.target_block_time_ms);
+        encoder.put_u64(self.retarget_interval);
+        encoder.put_u64(self.max_adjustment_factor);
+        encoder.put_u32(self.pow_limit_bits);
+        encoder.put_u32(self.action_bits);
+    }
+
+    pub fn decode_fields(decoder: &mut Decoder) -> Result<DifficultyConfig, DecodeError> {
+        Ok(DifficultyConfig {
+            initial_bits: decoder.get_u32()?,
+            target_block_time_ms: decoder.get_u64()?,
+            retarget_interval: decoder.get_u64()?,
+            max_adjustment_factor: decoder.get_u64()?,
+            pow_limit_bits: decoder.get_u32()?,
+            action_bits: decoder.get_u32()?,
+        })
+    }
+
+    fn limit(&self, bits: u32) -> u32 {
+        if is_easier_than(bits, self.pow_limit_bits) {
+            self.pow_limit_bits
+        } else {
+            bits
+        }
+    }
+}
+
+impl Block {
+    pub fn timestamp_millis(&self) -> Option<i64> {
+        DateTime::parse_from_rfc3339(&self.timestamp)
+            .ok()
+            .map(|timestamp| timestamp.timestamp_millis())
+    }
+
+    pub fn set_timestamp_millis(&mut self, millis: i64) {
+        self.timestamp = Utc.timestamp_millis_opt(millis)
+            .single()
+            .unwrap_or_default()
+            .to_rfc3339();
+    }
+}
+
+// Median timestamp of the last MEDIAN_TIME_SPAN blocks; a new block must be
+// later than this
+pub fn median_time_past(blocks: &[Block]) -> i64 {
+    let mut times: Vec<i64> = blocks[blocks.len().saturating_sub(MEDIAN_TIME_SPAN)..]
+        .iter()
+        .filter_map(|block| block.timestamp_millis())
+        .collect();
+    if times.is_empty() {
+        return i64::MIN;
+    }
+    times.sort_unstable();
+    times[times.len() / 2]
+}
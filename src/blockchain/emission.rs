@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use crate::blockchain::{AccountStateIndex, BitnunBlockchain, Transaction, TransactionKind};
+use crate::blockchain::encoding::{DecodeError, Decoder, Encoder};
+
+// How new coins enter circulation after genesis. The block subsidy starts at
+// the chain's mining_reward and halves every halving_interval blocks; action
+// mining draws on a separate budget per epoch that halves alongside it.
+// Nothing may be minted past max_supply, genesis included.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmissionSchedule {
+    pub max_supply: u64,
+    pub halving_interval: u64, // Blocks
+    pub action_epoch_length: u64, // Blocks per action-mining budget
+    pub action_budget_per_epoch: u64,
+}
+
+impl Default for EmissionSchedule {
+    fn default() -> Self {
+        EmissionSchedule {
+            max_supply: 1_000_000_000, // 10M BTN, genesis included
+            halving_interval: 210_000,
+            action_epoch_length: 1_000,
+            action_budget_per_epoch: 100_000, // 1000 BTN
+        }
+    }
+}
+
+impl EmissionSchedule {
+    pub fn halvings(&self, height: u64) -> u64 {
+        height / self.halving_interval.max(1)
+    }
+
+    // Subsidy scheduled for the block at `height`, before the cap applies
+    pub fn scheduled_subsidy(&self, initial_reward: u64, height: u64) -> u64 {
+        halve(initial_reward, self.halvings(height))
+    }
+
+    pub fn epoch_start(&self, height: u64) -> u64 {
+        let length = self.action_epoch_length.max(1);
+        height - height % length
+    }
+
+    // Action-mining budget of the epoch containing `height`
+    pub fn action_budget(&self, height: u64) -> u64 {
+        halve(self.action_budget_per_epoch, self.halvings(self.epoch_start(height)))
+    }
+
+    pub fn encode_fields(&self, encoder: &mut Encoder) {
+        encoder.put_u64(self.max_supply);
+        encoder.put_u64(self.halving_interval);
+        encoder.put_u64(self.action_epoch_length);
+        encoder.put_u64(self.action_budget_per_epoch);
+    }
+
+    pub fn decode_fields(decoder: &mut Decoder) -> Result<EmissionSchedule, DecodeError> {
+        Ok(EmissionSchedule {
+            max_supply: decoder.get_u64()?,
+            halving_interval: decoder.get_u64()?,
+            action_epoch_length: decoder.get_u64()?,
+            action_budget_per_epoch: decoder.get_u64()?,
+        })
+    }
+}
+
+fn halve(amount: u64, halvings: u64) -> u64 {
+    if halvings >= 64 {
+        0
+    } else {
+        amount >> halvings
+    }
+}
+
+pub fn is_action_mint(transaction: &Transaction) -> bool {
+    matches!(transaction.kind, TransactionKind::ActionReward { .. })
+}
+
+impl AccountStateIndex {
+    // Every coin is held as a balance or a stake; fees only move coins
+    pub fn circulating_supply(&self) -> u64 {
+        self.supply
+    }
+}
+
+impl BitnunBlockchain {
+    // Subsidy the block at `height` must pay, given the supply before it
+    pub fn block_subsidy(&self, height: u64, supply: u64) -> u64 {
+        let remaining = self.emission.max_supply.saturating_sub(supply);
+        self.emission.scheduled_subsidy(self.mining_reward, height).min(remaining)
+    }
+
+    // Action mining the next block may still include
+    pub(crate) fn action_mint_room(&self, supply: u64, subsidy: u64) -> u64 {
+        let height = self.blocks.len() as u64;
+        let minted = self.state.action_minted_in(self.emission.epoch_start(height));
+        let budget = self.emission.action_budget(height).saturating_sub(minted);
+        let remaining = self.emission.max_supply.saturating_sub(supply).saturating_sub(subsidy);
+        budget.min(remaining)
+    }
+
+    // Coins in circulation after the block at `height`; None for blocks
+    // below a snapshot
+    pub fn circulating_supply_at(&self, height: u64) -> Option<u64> {
+        if height < self.pruned_height.saturating_sub(1) || height >= self.blocks.len() as u64 {
+            return None;
+        }
+        Some(self.state_at(height as usize + 1).circulating_supply())
+    }
+
+    pub fn emission(&self) -> &EmissionSchedule {
+        &self.emission
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::{GenesisConfig, EASIEST_BITS};
+    use crate::consensus::ProofOfAction;
+    use crate::runtime;
+
+    fn action_mint(user: &str, timestamp: f64) -> Transaction {
+        let proof = ProofOfAction::new(user, "form_submit", timestamp, EASIEST_BITS);
+        Transaction::new_action_mining(user, "form_submit", proof.calculate_reward(), &proof.to_json())
+    }
+
+    fn held_supply(state: &AccountStateIndex) -> u64 {
+        state.accounts
+            .values()
+            .fold(0u64, |total, account| total.saturating_add(account.balance).saturating_add(account.stake))
+    }
+
+    #[test]
+    fn default_cap_is_ten_million_btn() {
+        let config = GenesisConfig::mainnet();
+        assert_eq!(config.emission.max_supply, 1_000_000_000);
+        assert!(config.initial_supply() < config.emission.max_supply);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn running_totals_follow_every_block() {
+        let mut config = GenesisConfig::dev();
+        config.emission.action_epoch_length = 2;
+        let mut chain = BitnunBlockchain::from_genesis_config(&config).unwrap();
+        assert_eq!(chain.state.circulating_supply(), held_supply(&chain.state));
+
+        let first = action_mint("btnactor", runtime::now_millis());
+        chain.add_transaction(&first).unwrap();
+        chain.mine_pending_transactions("btnminer"); // Height 1, epoch 0
+        assert_eq!(chain.state.action_epoch, 0);
+        assert_eq!(chain.state.epoch_action_minted, first.amount);
+
+        let second = action_mint("btnactor", runtime::now_millis() + 1.0);
+        chain.add_transaction(&second).unwrap();
+        chain.mine_pending_transactions("btnminer"); // Height 2 opens epoch 2
+        assert_eq!(chain.state.action_epoch, 2);
+        assert_eq!(chain.state.epoch_action_minted, second.amount);
+        assert_eq!(chain.state.action_minted_in(0), 0);
+
+        assert_eq!(chain.state.circulating_supply(), held_supply(&chain.state));
+        assert_eq!(chain.circulating_supply_at(2), Some(chain.state.circulating_supply()));
+        assert_eq!(chain.replay_chain().unwrap(), chain.state);
+    }
+}
@@ -0,0 +1,433 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Sha256, Digest};
+use crate::blockchain::{Block, BlockHeader, Transaction};
+
+// Every top-level encoding starts with the format version and a kind tag.
+// Integers are big-endian, variable-length fields carry a u32 length prefix
+// and optional fields a 0/1 presence byte.
+pub const ENCODING_VERSION: u8 = 1;
+
+pub const KIND_TRANSACTION: u8 = 0x01;
+pub const KIND_TRANSACTION_SIGNING: u8 = 0x02;
+pub const KIND_BLOCK_HEADER: u8 = 0x03;
+pub const KIND_BLOCK: u8 = 0x04;
+pub const KIND_CHAIN_EXPORT: u8 = 0x05;
+pub const KIND_STATE_SNAPSHOT: u8 = 0x06;
+pub const KIND_GENESIS_CONFIG: u8 = 0x07;
+pub const KIND_ACCOUNT_STATE: u8 = 0x08;
+pub const KIND_STATE_SUMMARY: u8 = 0x09;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    UnexpectedEnd,
+    UnsupportedVersion(u8),
+    UnsupportedExportVersion(u32), // Of a chain export or snapshot
+    UnexpectedKind { expected: u8, found: u8 },
+    InvalidPresenceFlag(u8),
+    InvalidUtf8,
+    TrailingBytes(usize),
+    RecordTooLarge(u32),
+    Io(String),
+    UnknownTransactionKind(String),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Encoder {
+    buffer: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new(kind: u8) -> Self {
+        Encoder {
+            buffer: vec![ENCODING_VERSION, kind],
+        }
+    }
+
+    pub fn put_u8(&mut self, value: u8) {
+        self.buffer.push(value);
+    }
+
+    pub fn put_u32(&mut self, value: u32) {
+        self.buffer.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn put_u64(&mut self, value: u64) {
+        self.buffer.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn put_bytes(&mut self, value: &[u8]) {
+        self.put_u32(value.len() as u32);
+        self.buffer.extend_from_slice(value);
+    }
+
+    pub fn put_str(&mut self, value: &str) {
+        self.put_bytes(value.as_bytes());
+    }
+
+    pub fn put_opt_str(&mut self, value: Option<&str>) {
+        match value {
+            Some(value) => {
+                self.put_u8(1);
+                self.put_str(value);
+            }
+            None => self.put_u8(0),
+        }
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.buffer
+    }
+}
+
+pub struct Decoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(bytes: &'a [u8], kind: u8) -> Result<Self, DecodeError> {
+        let mut decoder = Decoder { bytes, position: 0 };
+
+        let version = decoder.get_u8()?;
+        if version != ENCODING_VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+
+        let found = decoder.get_u8()?;
+        if found != kind {
+            return Err(DecodeError::UnexpectedKind { expected: kind, found });
+        }
+
+        Ok(decoder)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        let end = self.position.checked_add(len).ok_or(DecodeError::UnexpectedEnd)?;
+        if end > self.bytes.len() {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    pub fn get_u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn get_u32(&mut self) -> Result<u32, DecodeError> {
+        let bytes: [u8; 4] = self.take(4)?.try_into().unwrap();
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    pub fn get_u64(&mut self) -> Result<u64, DecodeError> {
+        let bytes: [u8; 8] = self.take(8)?.try_into().unwrap();
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    pub fn get_bytes(&mut self) -> Result<&'a [u8], DecodeError> {
+        let len = self.get_u32()? as usize;
+        self.take(len)
+    }
+
+    pub fn get_string(&mut self) -> Result<String, DecodeError> {
+        let bytes = self.get_bytes()?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidUtf8)
+    }
+
+    pub fn get_opt_string(&mut self) -> Result<Option<String>, DecodeError> {
+        match self.get_u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(self.get_string()?)),
+            flag => Err(DecodeError::InvalidPresenceFlag(flag)),
+        }
+    }
+
+    pub fn finish(self) -> Result<(), DecodeError> {
+        let remaining = self.bytes.len() - self.position;
+        if remaining > 0 {
+            return Err(DecodeError::TrailingBytes(remaining));
+        }
+        Ok(())
+    }
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(sha256_digest(bytes))
+}
+
+pub fn sha256_digest(bytes: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(bytes);
+    hasher.finalize().into()
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EncodingTestVector {
+    pub name: String,
+    pub encoding: String, // Hex-encoded canonical bytes
+    pub hash: String, // Hex-encoded SHA-256 of the canonical bytes
+    pub round_trip: Option<bool>, // Decoding and re-encoding reproduced the same bytes, if decodable
+}
+
+// Fixed fixtures that external tooling can re-encode and hash independently
+pub fn encoding_test_vectors() -> Vec<EncodingTestVector> {
+    let mut transfer = Transaction::new("btnsender", "btnrecipient", 1250);
+    transfer.id = "00000000-0000-4000-8000-000000000001".to_string();
+    transfer.timestamp = "2024-01-01T00:00:00+00:00".to_string();
+    transfer.nonce = 7;
+    transfer.public_key = Some("11".repeat(32));
+    transfer.signature = Some("22".repeat(64));
+
+    let mut reward = Transaction::new_mining_reward("btnminer", 1000);
+    reward.id = "00000000-0000-4000-8000-000000000002".to_string();
+    reward.timestamp = "2024-01-01T00:00:00+00:00".to_string();
+
+    let mut block = Block::new(1, "0".repeat(64), vec![transfer.clone(), reward.clone()]);
+    block.timestamp = "2024-01-01T00:01:00+00:00".to_string();
+    block.nonce = 42;
+    block.bits = 0x1f00ffff;
+    block.hash = block.calculate_hash();
+
+    let transfer_bytes = transfer.to_bytes();
+    let reward_bytes = reward.to_bytes();
+    let header_bytes = block.header_bytes();
+    let block_bytes = block.to_bytes();
+
+    vec![
+        test_vector("transaction_transfer", &transfer_bytes,
+            Some(Transaction::decode(&transfer_bytes).map(|tx| tx.to_bytes()))),
+        test_vector("transaction_signing_payload", &transfer.signing_payload(), None),
+        test_vector("transaction_mining_reward", &reward_bytes,
+            Some(Transaction::decode(&reward_bytes).map(|tx| tx.to_bytes()))),
+        test_vector("block_header", &header_bytes,
+            Some(BlockHeader::decode(&header_bytes).map(|header| header.to_bytes()))),
+        test_vector("block", &block_bytes,
+            Some(Block::decode(&block_bytes).map(|block| block.to_bytes()))),
+    ]
+}
+
+#[cfg(feature = "wasm")]
+#[wasm_bindgen]
+pub fn get_encoding_test_vectors() -> JsValue {
+    serde_wasm_bindgen::to_value(&encoding_test_vectors()).unwrap()
+}
+
+fn test_vector(
+    name: &str,
+    bytes: &[u8],
+    reencoded: Option<Result<Vec<u8>, DecodeError>>,
+) -> EncodingTestVector {
+    EncodingTestVector {
+        name: name.to_string(),
+        encoding: hex::encode(bytes),
+        hash: sha256_hex(bytes),
+        round_trip: reencoded.map(|result| result.map(|reencoded| reencoded == bytes).unwrap_or(false)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::{
+        AccountState, AccountStateIndex, CoSignature, DifficultyConfig, EmissionSchedule, GenesisAllocation, GenesisValidator,
+        LockedFunds, MultisigPolicy, StateSnapshot, CHAIN_EXPORT_VERSION,
+    };
+
+    // Expected bytes were produced by a separate implementation of the format,
+    // so a change to any layout shows up here rather than only as a new hash
+    const HEADER_HEX: &str = concat!(
+        "0103000000000000000100000019323032342d30312d30315430303a30313a30302b3030",
+        "3a3030000000043030666600000004313165651f00ffff000000000000002a0000000432",
+        "326464",
+    );
+    const HEADER_HASH: &str = "1c380e97bcd86337c8a56edc2d7e9adb3e647d24ebfbcfa4b1d107abccb13166";
+
+    const TRANSACTION_HEX: &str = concat!(
+        "01010000000474782d310000000962746e73656e6465720000000c62746e726563697069",
+        "656e7400000000000004e200000019323032342d30312d30315430303a30303a30302b30",
+        "303a3030000000087472616e73666572000000000000000a000000000000000300000000",
+        "000000070000000e6269746e756e2d6d61696e6e65740100000002313100000000000000",
+        "0000000000000000000100000002323200000001000000023333000000023434",
+    );
+    const TRANSACTION_HASH: &str = "4d96e26fd892f11c49078daa03d6a2c24c9e6a7ca01e8775cb233e535eb37a82";
+
+    const BLOCK_HEX: &str = concat!(
+        "0104000000840103000000000000000100000019323032342d30312d30315430303a3031",
+        "3a30302b30303a3030000000043030666600000040353437386665343961613538623862",
+        "656466623434343364343736313531393565313937356535303830333235393364306335",
+        "6534313733333863346665613600000000000000000000002a0000000130000000010000",
+        "00b001010000000474782d310000000962746e73656e6465720000000c62746e72656369",
+        "7069656e7400000000000004e200000019323032342d30312d30315430303a30303a3030",
+        "2b30303a3030000000087472616e73666572000000000000000a00000000000000030000",
+        "0000000000070000000e6269746e756e2d6d61696e6e6574010000000231310000000000",
+        "00000000000000000000000100000002323200000001000000023333000000023434",
+    );
+    const BLOCK_HASH: &str = "c17920acfe844b2728f6dc57acb77ae21be1ed921232c7eec23e9479c3e576eb";
+
+    const ACCOUNT_HEX: &str = concat!(
+        "010800000000000004e20000000000000002000000000000000500000000000000640000",
+        "0001000000056172742d3100000000000000010000000100000000000000320000000000",
+        "00000a0000000000000000010000000100000001000000026162",
+    );
+    const ACCOUNT_HASH: &str = "9ae7b66e37178e6d21cf2d57dc0d62231799026d37eb7975cb607881529ad7cd";
+
+    const SNAPSHOT_HEX: &str = concat!(
+        "0106000000010000000a6269746e756e2d64657600000000000003e8000000003b9aca00",
+        "0000000000033450000000000000006400000000000013882000ffff0000000000002710",
+        "000000000000000a00000000000000042100ffff1f00ffff000000010000000c62746e76",
+        "616c696461746f720000000000002710000000010000000962746e686f6c646572000000",
+        "00000003e8000000010000004b0103000000000000000100000019323032342d30312d30",
+        "315430303a30313a30302b30303a3030000000043030666600000004313165651f00ffff",
+        "000000000000002a00000004323264640000000000000001000000010000000962746e68",
+        "6f6c64657200000062010800000000000004e20000000000000002000000000000000500",
+        "0000000000006400000001000000056172742d3100000000000000010000000100000000",
+        "00000032000000000000000a000000000000000001000000010000000100000002616200",
+        "00000100000008616374696f6e2d31000000000000054600000000000000000000000000",
+        "0000fa",
+    );
+    const SNAPSHOT_HASH: &str = "522bbf10d5435a90892d7d81c26a92b8a7c3d19031d4741b9598033961fb5696";
+
+    fn header() -> BlockHeader {
+        BlockHeader {
+            index: 1,
+            timestamp: "2024-01-01T00:01:00+00:00".to_string(),
+            previous_hash: "00ff".to_string(),
+            merkle_root: "11ee".to_string(),
+            bits: 0x1f00ffff,
+            nonce: 42,
+            state_root: "22dd".to_string(),
+        }
+    }
+
+    fn transaction() -> Transaction {
+        let mut transaction = Transaction::new("btnsender", "btnrecipient", 1250);
+        transaction.id = "tx-1".to_string();
+        transaction.timestamp = "2024-01-01T00:00:00+00:00".to_string();
+        transaction.fee = 3;
+        transaction.nonce = 7;
+        transaction.public_key = Some("11".to_string());
+        transaction.signature = Some("22".to_string());
+        transaction.co_signatures.push(CoSignature {
+            public_key: "33".to_string(),
+            signature: "44".to_string(),
+        });
+        transaction
+    }
+
+    fn block() -> Block {
+        let mut block = Block::new(1, "00ff".to_string(), vec![transaction()]);
+        block.timestamp = "2024-01-01T00:01:00+00:00".to_string();
+        block.nonce = 42;
+        block.hash = block.calculate_hash();
+        block
+    }
+
+    fn account() -> AccountState {
+        AccountState {
+            balance: 1250,
+            nonce: 2,
+            last_activity: 5,
+            stake: 100,
+            token_balances: [("art-1".to_string(), 1)].into_iter().collect(),
+            locked: vec![LockedFunds { amount: 50, unlock_height: 10, unlock_time: 0 }],
+            multisig: Some(MultisigPolicy { threshold: 1, public_keys: vec!["ab".to_string()] }),
+        }
+    }
+
+    fn snapshot() -> StateSnapshot {
+        let mut state = AccountStateIndex::new();
+        state.height = 1;
+        state.insert_account("btnholder".to_string(), account());
+        state.spent_action_proofs.insert("action-1".to_string());
+        state.supply = 1350;
+        state.epoch_action_minted = 250;
+
+        StateSnapshot {
+            format_version: CHAIN_EXPORT_VERSION,
+            chain_id: "bitnun-dev".to_string(),
+            mining_reward: 1000,
+            emission: EmissionSchedule {
+                max_supply: 1_000_000_000,
+                halving_interval: 210_000,
+                action_epoch_length: 100,
+                action_budget_per_epoch: 5000,
+            },
+            difficulty: DifficultyConfig {
+                initial_bits: 0x2000ffff,
+                target_block_time_ms: 10_000,
+                retarget_interval: 10,
+                max_adjustment_factor: 4,
+                pow_limit_bits: 0x2100ffff,
+                action_bits: 0x1f00ffff,
+            },
+            genesis_validators: vec![GenesisValidator { address: "btnvalidator".to_string(), stake: 10_000 }],
+            genesis_allocations: vec![GenesisAllocation { address: "btnholder".to_string(), amount: 1_000 }],
+            headers: vec![header()],
+            state,
+        }
+    }
+
+    fn assert_known_answer(bytes: &[u8], expected_hex: &str, expected_hash: &str) {
+        assert_eq!(hex::encode(bytes), expected_hex);
+        assert_eq!(sha256_hex(bytes), expected_hash);
+    }
+
+    #[test]
+    fn header_encoding_is_stable() {
+        let header = header();
+        let bytes = header.to_bytes();
+        assert_known_answer(&bytes, HEADER_HEX, HEADER_HASH);
+        assert_eq!(header.calculate_hash(), HEADER_HASH);
+        assert_eq!(BlockHeader::decode(&bytes).unwrap(), header);
+    }
+
+    #[test]
+    fn transaction_encoding_is_stable() {
+        let transaction = transaction();
+        let bytes = transaction.to_bytes();
+        assert_known_answer(&bytes, TRANSACTION_HEX, TRANSACTION_HASH);
+        assert_eq!(transaction.hash(), TRANSACTION_HASH);
+        assert_eq!(Transaction::decode(&bytes).unwrap(), transaction);
+    }
+
+    #[test]
+    fn block_encoding_is_stable() {
+        let block = block();
+        let bytes = block.to_bytes();
+        assert_known_answer(&bytes, BLOCK_HEX, BLOCK_HASH);
+        assert_eq!(Block::decode(&bytes).unwrap(), block);
+    }
+
+    #[test]
+    fn account_encoding_is_stable() {
+        let account = account();
+        let bytes = account.to_bytes();
+        assert_known_answer(&bytes, ACCOUNT_HEX, ACCOUNT_HASH);
+        assert_eq!(AccountState::decode(&bytes).unwrap(), account);
+    }
+
+    #[test]
+    fn snapshot_encoding_is_stable() {
+        let snapshot = snapshot();
+        let bytes = snapshot.to_bytes();
+        assert_known_answer(&bytes, SNAPSHOT_HEX, SNAPSHOT_HASH);
+        assert_eq!(StateSnapshot::decode(&bytes).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn decoding_rejects_other_kinds_and_trailing_bytes() {
+        let bytes = header().to_bytes();
+        assert_eq!(
+            Transaction::decode(&bytes),
+            Err(DecodeError::UnexpectedKind { expected: KIND_TRANSACTION, found: KIND_BLOCK_HEADER }),
+        );
+
+        let mut padded = bytes.clone();
+        padded.push(0);
+        assert_eq!(BlockHeader::decode(&padded), Err(DecodeError::TrailingBytes(1)));
+    }
+}
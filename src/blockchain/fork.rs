@@ -0,0 +1,377 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use crate::blockchain::{is_action_mint, AccountStateIndex, BitnunBlockchain, Block, ChainViolation, PoolEntry, StateUndo};
+use crate::console_log;
+use crate::error::{BitnunResult, ChainError};
+
+// Newest blocks kept with undo data, and whole when a chain is opened from a
+// store, so a branch forking among them replays only its own blocks
+pub const REORG_WINDOW: u64 = 100;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BlockStatus {
+    Duplicate,
+    Extended, // Appended to the active chain
+    SideBranch, // Stored, but the active chain still has more work
+    Reorganized(ReorgEvent),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReorgEvent {
+    pub fork_height: u64, // Index of the last block both chains share
+    pub fork_hash: String,
+    pub depth: u64, // Blocks rolled back from the old chain
+    pub applied: u64, // Blocks applied from the new chain
+    pub old_tip: String,
+    pub new_tip: String,
+    pub returned_transactions: usize, // Orphaned transactions put back in the pool
+}
+
+impl BitnunBlockchain {
+    // Accepts a block mined elsewhere. Blocks on other branches are kept, and
+    // the chain switches to whichever branch has the most cumulative work.
+    pub fn accept_block(&mut self, block: Block) -> BitnunResult<BlockStatus> {
+        if self.is_known_block(&block.hash) {
+            return Ok(BlockStatus::Duplicate);
+        }
+
+        if block.previous_hash == self.get_latest_block().hash {
+            self.extend_chain(block)?;
+            return Ok(BlockStatus::Extended);
+        }
+
+        let (fork_position, mut branch) = self.branch_to(&block.previous_hash)
+            .ok_or_else(|| ChainError::UnknownParent(block.previous_hash.clone()))?;
+        branch.push(block.clone());
+
+        // Replaying the branch needs the state at the fork point
+        if fork_position + 1 < self.pruned_height as usize {
+            return Err(ChainError::ForkBelowPrunedHeight {
+                fork_height: fork_position as u64,
+                pruned_height: self.pruned_height,
+            }.into());
+        }
+
+        let mut candidate = self.blocks[..=fork_position].to_vec();
+        candidate.extend(branch);
+
+        let branch_work = candidate.iter().fold(0u128, |total, block| total.saturating_add(block.work()));
+        if branch_work <= self.chain_work() {
+            let previous_blocks = &candidate[..candidate.len() - 1];
+            self.check_block_header(previous_blocks, &block).map_err(ChainError::InvalidBlock)?;
+            console_log!("Stored side block {} at index {}", block.hash, block.index);
+            self.persist_side_block(&block)?;
+            self.side_blocks.insert(block.hash.clone(), block);
+            return Ok(BlockStatus::SideBranch);
+        }
+
+        let (state, branch_undo) = self.replay_branch(&candidate, fork_position).map_err(ChainError::InvalidBlock)?;
+        Ok(BlockStatus::Reorganized(self.reorganize(candidate, state, branch_undo, fork_position)))
+    }
+
+    pub fn reorg_events(&self) -> &[ReorgEvent] {
+        &self.reorg_events
+    }
+
+    pub fn side_block_count(&self) -> usize {
+        self.side_blocks.len()
+    }
+
+    fn is_known_block(&self, hash: &str) -> bool {
+        self.side_blocks.contains_key(hash) || self.index.height_of(hash).is_some()
+    }
+
+    // Walks side blocks back from `tip_hash` to the active chain; returns the
+    // position of the fork point there and the side blocks after it, in order
+    fn branch_to(&self, tip_hash: &str) -> Option<(usize, Vec<Block>)> {
+        let mut branch = Vec::new();
+        let mut hash = tip_hash.to_string();
+
+        loop {
+            if let Some(height) = self.index.height_of(&hash) {
+                branch.reverse();
+                return Some((height as usize, branch));
+            }
+            let block = self.side_blocks.get(&hash)?;
+            hash = block.previous_hash.clone();
+            branch.push(block.clone());
+        }
+    }
+
+    // Checks only the blocks after the fork point, on the state rolled back to it
+    fn replay_branch(
+        &self,
+        candidate: &[Block],
+        fork_position: usize,
+    ) -> Result<(AccountStateIndex, Vec<StateUndo>), ChainViolation> {
+        let mut state = self.state_after(fork_position);
+        let mut seen_transactions = HashSet::new();
+        let mut branch_undo = Vec::new();
+
+        for position in fork_position + 1..candidate.len() {
+            let block = &candidate[position];
+            self.check_block_header(&candidate[..position], block)?;
+            self.check_not_included(block, fork_position as u64 + 1)?;
+            branch_undo.push(state.undo_for(block));
+            self.check_block_transactions(&mut state, &mut seen_transactions, &candidate[..position], block)?;
+        }
+
+        Ok((state, branch_undo))
+    }
+
+    // State after the active chain's block at `position`: the tip's state
+    // rolled back where undo data reaches, else rebuilt from the pruned base
+    fn state_after(&self, position: usize) -> AccountStateIndex {
+        let depth = self.blocks.len() - 1 - position;
+        if depth > self.undo_log.len() {
+            return self.state_at(position + 1);
+        }
+
+        let mut state = self.state.clone();
+        for undo in self.undo_log.iter().rev().take(depth) {
+            state.revert(undo);
+        }
+        state
+    }
+
+    // Keeps undo data for the newest REORG_WINDOW blocks
+    pub(crate) fn record_undo(&mut self, undo: StateUndo) {
+        self.undo_log.push_back(undo);
+        if self.undo_log.len() > REORG_WINDOW as usize {
+            self.undo_log.pop_front();
+        }
+    }
+
+    // Forgets side blocks no reorganization can reach any more: those that
+    // would fork more than REORG_WINDOW below the tip, or below the pruned height
+    pub(crate) fn prune_side_blocks(&mut self) {
+        let floor = (self.blocks.len() as u64).saturating_sub(REORG_WINDOW).max(self.pruned_height);
+        self.side_blocks.retain(|_, block| block.index >= floor);
+    }
+
+    pub(crate) fn extend_chain(&mut self, block: Block) -> BitnunResult<()> {
+        self.check_block_header(&self.blocks, &block).map_err(ChainError::InvalidBlock)?;
+
+        self.check_not_included(&block, self.blocks.len() as u64).map_err(ChainError::InvalidBlock)?;
+
+        let mut state = self.state.clone();
+        let mut seen_transactions = HashSet::new();
+        self.check_block_transactions(&mut state, &mut seen_transactions, &self.blocks, &block)
+            .map_err(ChainError::InvalidBlock)?;
+
+        console_log!("Accepted block {} at index {}", block.hash, block.index);
+        self.append_block(block);
+        Ok(())
+    }
+
+    fn reorganize(
+        &mut self,
+        candidate: Vec<Block>,
+        state: AccountStateIndex,
+        branch_undo: Vec<StateUndo>,
+        fork_position: usize,
+    ) -> ReorgEvent {
+        let old_tip = self.get_latest_block().hash.clone();
+        let included: HashSet<String> = candidate[fork_position + 1..]
+            .iter()
+            .flat_map(|block| block.transactions.iter().map(|tx| tx.id.clone()))
+            .collect();
+
+        // Detached blocks become a side branch, and the new branch leaves side storage
+        let detached = self.blocks.split_off(fork_position + 1);
+        for block in detached.iter().rev() {
+            self.index.remove_block(block);
+        }
+        for block in &candidate[fork_position + 1..] {
+            self.index.add_block(block);
+        }
+        for block in &candidate[fork_position + 1..] {
+            self.side_blocks.remove(&block.hash);
+        }
+
+        // Orphaned transactions go back ahead of the existing pool so nonces
+        // stay ordered; action mints return too, only the rewards are dropped
+        let mut pool: Vec<PoolEntry> = detached
+            .iter()
+            .flat_map(|block| block.transactions.iter())
+            .filter(|tx| (!tx.is_system() || is_action_mint(tx)) && !included.contains(&tx.id))
+            .map(|tx| PoolEntry::new(tx.clone()))
+            .collect();
+        let returned_transactions = pool.len();
+        pool.extend(self.pending_transactions
+            .take()
+            .into_iter()
+            .filter(|entry| !included.contains(&entry.transaction.id)));
+
+        let depth = detached.len() as u64;
+        let detached_accounts: HashSet<String> = detached
+            .iter()
+            .flat_map(|block| block.transactions.iter())
+            .flat_map(|tx| std::iter::once(tx.from_address.as_str()).chain(tx.recipients()).map(str::to_string))
+            .collect();
+        let applied = (candidate.len() - fork_position - 1) as u64;
+        for block in detached {
+            self.side_blocks.insert(block.hash.clone(), block);
+        }
+        self.prune_side_blocks();
+
+        self.blocks = candidate;
+        self.state = state;
+        self.undo_log.truncate(self.undo_log.len().saturating_sub(depth as usize));
+        for undo in branch_undo {
+            self.record_undo(undo);
+        }
+        for entry in pool {
+            self.pending_transactions.push(entry);
+        }
+        self.evict_invalid_pending();
+        self.carbon_offset = (self.carbon_offset + applied * 10).saturating_sub(depth * 10);
+        self.mining_bits = self.difficulty_config.next_bits(&self.blocks);
+        if let Err(error) = self.persist_blocks_from(fork_position + 1, &detached_accounts) {
+            console_log!("Failed to persist reorganization: {}", error);
+        }
+
+        let event = ReorgEvent {
+            fork_height: fork_position as u64,
+            fork_hash: self.blocks[fork_position].hash.clone(),
+            depth,
+            applied,
+            old_tip,
+            new_tip: self.get_latest_block().hash.clone(),
+            returned_transactions,
+        };
+        console_log!("Reorganized at height {}: rolled back {} blocks, applied {}",
+            event.fork_height, event.depth, event.applied);
+        self.reorg_events.push(event.clone());
+        event
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::{GenesisConfig, Transaction, EASIEST_BITS};
+    use crate::consensus::ProofOfAction;
+    use crate::runtime;
+    use crate::blockchain::test_support::{dev_keypair, signed};
+
+    fn chains() -> (BitnunBlockchain, BitnunBlockchain) {
+        let dev = dev_keypair();
+        let config = GenesisConfig::dev();
+        let mut chain = BitnunBlockchain::from_genesis_config(&config).unwrap();
+        let mut rival = BitnunBlockchain::from_genesis_config(&config).unwrap();
+
+        let nft = signed(Transaction::new_nft_mint(&dev.address(), "btncollector", "art-1", "ipfs://art-1"), &dev);
+        chain.add_transaction(&nft).unwrap();
+        for _ in 0..3 {
+            chain.mine_pending_transactions("btnminer");
+        }
+
+        let transfer = signed(Transaction::new(&dev.address(), "btnpayee", 700), &dev);
+        rival.add_transaction(&transfer).unwrap();
+        rival.mine_pending_transactions("btnrival");
+        for _ in 0..3 {
+            rival.mine_pending_transactions("btnrival");
+        }
+        (chain, rival)
+    }
+
+    #[test]
+    fn reorganization_rolls_back_to_the_fork_point() {
+        let (mut chain, rival) = chains();
+        let mut status = BlockStatus::Duplicate;
+        for block in &rival.blocks[1..] {
+            status = chain.accept_block(block.clone()).unwrap();
+        }
+
+        match status {
+            BlockStatus::Reorganized(event) => {
+                assert_eq!(event.fork_height, 0);
+                assert_eq!(event.depth, 3);
+                assert_eq!(event.applied, 4);
+            }
+            other => panic!("expected a reorganization, got {:?}", other),
+        }
+        assert_eq!(chain.state, rival.state);
+        assert_eq!(chain.state.nft_owner("art-1"), None);
+        assert_eq!(chain.replay_chain().unwrap(), chain.state);
+        assert_eq!(chain.undo_log.len(), 4);
+    }
+
+    #[test]
+    fn orphaned_action_mints_return_to_the_pool() {
+        let config = GenesisConfig::dev();
+        let mut chain = BitnunBlockchain::from_genesis_config(&config).unwrap();
+        let mut rival = BitnunBlockchain::from_genesis_config(&config).unwrap();
+
+        // Dated ahead so it is still inside the window on the rival branch
+        let proof = ProofOfAction::new("btnactor", "form_submit", runtime::now_millis() + 60_000.0, EASIEST_BITS);
+        let mint = Transaction::new_action_mining("btnactor", "form_submit", proof.calculate_reward(), &proof.to_json());
+        chain.add_transaction(&mint).unwrap();
+        chain.mine_pending_transactions("btnminer");
+        let orphaned_reward = chain.get_latest_block().transactions.last().unwrap().id.clone();
+        for _ in 0..2 {
+            rival.mine_pending_transactions("btnrival");
+        }
+
+        let mut status = BlockStatus::Duplicate;
+        for block in &rival.blocks[1..] {
+            status = chain.accept_block(block.clone()).unwrap();
+        }
+        match status {
+            BlockStatus::Reorganized(event) => assert_eq!(event.returned_transactions, 1),
+            other => panic!("expected a reorganization, got {:?}", other),
+        }
+        let pending: Vec<&str> = chain.pending_transactions.transactions().map(|tx| tx.id.as_str()).collect();
+        assert_eq!(pending, vec![mint.id.as_str()]);
+        assert!(!pending.contains(&orphaned_reward.as_str()));
+        assert!(!chain.state.is_action_spent(&proof.action_id()));
+
+        chain.mine_pending_transactions("btnminer");
+        assert!(chain.state.is_action_spent(&proof.action_id()));
+        assert_eq!(chain.get_balance("btnactor"), proof.calculate_reward());
+    }
+
+    #[test]
+    fn side_blocks_out_of_reorg_reach_are_pruned() {
+        let mut config = GenesisConfig::dev();
+        config.difficulty.retarget_interval = 1_000; // Keep the target easy for fast blocks
+        let mut chain = BitnunBlockchain::from_genesis_config(&config).unwrap();
+        let mut rival = BitnunBlockchain::from_genesis_config(&config).unwrap();
+        for _ in 0..2 {
+            chain.mine_pending_transactions("btnminer");
+        }
+        rival.mine_pending_transactions("btnrival");
+        assert_eq!(chain.accept_block(rival.blocks[1].clone()).unwrap(), BlockStatus::SideBranch);
+
+        while chain.blocks.len() as u64 <= REORG_WINDOW + 1 {
+            assert_eq!(chain.side_block_count(), 1);
+            chain.mine_pending_transactions("btnminer");
+        }
+        assert_eq!(chain.side_block_count(), 0);
+        assert!(chain.index.height_of(&rival.blocks[1].hash).is_none());
+    }
+
+    #[test]
+    fn reorganization_without_undo_data_rebuilds_from_the_base() {
+        let (mut chain, rival) = chains();
+        chain.undo_log.clear();
+        for block in &rival.blocks[1..] {
+            chain.accept_block(block.clone()).unwrap();
+        }
+        assert_eq!(chain.state, rival.state);
+        assert_eq!(chain.undo_log.len(), 4);
+    }
+
+    #[test]
+    fn undo_restores_the_state_before_each_block() {
+        let (chain, _) = chains();
+        let mut state = chain.state.clone();
+        for position in (1..chain.blocks.len()).rev() {
+            state.revert(&chain.undo_log[position - 1]);
+            assert_eq!(state, chain.state_at(position));
+        }
+    }
+}
+
@@ -0,0 +1,303 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use crate::blockchain::{derive_address, AccountStateIndex, Block, DifficultyConfig, EmissionSchedule, KeyPair, Transaction, DEFAULT_CHAIN_ID, EASIEST_BITS};
+use crate::blockchain::encoding::{sha256_hex, Encoder, KIND_GENESIS_CONFIG};
+use crate::error::{BitnunResult, ConfigError};
+
+pub const GENESIS_TIMESTAMP: &str = "2024-01-01T00:00:00+00:00";
+
+// Well-known key funded by the dev preset; never use it outside local testing
+pub const DEV_SECRET_KEY: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+
+// Holder of the mainnet genesis allocation; its address is derived like any other
+pub const MAINNET_TREASURY_PUBLIC_KEY: &str = "ba1b870b56b7ee0c1b93f1f6bb7ea5b7a882b2e372fd75bee5d5008fbe75eb0a";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GenesisAllocation {
+    pub address: String,
+    pub amount: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GenesisValidator {
+    pub address: String,
+    pub stake: u64,
+}
+
+// Everything that determines block 0. Two nodes with the same config always
+// derive the same genesis hash.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GenesisConfig {
+    pub chain_id: String,
+    pub timestamp: String, // RFC 3339
+    pub allocations: Vec<GenesisAllocation>,
+    #[serde(default)]
+    pub validators: Vec<GenesisValidator>,
+    #[serde(default = "default_mining_reward")]
+    pub mining_reward: u64,
+    #[serde(default)]
+    pub difficulty: DifficultyConfig,
+    #[serde(default)]
+    pub emission: EmissionSchedule,
+}
+
+fn default_mining_reward() -> u64 {
+    1000 // 10.00 BTN in cents
+}
+
+impl GenesisConfig {
+    pub fn mainnet() -> GenesisConfig {
+        GenesisConfig {
+            chain_id: DEFAULT_CHAIN_ID.to_string(),
+            timestamp: GENESIS_TIMESTAMP.to_string(),
+            allocations: vec![GenesisAllocation {
+                address: derive_address(MAINNET_TREASURY_PUBLIC_KEY).expect("treasury key is valid"),
+                amount: 500000000, // 5M BTN; the rest of the cap is emitted
+            }],
+            validators: Vec::new(),
+            mining_reward: default_mining_reward(),
+            difficulty: DifficultyConfig::default(),
+            emission: EmissionSchedule::default(),
+        }
+    }
+
+    pub fn testnet() -> GenesisConfig {
+        GenesisConfig {
+            chain_id: "bitnun-testnet".to_string(),
+            difficulty: DifficultyConfig {
+                target_block_time_ms: 5_000,
+                ..DifficultyConfig::default()
+            },
+            ..GenesisConfig::mainnet()
+        }
+    }
+
+    // Funds DEV_SECRET_KEY and makes it the only validator; blocks are near-instant
+    pub fn dev() -> GenesisConfig {
+        let dev_address = KeyPair::from_secret_key(DEV_SECRET_KEY)
+            .expect("dev key is valid")
+            .address();
+
+        GenesisConfig {
+            chain_id: "bitnun-dev".to_string(),
+            timestamp: GENESIS_TIMESTAMP.to_string(),
+            allocations: vec![GenesisAllocation {
+                address: dev_address.clone(),
+                amount: 500000000,
+            }],
+            validators: vec![GenesisValidator {
+                address: dev_address,
+                stake: 10000,
+            }],
+            mining_reward: default_mining_reward(),
+            difficulty: DifficultyConfig {
+                initial_bits: EASIEST_BITS,
+                target_block_time_ms: 1_000,
+                action_bits: EASIEST_BITS,
+                ..DifficultyConfig::default()
+            },
+            emission: EmissionSchedule::default(),
+        }
+    }
+
+    pub fn preset(name: &str) -> BitnunResult<GenesisConfig> {
+        match name {
+            "mainnet" => Ok(GenesisConfig::mainnet()),
+            "testnet" => Ok(GenesisConfig::testnet()),
+            "dev" => Ok(GenesisConfig::dev()),
+            _ => Err(ConfigError::UnknownPreset(name.to_string()).into()),
+        }
+    }
+
+    pub fn from_json(json: &str) -> BitnunResult<GenesisConfig> {
+        let config: GenesisConfig = serde_json::from_str(json)
+            .map_err(|error| ConfigError::Parse(error.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_toml(source: &str) -> BitnunResult<GenesisConfig> {
+        let config: GenesisConfig = toml::from_str(source)
+            .map_err(|error| ConfigError::Parse(error.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("genesis config serializes")
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.chain_id.is_empty() {
+            return Err(ConfigError::EmptyChainId);
+        }
+
+        self.normalized_timestamp()?;
+
+        if self.allocations.is_empty() {
+            return Err(ConfigError::NoAllocations);
+        }
+
+        let mut supply = 0u64;
+        for allocation in &self.allocations {
+            if allocation.address.is_empty() || allocation.amount == 0 {
+                return Err(ConfigError::InvalidAllocation(allocation.address.clone()));
+            }
+            supply = supply.checked_add(allocation.amount).ok_or(ConfigError::SupplyOverflow)?;
+        }
+        for validator in &self.validators {
+            if validator.address.is_empty() || validator.stake == 0 {
+                return Err(ConfigError::InvalidValidator(validator.address.clone()));
+            }
+            supply = supply.checked_add(validator.stake).ok_or(ConfigError::SupplyOverflow)?;
+        }
+
+        // Genesis counts against the cap like every later mint
+        if supply > self.emission.max_supply {
+            return Err(ConfigError::SupplyAboveCap {
+                supply,
+                max_supply: self.emission.max_supply,
+            });
+        }
+
+        Ok(())
+    }
+
+    // Coins genesis creates, as balances and validator stakes
+    pub fn initial_supply(&self) -> u64 {
+        let allocated = self.allocations.iter().fold(0u64, |total, allocation| total.saturating_add(allocation.amount));
+        self.validators.iter().fold(allocated, |total, validator| total.saturating_add(validator.stake))
+    }
+
+    // Hash of the canonical config encoding; used as the genesis previous_hash
+    // so the block also commits to validators and mining parameters
+    pub fn config_hash(&self) -> BitnunResult<String> {
+        let mut encoder = Encoder::new(KIND_GENESIS_CONFIG);
+        encoder.put_str(&self.chain_id);
+        encoder.put_str(&self.normalized_timestamp()?);
+
+        encoder.put_u32(self.allocations.len() as u32);
+        for allocation in &self.allocations {
+            encoder.put_str(&allocation.address);
+            encoder.put_u64(allocation.amount);
+        }
+
+        encoder.put_u32(self.validators.len() as u32);
+        for validator in &self.validators {
+            encoder.put_str(&validator.address);
+            encoder.put_u64(validator.stake);
+        }
+
+        encoder.put_u64(self.mining_reward);
+        self.difficulty.encode_fields(&mut encoder);
+        self.emission.encode_fields(&mut encoder);
+        Ok(sha256_hex(&encoder.finish()))
+    }
+
+    pub fn genesis_block(&self) -> BitnunResult<Block> {
+        self.validate()?;
+        let timestamp = self.normalized_timestamp()?;
+
+        let allocations = self.allocations
+            .iter()
+            .enumerate()
+            .map(|(position, allocation)| {
+                let transaction = Transaction::new_genesis(&allocation.address, allocation.amount);
+                (format!("genesis-{}", position), transaction)
+            });
+        let stakes = self.validators
+            .iter()
+            .enumerate()
+            .map(|(position, validator)| {
+                let transaction = Transaction::new_genesis_stake(&validator.address, validator.stake);
+                (format!("genesis-stake-{}", position), transaction)
+            });
+
+        let transactions = allocations
+            .chain(stakes)
+            .map(|(id, mut transaction)| {
+                transaction.id = id;
+                transaction.timestamp = timestamp.clone();
+                transaction.chain_id = self.chain_id.clone();
+                transaction
+            })
+            .collect();
+
+        let mut block = Block::new(0, self.config_hash()?, transactions);
+        block.timestamp = timestamp;
+        let mut state = AccountStateIndex::new();
+        state.apply_block(&block, 0);
+        block.state_root = state.state_root();
+        block.hash = block.calculate_hash();
+        Ok(block)
+    }
+
+    pub fn genesis_hash(&self) -> BitnunResult<String> {
+        Ok(self.genesis_block()?.hash)
+    }
+
+    // Equivalent spellings of the same instant hash identically
+    fn normalized_timestamp(&self) -> Result<String, ConfigError> {
+        DateTime::parse_from_rfc3339(&self.timestamp)
+            .map(|timestamp| timestamp.with_timezone(&Utc).to_rfc3339())
+            .map_err(|_| ConfigError::InvalidTimestamp(self.timestamp.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::{BitnunBlockchain, ADDRESS_PREFIX};
+
+    #[test]
+    fn rejects_validators_without_address_or_stake() {
+        let mut config = GenesisConfig::dev();
+        config.validators = vec![GenesisValidator { address: String::new(), stake: 100 }];
+        assert_eq!(config.validate(), Err(ConfigError::InvalidValidator(String::new())));
+
+        config.validators = vec![GenesisValidator { address: "btnvalidator".to_string(), stake: 0 }];
+        assert_eq!(config.validate(), Err(ConfigError::InvalidValidator("btnvalidator".to_string())));
+
+        config.validators = vec![GenesisValidator { address: "btnvalidator".to_string(), stake: 100 }];
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn initial_supply_counts_validator_stakes() {
+        let config = GenesisConfig::dev();
+        assert_eq!(config.initial_supply(), 500000000 + 10000);
+        let chain = BitnunBlockchain::from_genesis_config(&config).unwrap();
+        assert_eq!(chain.state.circulating_supply(), config.initial_supply());
+    }
+
+    #[test]
+    fn preset_allocations_go_to_derived_addresses() {
+        for name in ["mainnet", "testnet", "dev"] {
+            for allocation in GenesisConfig::preset(name).unwrap().allocations {
+                assert!(allocation.address.starts_with(ADDRESS_PREFIX), "{} allocation to {}", name, allocation.address);
+                assert_eq!(allocation.address.len(), ADDRESS_PREFIX.len() + 40);
+            }
+        }
+        assert_eq!(GenesisConfig::mainnet().allocations[0].address, derive_address(MAINNET_TREASURY_PUBLIC_KEY).unwrap());
+    }
+
+    // Changing any preset, or the encoding behind these hashes, forks the network
+    #[test]
+    fn preset_genesis_hashes_are_pinned() {
+        for (name, config_hash, genesis_hash) in [
+            ("mainnet",
+                "c5beb646510750bffdc173df743ba6a973aa9420d9b5b642fcede2d42286dafd",
+                "a676c222ca3261d6858cfa9cf3808656571fda899a16769c12d18e4399f60bae"),
+            ("testnet",
+                "d6c7b8080d9944339f2518a8ccfa2de2bb381059c857d3fe95306309779ef9e7",
+                "9daf2f62b5766ddfbaf5c528bd0ff8b5b5095f4d1df5406bc46203bdb46dd731"),
+            ("dev",
+                "60b50fd38d95b017b8a86fd3c4244ba4d9cec0c34ecf2372fc7b8d8d8e555ef4",
+                "7816a71430f0c97809962bfef4b0c1ba12bc47daba5ec78d8c36af173052faa2"),
+        ] {
+            let config = GenesisConfig::preset(name).unwrap();
+            assert_eq!(config.config_hash().unwrap(), config_hash, "{} config hash", name);
+            assert_eq!(config.genesis_hash().unwrap(), genesis_hash, "{} genesis hash", name);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::blockchain::{MerkleProof, Transaction};
+use crate::blockchain::encoding::{sha256_digest, DecodeError, Decoder, Encoder, KIND_BLOCK_HEADER};
+use crate::blockchain::pow::{meets_target, work_from_compact};
+use crate::error::BitnunResult;
+
+// Everything a light client needs to follow the chain; transactions are
+// committed only through merkle_root, so hashing cost is independent of block size
+#[cfg_attr(feature = "wasm", wasm_bindgen(getter_with_clone))]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub index: u64,
+    pub timestamp: String,
+    pub previous_hash: String,
+    pub merkle_root: String,
+    pub bits: u32, // Compact proof-of-work target
+    pub nonce: u64,
+    pub state_root: String,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl BlockHeader {
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn calculate_hash(&self) -> String {
+        hex::encode(self.hash_digest())
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn meets_target(&self) -> bool {
+        meets_target(&self.hash_digest(), self.bits)
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new(KIND_BLOCK_HEADER);
+        encoder.put_u64(self.index);
+        encoder.put_str(&self.timestamp);
+        encoder.put_str(&self.previous_hash);
+        encoder.put_str(&self.merkle_root);
+        encoder.put_u32(self.bits);
+        encoder.put_u64(self.nonce);
+        encoder.put_str(&self.state_root);
+        encoder.finish()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn from_bytes(bytes: &[u8]) -> BitnunResult<BlockHeader> {
+        Ok(BlockHeader::decode(bytes)?)
+    }
+}
+
+impl BlockHeader {
+    pub fn hash_digest(&self) -> [u8; 32] {
+        sha256_digest(&self.to_bytes())
+    }
+
+    pub fn work(&self) -> u128 {
+        work_from_compact(self.bits)
+    }
+
+    // Light-client check that a block with this header includes `transaction`
+    pub fn includes_transaction(&self, transaction: &Transaction, proof: &MerkleProof) -> bool {
+        proof.verify(&transaction.to_bytes(), &self.merkle_root)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<BlockHeader, DecodeError> {
+        let mut decoder = Decoder::new(bytes, KIND_BLOCK_HEADER)?;
+        let header = BlockHeader {
+            index: decoder.get_u64()?,
+            timestamp: decoder.get_string()?,
+            previous_hash: decoder.get_string()?,
+            merkle_root: decoder.get_string()?,
+            bits: decoder.get_u32()?,
+            nonce: decoder.get_u64()?,
+            state_root: decoder.get_string()?,
+        };
+        decoder.finish()?;
+        Ok(header)
+    }
+}
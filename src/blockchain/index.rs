@@ -0,0 +1,293 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use crate::blockchain::{BitnunBlockchain, Block, Transaction, TransactionKind, TransferOutput};
+use crate::error::{BitnunResult, ChainError};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TransactionLocation {
+    pub height: u64,
+    pub position: u32, // Index within the block's transactions
+}
+
+// Secondary indexes over the active chain. Blocks held as headers only (from
+// a snapshot) are indexed by hash but contribute no transactions.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChainIndex {
+    block_heights: HashMap<String, u64>,
+    transactions: HashMap<String, TransactionLocation>,
+    addresses: HashMap<String, Vec<TransactionLocation>>, // Oldest first
+}
+
+impl ChainIndex {
+    pub fn build(blocks: &[Block]) -> ChainIndex {
+        let mut index = ChainIndex::default();
+        for block in blocks {
+            index.add_block(block);
+        }
+        index
+    }
+
+    // `block` must extend the chain indexed so far
+    pub fn add_block(&mut self, block: &Block) {
+        self.block_heights.insert(block.hash.clone(), block.index);
+
+        for (position, transaction) in block.transactions.iter().enumerate() {
+            let location = TransactionLocation {
+                height: block.index,
+                position: position as u32,
+            };
+            self.transactions.insert(transaction.id.clone(), location);
+
+            for address in involved_addresses(transaction) {
+                self.addresses.entry(address.to_string()).or_default().push(location);
+            }
+        }
+    }
+
+    // `block` must be the last block added
+    pub fn remove_block(&mut self, block: &Block) {
+        self.block_heights.remove(&block.hash);
+
+        for transaction in &block.transactions {
+            self.transactions.remove(&transaction.id);
+
+            for address in involved_addresses(transaction) {
+                if let Some(history) = self.addresses.get_mut(address) {
+                    history.retain(|location| location.height != block.index);
+                    if history.is_empty() {
+                        self.addresses.remove(address);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn height_of(&self, block_hash: &str) -> Option<u64> {
+        self.block_heights.get(block_hash).copied()
+    }
+
+    pub fn locate(&self, transaction_id: &str) -> Option<TransactionLocation> {
+        self.transactions.get(transaction_id).copied()
+    }
+
+    pub fn address_history(&self, address: &str) -> &[TransactionLocation] {
+        self.addresses.get(address).map(|history| history.as_slice()).unwrap_or(&[])
+    }
+}
+
+fn involved_addresses(transaction: &Transaction) -> Vec<&str> {
+    let mut addresses = Vec::new();
+    if !transaction.from_address.is_empty() {
+        addresses.push(transaction.from_address.as_str());
+    }
+    for recipient in transaction.recipients() {
+        if recipient != transaction.from_address {
+            addresses.push(recipient);
+        }
+    }
+    addresses
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TransactionRecord {
+    pub transaction: Transaction,
+    pub block_hash: String,
+    pub block_height: u64,
+    pub position: u32,
+    pub confirmations: u64, // 1 when in the tip block
+    #[serde(default)]
+    pub outputs: Vec<TransferOutput>, // In address history, the batch outputs paying that address
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AddressHistoryPage {
+    pub entries: Vec<TransactionRecord>, // Newest first
+    pub next_cursor: Option<String>, // Pass back to continue with older entries
+}
+
+impl BitnunBlockchain {
+    // Searches the active chain, then side branches
+    pub fn block_by_hash(&self, hash: &str) -> BitnunResult<Option<Block>> {
+        match self.index.height_of(hash) {
+            Some(height) => self.block_at(height),
+            None => Ok(self.side_blocks.get(hash).cloned()),
+        }
+    }
+
+    // Confirmed transactions only; pending ones have no inclusion block
+    pub fn transaction(&self, id: &str) -> BitnunResult<Option<TransactionRecord>> {
+        match self.index.locate(id) {
+            Some(location) => self.transaction_record(location),
+            None => Ok(None),
+        }
+    }
+
+    // Pages through an address's transactions from newest to oldest. The
+    // cursor is the `next_cursor` of the previous page. `type_filter` matches
+    // the JSON `transaction_type` exactly, or by prefix when it ends in `*`
+    // (e.g. "nft_*" or "action_mining_*").
+    pub fn address_history(
+        &self,
+        address: &str,
+        cursor: Option<&str>,
+        limit: usize,
+        type_filter: Option<&str>,
+    ) -> BitnunResult<AddressHistoryPage> {
+        if limit == 0 {
+            return Err(ChainError::InvalidPageLimit(limit).into());
+        }
+
+        let history = self.index.address_history(address);
+        let end = match cursor {
+            Some(cursor) => {
+                let before = parse_cursor(cursor)?;
+                history.partition_point(|location| *location < before)
+            }
+            None => history.len(),
+        };
+
+        let mut entries = Vec::new();
+        let mut next_cursor = None;
+        let mut block: Option<Cow<'_, Block>> = None;
+        for location in history[..end].iter().rev() {
+            // An address's entries are grouped by block, so each body is read once
+            if block.as_ref().is_none_or(|block| block.index != location.height) {
+                block = self.block_body_at(location.height)?;
+            }
+            let located = block
+                .as_deref()
+                .and_then(|block| Some((block, block.transactions.get(location.position as usize)?)));
+            let (block, transaction) = match located {
+                Some(located) => located,
+                None => continue, // Body no longer available
+            };
+            if !type_filter.is_none_or(|filter| type_matches(&transaction.kind.transaction_type(), filter)) {
+                continue;
+            }
+
+            // Only hand out a cursor once another matching entry is known to follow
+            if entries.len() == limit {
+                next_cursor = entries.last().map(|record: &TransactionRecord| {
+                    format!("{}:{}", record.block_height, record.position)
+                });
+                break;
+            }
+            let mut record = self.record_in(block, location.position, transaction);
+            if let TransactionKind::BatchTransfer { outputs } = &transaction.kind {
+                record.outputs = outputs.iter().filter(|output| output.to_address == address).cloned().collect();
+            }
+            entries.push(record);
+        }
+
+        Ok(AddressHistoryPage { entries, next_cursor })
+    }
+
+    fn transaction_record(&self, location: TransactionLocation) -> BitnunResult<Option<TransactionRecord>> {
+        let block = match self.block_body_at(location.height)? {
+            Some(block) => block,
+            None => return Ok(None),
+        };
+
+        Ok(block.transactions
+            .get(location.position as usize)
+            .map(|transaction| self.record_in(&block, location.position, transaction)))
+    }
+
+    fn record_in(&self, block: &Block, position: u32, transaction: &Transaction) -> TransactionRecord {
+        TransactionRecord {
+            transaction: transaction.clone(),
+            block_hash: block.hash.clone(),
+            block_height: block.index,
+            position,
+            confirmations: self.blocks.len() as u64 - block.index,
+            outputs: Vec::new(),
+        }
+    }
+}
+
+fn parse_cursor(cursor: &str) -> Result<TransactionLocation, ChainError> {
+    let invalid = || ChainError::InvalidCursor(cursor.to_string());
+    let (height, position) = cursor.split_once(':').ok_or_else(invalid)?;
+    Ok(TransactionLocation {
+        height: height.parse().map_err(|_| invalid())?,
+        position: position.parse().map_err(|_| invalid())?,
+    })
+}
+
+fn type_matches(transaction_type: &str, filter: &str) -> bool {
+    match filter.strip_suffix('*') {
+        Some(prefix) => transaction_type.starts_with(prefix),
+        None => transaction_type == filter,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::type_matches;
+    use crate::blockchain::{BitnunBlockchain, Transaction, TransactionKind, TransferOutput};
+    use crate::blockchain::test_support::{dev_keypair, signed};
+    use crate::error::{BitnunError, ChainError};
+
+    #[test]
+    fn filtered_history_pages_only_matching_entries() {
+        let dev = dev_keypair();
+        let mut chain = BitnunBlockchain::from_preset("dev").unwrap();
+        let outputs = vec![
+            TransferOutput { to_address: "btnpayee".to_string(), amount: 10 },
+            TransferOutput { to_address: "btnother".to_string(), amount: 4 },
+            TransferOutput { to_address: "btnpayee".to_string(), amount: 6 },
+        ];
+        let batch = signed(Transaction::new_batch(&dev.address(), outputs), &dev);
+        chain.add_transaction(&batch).unwrap();
+        chain.mine_pending_transactions("btnminer");
+
+        for nonce in 1..=2 {
+            let mut transfer = Transaction::new(&dev.address(), "btnpayee", 100);
+            transfer.set_nonce(nonce);
+            let transfer = signed(transfer, &dev);
+            chain.add_transaction(&transfer).unwrap();
+        }
+        chain.mine_pending_transactions("btnminer");
+
+        // The older batch doesn't match, so the page ends without a cursor
+        let page = chain.address_history("btnpayee", None, 2, Some("transfer")).unwrap();
+        assert_eq!(page.entries.len(), 2);
+        assert!(page.entries.iter().all(|record| record.transaction.id != batch.id));
+        assert_eq!(page.next_cursor, None);
+
+        let page = chain.address_history("btnpayee", None, 1, Some("transfer")).unwrap();
+        assert_eq!(page.entries.len(), 1);
+        let cursor = page.next_cursor.expect("a second transfer follows");
+        let page = chain.address_history("btnpayee", Some(&cursor), 1, Some("transfer")).unwrap();
+        assert_eq!(page.entries.len(), 1);
+        assert_eq!(page.next_cursor, None);
+
+        // A batch shows once, with only the outputs paying the queried address
+        let page = chain.address_history("btnpayee", None, 10, Some("batch_*")).unwrap();
+        assert_eq!(page.entries.len(), 1);
+        assert_eq!(page.entries[0].transaction.id, batch.id);
+        assert_eq!(page.entries[0].outputs.iter().map(|output| output.amount).collect::<Vec<_>>(), vec![10, 6]);
+    }
+
+    #[test]
+    fn history_pages_need_a_positive_limit() {
+        let dev = dev_keypair();
+        let chain = BitnunBlockchain::from_preset("dev").unwrap();
+        assert_eq!(chain.address_history(&dev.address(), None, 0, None).err(),
+            Some(BitnunError::Chain(ChainError::InvalidPageLimit(0))));
+        assert_eq!(chain.address_history(&dev.address(), None, 1, None).unwrap().entries.len(), 1);
+    }
+
+    #[test]
+    fn action_rewards_match_by_their_json_type() {
+        let kind = TransactionKind::ActionReward {
+            action_type: "recycling".to_string(),
+            action_proof: "{}".to_string(),
+        };
+        assert!(type_matches(&kind.transaction_type(), "action_mining_*"));
+        assert!(type_matches(&kind.transaction_type(), "action_mining_recycling"));
+        assert!(!type_matches(&kind.transaction_type(), "action_mining_planting"));
+        assert!(!type_matches(&TransactionKind::MiningReward.transaction_type(), "action_mining_*"));
+    }
+}
@@ -0,0 +1,110 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+use sha2::{Sha256, Digest};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use rand::rngs::OsRng;
+use crate::error::{BitnunResult, KeyError};
+
+pub const ADDRESS_PREFIX: &str = "btn";
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct KeyPair {
+    signing_key: SigningKey,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl KeyPair {
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn generate() -> KeyPair {
+        KeyPair {
+            signing_key: SigningKey::generate(&mut OsRng),
+        }
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn from_secret_key(secret_key_hex: &str) -> BitnunResult<KeyPair> {
+        let secret: [u8; 32] = hex::decode(secret_key_hex)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(KeyError::InvalidSecretKey)?;
+        Ok(KeyPair {
+            signing_key: SigningKey::from_bytes(&secret),
+        })
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn public_key(&self) -> String {
+        hex::encode(self.signing_key.verifying_key().as_bytes())
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn secret_key(&self) -> String {
+        hex::encode(self.signing_key.to_bytes())
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn address(&self) -> String {
+        address_from_bytes(self.signing_key.verifying_key().as_bytes())
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn sign(&self, message: &[u8]) -> String {
+        hex::encode(self.signing_key.sign(message).to_bytes())
+    }
+}
+
+// Addresses are the prefix followed by the first 20 bytes of SHA-256(public key)
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn derive_address(public_key_hex: &str) -> BitnunResult<String> {
+    let public_key: [u8; 32] = hex::decode(public_key_hex)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(KeyError::InvalidPublicKey)?;
+    VerifyingKey::from_bytes(&public_key).map_err(|_| KeyError::InvalidPublicKey)?;
+    Ok(address_from_bytes(&public_key))
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn verify_signature(public_key_hex: &str, message: &[u8], signature_hex: &str) -> bool {
+    let public_key = match hex::decode(public_key_hex).ok().and_then(|b| <[u8; 32]>::try_from(b).ok()) {
+        Some(bytes) => bytes,
+        None => return false,
+    };
+    let signature = match hex::decode(signature_hex).ok().and_then(|b| <[u8; 64]>::try_from(b).ok()) {
+        Some(bytes) => Signature::from_bytes(&bytes),
+        None => return false,
+    };
+
+    match VerifyingKey::from_bytes(&public_key) {
+        Ok(verifying_key) => verifying_key.verify_strict(message, &signature).is_ok(),
+        Err(_) => false,
+    }
+}
+
+pub(crate) fn address_from_bytes(public_key: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(public_key);
+    let digest = hasher.finalize();
+    format!("{}{}", ADDRESS_PREFIX, hex::encode(&digest[..20]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_order_keys_cannot_sign_for_every_message() {
+        // The identity point with R = identity and s = 0 passes the lax
+        // check for any message
+        let mut identity = [0u8; 32];
+        identity[0] = 1;
+        let public_key = hex::encode(identity);
+        let signature = hex::encode([identity, [0u8; 32]].concat());
+        assert!(!verify_signature(&public_key, b"any message", &signature));
+
+        let keypair = KeyPair::generate();
+        let signature = keypair.sign(b"message");
+        assert!(verify_signature(&keypair.public_key(), b"message", &signature));
+        assert!(!verify_signature(&keypair.public_key(), b"other message", &signature));
+    }
+}
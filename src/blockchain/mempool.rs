@@ -0,0 +1,296 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use crate::blockchain::Transaction;
+use crate::runtime;
+
+// Consensus limits on a block's body, checked for every block
+pub const MAX_BLOCK_TRANSACTIONS: usize = 1000;
+pub const MAX_BLOCK_BYTES: usize = 1024 * 1024;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MempoolConfig {
+    pub max_transactions: usize, // Beyond this the lowest fee rate is evicted
+    pub expiry_ms: u64, // Entries older than this are dropped before mining
+}
+
+impl Default for MempoolConfig {
+    fn default() -> Self {
+        MempoolConfig {
+            max_transactions: 5000,
+            expiry_ms: 60 * 60 * 1000, // 1 hour
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PoolEntry {
+    pub transaction: Transaction,
+    pub size: usize, // Encoded bytes
+    pub received_at: f64, // Milliseconds since the Unix epoch
+}
+
+// Pending transactions in arrival order. Each sender's entries arrive in
+// nonce order, so that order is kept; blocks are filled by fee rate.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Mempool {
+    entries: Vec<PoolEntry>,
+    config: MempoolConfig,
+}
+
+impl PoolEntry {
+    pub fn new(transaction: Transaction) -> Self {
+        PoolEntry {
+            size: transaction.to_bytes().len(),
+            transaction,
+            received_at: runtime::now_millis(),
+        }
+    }
+
+    // Fee per byte, compared exactly by cross-multiplying
+    pub fn cmp_fee_rate(&self, other: &PoolEntry) -> Ordering {
+        let own = self.transaction.fee as u128 * other.size as u128;
+        let theirs = other.transaction.fee as u128 * self.size as u128;
+        own.cmp(&theirs)
+    }
+}
+
+impl Mempool {
+    pub fn new(config: MempoolConfig) -> Self {
+        Mempool {
+            entries: Vec::new(),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &MempoolConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: MempoolConfig) {
+        self.config = config;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.entries.len() >= self.config.max_transactions
+    }
+
+    pub fn transactions(&self) -> impl Iterator<Item = &Transaction> {
+        self.entries.iter().map(|entry| &entry.transaction)
+    }
+
+    pub fn push(&mut self, entry: PoolEntry) {
+        self.entries.push(entry);
+    }
+
+    pub fn take(&mut self) -> Vec<PoolEntry> {
+        std::mem::take(&mut self.entries)
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&Transaction) -> bool) {
+        self.entries.retain(|entry| keep(&entry.transaction));
+    }
+
+    // The entry a full pool gives up for `incoming`: the lowest fee rate
+    // among the last entries of each sender, so no remaining nonce is left
+    // without its predecessor. None if nothing pays less than `incoming`.
+    pub fn eviction_candidate(&self, incoming: &PoolEntry) -> Option<String> {
+        let mut last_by_sender: HashMap<&str, &PoolEntry> = HashMap::new();
+        let mut candidates: Vec<&PoolEntry> = Vec::new();
+        for entry in &self.entries {
+            if entry.transaction.is_system() {
+                candidates.push(entry);
+            } else {
+                last_by_sender.insert(entry.transaction.from_address.as_str(), entry);
+            }
+        }
+
+        let incoming_sender = incoming.transaction.from_address.as_str();
+        candidates.extend(last_by_sender
+            .into_iter()
+            .filter(|(sender, _)| incoming.transaction.is_system() || *sender != incoming_sender)
+            .map(|(_, entry)| entry));
+
+        candidates
+            .into_iter()
+            .min_by(|a, b| a.cmp_fee_rate(b).then(b.received_at.total_cmp(&a.received_at)))
+            .filter(|entry| entry.cmp_fee_rate(incoming) == Ordering::Less)
+            .map(|entry| entry.transaction.id.clone())
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<Transaction> {
+        let position = self.entries.iter().position(|entry| entry.transaction.id == id)?;
+        Some(self.entries.remove(position).transaction)
+    }
+
+    // Removes entries received more than expiry_ms before `now`
+    pub fn expire(&mut self, now: f64) -> Vec<Transaction> {
+        let cutoff = now - self.config.expiry_ms as f64;
+        let (expired, kept) = self.take().into_iter().partition(|entry| entry.received_at < cutoff);
+        self.entries = kept;
+        expired.into_iter().map(|entry| entry.transaction).collect()
+    }
+
+    // Highest fee rate first, taking each sender's transactions in nonce
+    // order, until either limit is reached
+    pub fn select(&self, max_transactions: usize, max_bytes: usize) -> Vec<Transaction> {
+        let mut queues: Vec<VecDeque<&PoolEntry>> = Vec::new();
+        let mut sender_queues: HashMap<&str, usize> = HashMap::new();
+        for entry in &self.entries {
+            // System transactions have no nonce, so each gets a queue of its own
+            if !entry.transaction.is_system() {
+                let sender = entry.transaction.from_address.as_str();
+                if let Some(&queue) = sender_queues.get(sender) {
+                    queues[queue].push_back(entry);
+                    continue;
+                }
+                sender_queues.insert(sender, queues.len());
+            }
+            queues.push(VecDeque::from([entry]));
+        }
+
+        let mut heads: BinaryHeap<Candidate> = queues
+            .iter()
+            .enumerate()
+            .map(|(queue, entries)| Candidate { entry: entries[0], queue })
+            .collect();
+
+        let mut selected = Vec::new();
+        let mut bytes = 0;
+        while selected.len() < max_transactions {
+            let Candidate { entry, queue } = match heads.pop() {
+                Some(candidate) => candidate,
+                None => break,
+            };
+            if bytes + entry.size > max_bytes {
+                continue; // The sender's later nonces can't be included without it
+            }
+
+            bytes += entry.size;
+            selected.push(entry.transaction.clone());
+            queues[queue].pop_front();
+            if let Some(next) = queues[queue].front() {
+                heads.push(Candidate { entry: next, queue });
+            }
+        }
+
+        selected
+    }
+}
+
+struct Candidate<'a> {
+    entry: &'a PoolEntry,
+    queue: usize,
+}
+
+// Max-heap order: higher fee rate first, then the earlier sender queue
+impl Ord for Candidate<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.entry.cmp_fee_rate(other.entry).then(other.queue.cmp(&self.queue))
+    }
+}
+
+impl PartialOrd for Candidate<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Candidate<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Same encoded size for every sender of the same name length, so fee
+    // rates order like fees
+    fn entry(sender: &str, nonce: u64, fee: u64) -> PoolEntry {
+        let mut transaction = Transaction::new(sender, "btnrecipient", 1);
+        transaction.timestamp = "2024-01-01T00:00:00+00:00".to_string();
+        transaction.nonce = nonce;
+        transaction.fee = fee;
+        PoolEntry::new(transaction)
+    }
+
+    fn pool_of(entries: &[&PoolEntry]) -> Mempool {
+        let mut pool = Mempool::new(MempoolConfig { max_transactions: entries.len(), expiry_ms: 1_000 });
+        for entry in entries {
+            pool.push((*entry).clone());
+        }
+        pool
+    }
+
+    fn ids(transactions: &[Transaction]) -> Vec<&str> {
+        transactions.iter().map(|transaction| transaction.id.as_str()).collect()
+    }
+
+    #[test]
+    fn selection_follows_fee_rate_but_keeps_each_senders_nonces_in_order() {
+        let (alice_0, alice_1) = (entry("btnalice", 0, 1), entry("btnalice", 1, 100));
+        let (bob_0, carol_0) = (entry("btnbobby", 0, 50), entry("btncarol", 0, 10));
+        let pool = pool_of(&[&alice_0, &alice_1, &bob_0, &carol_0]);
+
+        // Alice's second transaction pays most but waits for her first
+        let selected = pool.select(MAX_BLOCK_TRANSACTIONS, MAX_BLOCK_BYTES);
+        let expected = [&bob_0, &carol_0, &alice_0, &alice_1].map(|entry| entry.transaction.id.as_str());
+        assert_eq!(ids(&selected), expected);
+    }
+
+    #[test]
+    fn selection_stops_at_the_block_limits() {
+        let (alice_0, alice_1, bob_0) = (entry("btnalice", 0, 30), entry("btnalice", 1, 20), entry("btnbobby", 0, 10));
+        let pool = pool_of(&[&alice_0, &alice_1, &bob_0]);
+        let size = alice_0.size;
+
+        let selected = pool.select(2, MAX_BLOCK_BYTES);
+        assert_eq!(ids(&selected), [&alice_0, &alice_1].map(|entry| entry.transaction.id.as_str()));
+        let selected = pool.select(MAX_BLOCK_TRANSACTIONS, 2 * size + 1);
+        assert_eq!(ids(&selected), [&alice_0, &alice_1].map(|entry| entry.transaction.id.as_str()));
+
+        // A transaction that doesn't fit holds back only its own sender
+        let mut large = entry("btnlarge", 0, 1_000).transaction;
+        large.to_address = "btn".repeat(100);
+        let large = PoolEntry::new(large);
+        let pool = pool_of(&[&large, &alice_0, &bob_0]);
+        let selected = pool.select(MAX_BLOCK_TRANSACTIONS, 2 * size);
+        assert_eq!(ids(&selected), [&alice_0, &bob_0].map(|entry| entry.transaction.id.as_str()));
+    }
+
+    #[test]
+    fn a_full_pool_gives_up_the_lowest_fee_rate_a_sender_can_spare() {
+        let (alice_0, alice_1, bob_0) = (entry("btnalice", 0, 5), entry("btnalice", 1, 1), entry("btnbobby", 0, 2));
+        let pool = pool_of(&[&alice_0, &alice_1, &bob_0]);
+        assert!(pool.is_full());
+
+        assert_eq!(pool.eviction_candidate(&entry("btncarol", 0, 3)), Some(alice_1.transaction.id.clone()));
+        assert_eq!(pool.eviction_candidate(&entry("btncarol", 0, 1)), None);
+        // Alice's own entries stay, or her next nonce would lose its predecessor
+        assert_eq!(pool.eviction_candidate(&entry("btnalice", 2, 10)), Some(bob_0.transaction.id.clone()));
+    }
+
+    #[test]
+    fn entries_expire_after_the_configured_age() {
+        let (mut stale, fresh) = (entry("btnalice", 0, 1), entry("btnbobby", 0, 1));
+        stale.received_at = fresh.received_at - 1_001.0;
+        let mut pool = pool_of(&[&stale, &fresh]);
+
+        let expired = pool.expire(fresh.received_at);
+        assert_eq!(ids(&expired), [stale.transaction.id.as_str()]);
+        assert_eq!(pool.transactions().map(|transaction| transaction.id.as_str()).collect::<Vec<_>>(), [fresh.transaction.id.as_str()]);
+    }
+}
@@ -1,69 +1,191 @@
 use sha2::{Sha256, Digest};
 use serde::{Deserialize, Serialize};
 
+// Leaves and internal nodes hash under different prefixes, so an internal
+// node can never be passed off as a leaf (or the other way round)
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+pub const EMPTY_MERKLE_ROOT: [u8; 32] = [0u8; 32];
+
+// Binary merkle tree over arbitrary byte strings. A node without a sibling
+// moves up a level unchanged instead of being paired with a copy of itself,
+// so no two different leaf lists share a root (see CVE-2012-2459).
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MerkleTree {
-    pub root: String,
-    pub leaves: Vec<String>,
+    levels: Vec<Vec<[u8; 32]>>, // Leaf hashes first, root last
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SiblingSide {
+    Left,
+    Right,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProofStep {
+    pub hash: String, // Hex-encoded sibling digest
+    pub side: SiblingSide, // Where the sibling sits relative to the running hash
+}
+
+// Siblings from the leaf level up to just below the root; levels where the
+// node had no sibling contribute no step
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub leaf_index: usize,
+    pub steps: Vec<ProofStep>,
 }
 
 impl MerkleTree {
-    pub fn new(data: Vec<String>) -> Self {
-        let root = Self::calculate_root(&data);
-        MerkleTree {
-            root,
-            leaves: data,
+    pub fn new<T: AsRef<[u8]>>(leaves: &[T]) -> Self {
+        if leaves.is_empty() {
+            return MerkleTree { levels: Vec::new() };
+        }
+
+        let mut levels = vec![leaves.iter().map(|leaf| leaf_hash(leaf.as_ref())).collect::<Vec<[u8; 32]>>()];
+
+        while levels[levels.len() - 1].len() > 1 {
+            let next_level = levels[levels.len() - 1]
+                .chunks(2)
+                .map(|chunk| match chunk {
+                    [left, right] => node_hash(left, right),
+                    _ => chunk[0], // Promoted, never duplicated
+                })
+                .collect();
+            levels.push(next_level);
         }
+
+        MerkleTree { levels }
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.levels.last().map(|top| top[0]).unwrap_or(EMPTY_MERKLE_ROOT)
+    }
+
+    pub fn root_hex(&self) -> String {
+        hex::encode(self.root())
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.levels.first().map(|leaves| leaves.len()).unwrap_or(0)
     }
 
-    fn calculate_root(data: &[String]) -> String {
-        if data.is_empty() {
-            return "0".to_string();
+    pub fn generate_proof(&self, index: usize) -> Option<MerkleProof> {
+        if index >= self.leaf_count() {
+            return None;
         }
 
-        let mut hashes: Vec<String> = data
-            .iter()
-            .map(|item| {
-                let mut hasher = Sha256::new();
-                hasher.update(item.as_bytes());
-                format!("{:x}", hasher.finalize())
-            })
-            .collect();
-
-        while hashes.len() > 1 {
-            let mut next_level = Vec::new();
-            
-            for chunk in hashes.chunks(2) {
-                let combined = if chunk.len() == 2 {
-                    format!("{}{}", chunk[0], chunk[1])
-                } else {
-                    format!("{}{}", chunk[0], chunk[0])
-                };
-
-                let mut hasher = Sha256::new();
-                hasher.update(combined.as_bytes());
-                next_level.push(format!("{:x}", hasher.finalize()));
+        let mut steps = Vec::new();
+        let mut position = index;
+
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = if position.is_multiple_of(2) {
+                level.get(position + 1).map(|hash| (hash, SiblingSide::Right))
+            } else {
+                Some((&level[position - 1], SiblingSide::Left))
+            };
+
+            if let Some((hash, side)) = sibling {
+                steps.push(ProofStep {
+                    hash: hex::encode(hash),
+                    side,
+                });
             }
+            position /= 2;
+        }
+
+        Some(MerkleProof {
+            leaf_index: index,
+            steps,
+        })
+    }
+
+    pub fn verify_proof(&self, leaf: &[u8], proof: &MerkleProof) -> bool {
+        proof.verify(leaf, &self.root_hex())
+    }
+}
+
+impl MerkleProof {
+    // Recomputes the root from the leaf's bytes; needs nothing but the
+    // expected hex root, e.g. a block header's merkle_root
+    pub fn verify(&self, leaf: &[u8], root: &str) -> bool {
+        let mut hash = leaf_hash(leaf);
+
+        for step in &self.steps {
+            let sibling = match hex::decode(&step.hash).ok().and_then(|bytes| <[u8; 32]>::try_from(bytes).ok()) {
+                Some(sibling) => sibling,
+                None => return false,
+            };
 
-            hashes = next_level;
+            hash = match step.side {
+                SiblingSide::Left => node_hash(&sibling, &hash),
+                SiblingSide::Right => node_hash(&hash, &sibling),
+            };
         }
 
-        hashes[0].clone()
+        hex::encode(hash) == root
+    }
+}
+
+pub fn leaf_hash(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+pub fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree_of(leaves: &[&str]) -> MerkleTree {
+        MerkleTree::new(leaves)
     }
 
-    pub fn verify_proof(&self, leaf: &str, proof: &[String]) -> bool {
-        let mut hash = {
-            let mut hasher = Sha256::new();
-            hasher.update(leaf.as_bytes());
-            format!("{:x}", hasher.finalize())
+    #[test]
+    fn leaves_and_nodes_hash_apart() {
+        let tree = tree_of(&["a", "b", "c", "d"]);
+        let (left, right) = (node_hash(&leaf_hash(b"a"), &leaf_hash(b"b")), node_hash(&leaf_hash(b"c"), &leaf_hash(b"d")));
+        assert_eq!(tree.root(), node_hash(&left, &right));
+
+        // The two children of the root, offered as a single leaf
+        let children = [left, right].concat();
+        assert_ne!(leaf_hash(&children), tree.root());
+        assert_ne!(MerkleTree::new(&[children]).root(), tree.root());
+
+        // An internal node with its sibling as the proof
+        let proof = MerkleProof {
+            leaf_index: 0,
+            steps: vec![ProofStep { hash: hex::encode(right), side: SiblingSide::Right }],
         };
+        assert!(!proof.verify(&left, &tree.root_hex()));
+        assert!(!tree.verify_proof(&left, &proof));
+    }
 
-        for proof_element in proof {
-            let mut hasher = Sha256::new();
-            hasher.update(format!("{}{}", hash, proof_element).as_bytes());
-            hash = format!("{:x}", hasher.finalize());
+    #[test]
+    fn repeating_the_last_leaves_changes_the_root() {
+        // CVE-2012-2459: pairing an odd node with itself lets [a, b, c] and
+        // [a, b, c, c] share a root
+        for (odd, padded) in [
+            (vec!["a", "b", "c"], vec!["a", "b", "c", "c"]),
+            (vec!["a", "b", "c", "d", "e"], vec!["a", "b", "c", "d", "e", "e"]),
+            (vec!["a", "b", "c", "d", "e", "f"], vec!["a", "b", "c", "d", "e", "f", "e", "f"]),
+        ] {
+            assert_ne!(tree_of(&odd).root(), tree_of(&padded).root(), "{:?}", odd);
         }
 
-        hash == self.root
+        // The odd node is promoted as is
+        let tree = tree_of(&["a", "b", "c"]);
+        assert_eq!(tree.root(), node_hash(&node_hash(&leaf_hash(b"a"), &leaf_hash(b"b")), &leaf_hash(b"c")));
+        assert_eq!(tree.generate_proof(2).unwrap().steps.len(), 1);
     }
-}
\ No newline at end of file
+}
@@ -1,116 +1,397 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
-use sha2::{Sha256, Digest};
-use chrono::{DateTime, Utc};
-use uuid::Uuid;
+use std::collections::{HashMap, HashSet, VecDeque};
 use crate::console_log;
+use crate::runtime;
+use crate::error::{BitnunResult, ChainError};
 
 pub mod block;
+pub mod header;
 pub mod transaction;
+pub mod transaction_kind;
 pub mod merkle;
+pub mod state;
+pub mod keys;
+pub mod encoding;
+pub mod validation;
+pub mod difficulty;
+pub mod pow;
+pub mod fork;
+pub mod archive;
+pub mod genesis;
+pub mod store;
+pub mod index;
+pub mod state_tree;
+pub mod mempool;
+pub mod emission;
+pub mod multisig;
+#[cfg(test)]
+pub(crate) mod test_support;
 
 pub use block::*;
+pub use header::*;
 pub use transaction::*;
+pub use transaction_kind::*;
 pub use merkle::*;
+pub use state::*;
+pub use keys::*;
+pub use encoding::*;
+pub use validation::*;
+pub use difficulty::*;
+pub use pow::*;
+pub use fork::*;
+pub use archive::*;
+pub use genesis::*;
+pub use store::*;
+pub use index::*;
+pub use state_tree::*;
+pub use mempool::*;
+pub use emission::*;
+pub use multisig::*;
 
-#[wasm_bindgen]
+pub const DEFAULT_CHAIN_ID: &str = "bitnun-mainnet";
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BitnunBlockchain {
-    blocks: Vec<Block>,
-    pending_transactions: Vec<Transaction>,
-    mining_difficulty: u32,
-    mining_reward: u64,
-    total_supply: u64,
+    chain_id: String,
+    blocks: Vec<Block>, // Active chain, genesis first
+    side_blocks: HashMap<String, Block>, // Blocks on competing branches, by hash
+    reorg_events: Vec<ReorgEvent>,
+    pruned_height: u64, // Blocks below this height were loaded from a snapshot as headers only
+    base_state: AccountStateIndex, // State after the pruned blocks
+    index: ChainIndex,
+    pending_transactions: Mempool,
+    #[serde(skip)]
+    partial_transactions: HashMap<String, PoolEntry>, // Multisig spends still gathering signatures, by id; local only
+    state: AccountStateIndex,
+    #[serde(skip)]
+    undo_log: VecDeque<StateUndo>, // Undo data of the newest blocks, tip last; local only
+    last_evictions: Vec<EvictedTransaction>,
+    difficulty_config: DifficultyConfig,
+    mining_bits: u32, // Target required of the next block
+    mining_reward: u64, // Initial block subsidy, halved by the emission schedule
+    emission: EmissionSchedule,
+    genesis_validators: Vec<GenesisValidator>,
+    genesis_allocations: Vec<GenesisAllocation>,
     carbon_offset: u64, // CO2 saved in grams
+    #[serde(skip)]
+    store: Option<StoreHandle>,
 }
 
-#[wasm_bindgen]
+impl Default for BitnunBlockchain {
+    fn default() -> Self {
+        BitnunBlockchain::new()
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl BitnunBlockchain {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new() -> BitnunBlockchain {
-        let genesis_block = Block::genesis();
-        console_log!("Genesis block created: {}", genesis_block.hash);
-        
-        BitnunBlockchain {
-            blocks: vec![genesis_block],
-            pending_transactions: Vec::new(),
-            mining_difficulty: 2,
-            mining_reward: 1000, // 10.00 BTN in cents
-            total_supply: 1000000000, // 10M BTN total supply
-            carbon_offset: 0,
-        }
+        BitnunBlockchain::with_chain_id(DEFAULT_CHAIN_ID)
+    }
+
+    // Mainnet parameters under a different chain id
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn with_chain_id(chain_id: &str) -> BitnunBlockchain {
+        let config = GenesisConfig {
+            chain_id: chain_id.to_string(),
+            ..GenesisConfig::mainnet()
+        };
+        BitnunBlockchain::from_genesis_config(&config).expect("mainnet preset is valid")
+    }
+
+    // "mainnet", "testnet" or "dev"
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn from_preset(name: &str) -> BitnunResult<BitnunBlockchain> {
+        BitnunBlockchain::from_genesis_config(&GenesisConfig::preset(name)?)
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn from_genesis_json(json: &str) -> BitnunResult<BitnunBlockchain> {
+        BitnunBlockchain::from_genesis_config(&GenesisConfig::from_json(json)?)
     }
 
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn from_genesis_toml(source: &str) -> BitnunResult<BitnunBlockchain> {
+        BitnunBlockchain::from_genesis_config(&GenesisConfig::from_toml(source)?)
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn get_genesis_hash(&self) -> String {
+        self.blocks[0].hash.clone()
+    }
+
+    #[cfg(feature = "wasm")]
     #[wasm_bindgen]
-    pub fn add_transaction(&mut self, transaction: &Transaction) -> bool {
-        if self.validate_transaction(transaction) {
-            self.pending_transactions.push(transaction.clone());
-            console_log!("Transaction added: {}", transaction.id);
-            true
-        } else {
-            console_log!("Invalid transaction rejected: {}", transaction.id);
-            false
+    pub fn get_genesis_validators(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.genesis_validators).unwrap()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn add_transaction(&mut self, transaction: &Transaction) -> BitnunResult<()> {
+        self.validate_transaction(transaction)?;
+        let entry = PoolEntry::new(transaction.clone());
+
+        // A full pool only takes the transaction if it outbids something
+        if self.pending_transactions.is_full() {
+            let evicted = self.pending_transactions
+                .eviction_candidate(&entry)
+                .ok_or(ChainError::MempoolFull { fee: transaction.fee })?;
+            self.pending_transactions.remove(&evicted);
+            console_log!("Evicted pending transaction {}: {:?}", evicted, EvictionReason::PoolFull);
+            self.last_evictions.push(EvictedTransaction {
+                transaction_id: evicted,
+                reason: EvictionReason::PoolFull,
+            });
         }
+
+        self.pending_transactions.push(entry);
+        console_log!("Transaction added: {}", transaction.id);
+        Ok(())
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn mine_pending_transactions(&mut self, mining_reward_address: &str) -> String {
-        // Add mining reward transaction
-        let reward_tx = Transaction::new_mining_reward(mining_reward_address, self.mining_reward);
-        self.pending_transactions.push(reward_tx);
+        // Re-verify the pool against confirmed state before building the block
+        self.evict_invalid_pending();
+
+        let supply = self.state.circulating_supply();
+        let subsidy = self.block_subsidy(self.blocks.len() as u64, supply);
+
+        // Best fee rates that fit next to the mining reward, which collects their fees
+        let mut reward_tx = Transaction::new_mining_reward(mining_reward_address, subsidy);
+        let mut transactions = self.pending_transactions.select(
+            MAX_BLOCK_TRANSACTIONS - 1,
+            MAX_BLOCK_BYTES - reward_tx.to_bytes().len(),
+        );
+
+        // Action mining waits for the next epoch once its budget is spent
+        let mut action_room = self.action_mint_room(supply, subsidy);
+        transactions.retain(|tx| {
+            if !is_action_mint(tx) {
+                return true;
+            }
+            if tx.amount > action_room {
+                return false;
+            }
+            action_room -= tx.amount;
+            true
+        });
+
+        let fees = transactions.iter().fold(0u64, |total, tx| total.saturating_add(tx.fee));
+        reward_tx.amount = subsidy.saturating_add(fees);
+        transactions.push(reward_tx);
 
         let previous_hash = self.get_latest_block().hash.clone();
         let mut new_block = Block::new(
             self.blocks.len() as u64,
             previous_hash,
-            self.pending_transactions.clone(),
+            transactions,
         );
 
+        // A clock behind recent blocks must still move past their median time
+        let earliest = median_time_past(&self.blocks).saturating_add(1);
+        if new_block.timestamp_millis().is_none_or(|time| time < earliest) {
+            new_block.set_timestamp_millis(earliest);
+        }
+
+        let mut next_state = self.state.clone();
+        next_state.apply_block(&new_block, self.emission.epoch_start(new_block.index));
+        new_block.state_root = next_state.state_root();
+
         // Proof-of-Action mining (lightweight)
-        new_block.mine_block(self.mining_difficulty);
-        
-        // Calculate carbon offset (each block saves CO2)
-        self.carbon_offset += 10; // 10g CO2 per block
+        new_block.mine_block(self.mining_bits);
         
         console_log!("Block mined: {} with {} transactions", new_block.hash, new_block.transactions.len());
         
-        self.blocks.push(new_block.clone());
-        self.pending_transactions.clear();
+        let hash = new_block.hash.clone();
+        self.append_block(new_block);
+        hash
+    }
+
+    // Blocks from other nodes; may extend the chain, join a side branch or trigger a reorg
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen]
+    pub fn submit_block(&mut self, block: &Block) -> BitnunResult<JsValue> {
+        let status = self.accept_block(block.clone())?;
+        Ok(serde_wasm_bindgen::to_value(&status).unwrap())
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn export_chain(&self) -> Vec<u8> {
+        self.export_chain_from(0)
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn export_chain_from(&self, height: u64) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write_chain(&mut bytes, height).expect("writing to memory cannot fail");
+        bytes
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn import_chain(bytes: &[u8]) -> BitnunResult<BitnunBlockchain> {
+        BitnunBlockchain::read_chain(&mut &bytes[..])
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn export_snapshot(&self, height: u64) -> BitnunResult<Vec<u8>> {
+        Ok(self.snapshot_at(height)?.to_bytes())
+    }
+
+    // `recent_blocks` is an export_chain_from(height) of the same chain
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn import_snapshot(snapshot: &[u8], recent_blocks: &[u8]) -> BitnunResult<BitnunBlockchain> {
+        let snapshot = StateSnapshot::decode(snapshot)?;
+        BitnunBlockchain::from_snapshot(&snapshot, &mut &recent_blocks[..])
+    }
 
-        new_block.hash
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen]
+    pub fn get_reorg_events(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.reorg_events).unwrap()
+    }
+
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen]
+    pub fn get_block_by_hash(&self, hash: &str) -> BitnunResult<JsValue> {
+        Ok(serde_wasm_bindgen::to_value(&self.block_by_hash(hash)?).unwrap())
     }
 
+    #[cfg(feature = "wasm")]
     #[wasm_bindgen]
+    pub fn get_block_by_height(&self, height: u64) -> BitnunResult<JsValue> {
+        Ok(serde_wasm_bindgen::to_value(&self.block_at(height)?).unwrap())
+    }
+
+    // The transaction with its inclusion block and confirmation count
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen]
+    pub fn get_transaction(&self, id: &str) -> BitnunResult<JsValue> {
+        Ok(serde_wasm_bindgen::to_value(&self.transaction(id)?).unwrap())
+    }
+
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen]
+    pub fn get_address_history(
+        &self,
+        address: &str,
+        cursor: Option<String>,
+        limit: usize,
+        type_filter: Option<String>,
+    ) -> BitnunResult<JsValue> {
+        let page = self.address_history(address, cursor.as_deref(), limit, type_filter.as_deref())?;
+        Ok(serde_wasm_bindgen::to_value(&page).unwrap())
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn get_balance(&self, address: &str) -> u64 {
-        let mut balance = 0u64;
+        self.state.balance_of(address)
+    }
 
-        for block in &self.blocks {
-            for transaction in &block.transactions {
-                if transaction.from_address == address {
-                    balance = balance.saturating_sub(transaction.amount);
-                }
-                if transaction.to_address == address {
-                    balance = balance.saturating_add(transaction.amount);
-                }
+    // Account state with a sparse merkle proof against the tip's state_root
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen]
+    pub fn get_balance_with_proof(&self, address: &str) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.balance_with_proof(address)).unwrap()
+    }
+
+    // Adds co-signatures for a multisig spend; the status says how many
+    // are collected and whether the spend entered the pool
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen]
+    pub fn submit_partial_signatures(&mut self, transaction: &Transaction) -> BitnunResult<JsValue> {
+        let status = self.collect_signatures(transaction)?;
+        Ok(serde_wasm_bindgen::to_value(&status).unwrap())
+    }
+
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen]
+    pub fn get_multisig_policy(&self, address: &str) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.multisig_policy(address)).unwrap()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn get_chain_id(&self) -> String {
+        self.chain_id.clone()
+    }
+
+    // Nonce the sender's next transaction must carry, counting queued ones
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn get_next_nonce(&self, address: &str) -> u64 {
+        let pending = self.pending_transactions
+            .transactions()
+            .filter(|tx| tx.from_address == address)
+            .count() as u64;
+        self.state.nonce_of(address) + pending
+    }
+
+    // Balance the next block may spend, leaving out funds still time-locked
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn get_spendable_balance(&self, address: &str) -> u64 {
+        self.state.spendable_of(address, self.blocks.len() as u64, runtime::now_millis() as u64)
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn get_pending_balance(&self, address: &str) -> u64 {
+        self.get_balance(address).saturating_sub(self.pending_debits(address))
+    }
+
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen]
+    pub fn get_last_evictions(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(self.last_evictions()).unwrap()
+    }
+
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen]
+    pub fn get_account_state(&self, address: &str) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.account_state(address)).unwrap()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn rebuild_state(&mut self) {
+        self.state = self.state_at(self.blocks.len());
+        console_log!("Account state rebuilt from {} blocks", self.blocks.len());
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn verify_state_consistency(&self) -> bool {
+        let rebuilt = self.state_at(self.blocks.len());
+        if rebuilt != self.state {
+            console_log!("Account state index diverges from rebuilt state");
+            return false;
+        }
+
+        // Cross-check every indexed balance against a full chain rescan
+        for (address, account) in &self.state.accounts {
+            let scanned = self.scan_balance(address);
+            if scanned != account.balance {
+                console_log!("Balance mismatch for {}: indexed {}, scanned {}", address, account.balance, scanned);
+                return false;
             }
         }
 
-        balance
+        true
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn validate_chain(&self) -> bool {
-        for i in 1..self.blocks.len() {
-            let current_block = &self.blocks[i];
-            let previous_block = &self.blocks[i - 1];
+        if !Self::validate_header_chain(&self.headers()) {
+            return false;
+        }
 
-            if current_block.hash != current_block.calculate_hash() {
-                console_log!("Invalid block hash at index {}", i);
+        for (position, block) in self.blocks.iter().enumerate() {
+            if block.hash != block.calculate_hash() {
+                console_log!("Invalid block hash at index {}", block.index);
                 return false;
             }
 
-            if current_block.previous_hash != previous_block.hash {
-                console_log!("Invalid previous hash at index {}", i);
+            if block.bits != self.difficulty_config.next_bits(&self.blocks[..position]) {
+                console_log!("Unexpected target at index {}", block.index);
                 return false;
             }
         }
@@ -119,26 +400,325 @@ impl BitnunBlockchain {
         true
     }
 
+    // Pool capacity before the lowest fee rate is evicted, and how long an entry may wait
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn configure_mempool(&mut self, max_transactions: usize, expiry_ms: u64) {
+        self.pending_transactions.set_config(MempoolConfig {
+            max_transactions,
+            expiry_ms,
+        });
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn get_pending_count(&self) -> usize {
+        self.pending_transactions.len()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn get_mining_bits(&self) -> u32 {
+        self.mining_bits
+    }
+
+    // Hex-encoded, since the total can exceed what JS numbers hold exactly
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn get_chain_work(&self) -> String {
+        format!("{:x}", self.chain_work())
+    }
+
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen]
+    pub fn validate_chain_full(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.validation_report()).unwrap()
+    }
+
+    // Light validation of linkage and proof-of-work without block bodies
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen]
+    pub fn validate_headers(headers: JsValue) -> bool {
+        match serde_wasm_bindgen::from_value::<Vec<BlockHeader>>(headers) {
+            Ok(headers) => Self::validate_header_chain(&headers),
+            Err(_) => false,
+        }
+    }
+
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen]
+    pub fn get_headers(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.headers()).unwrap()
+    }
+
+    #[cfg(feature = "wasm")]
     #[wasm_bindgen]
     pub fn get_chain_stats(&self) -> JsValue {
-        let stats = ChainStats {
-            total_blocks: self.blocks.len(),
-            total_transactions: self.blocks.iter().map(|b| b.transactions.len()).sum(),
-            total_supply: self.total_supply,
-            carbon_offset: self.carbon_offset,
-            mining_difficulty: self.mining_difficulty,
-        };
+        serde_wasm_bindgen::to_value(&self.chain_stats()).unwrap()
+    }
+
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen]
+    pub fn get_circulating_supply(&self, height: u64) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.circulating_supply_at(height)).unwrap()
+    }
+
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen]
+    pub fn get_emission_schedule(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.emission).unwrap()
+    }
+
+    // Where a proof of action must have been made to go into the next block:
+    // after the tip's median time and at most the allowed drift ahead of the clock
+    fn next_proof_window(&self) -> (i64, i64) {
+        (median_time_past(&self.blocks), runtime::now_millis() as i64 + MAX_FUTURE_DRIFT_MS)
+    }
 
-        serde_wasm_bindgen::to_value(&stats).unwrap()
+    fn validate_transaction(&self, transaction: &Transaction) -> Result<(), ChainError> {
+        transaction.check_fields().map_err(ChainError::MalformedTransaction)?;
+
+        if self.index.locate(&transaction.id).is_some() {
+            return Err(ChainError::DuplicateTransaction(transaction.id.clone()));
+        }
+
+        // Rewards and genesis are created by the chain itself; only action
+        // mining arrives through the pool
+        if transaction.is_system() {
+            if !is_action_mint(transaction) {
+                return Err(ChainError::MalformedTransaction(format!("{} can't be submitted", transaction.kind.tag())));
+            }
+
+            // Each action mints once, whether confirmed or still pending
+            let action_id = self.check_action_proof(transaction, self.next_proof_window())
+                .map_err(ChainError::InvalidActionProof)?
+                .action_id();
+            let pending = self.pending_transactions
+                .transactions()
+                .filter_map(|tx| tx.proof_of_action())
+                .any(|proof| proof.action_id() == action_id);
+            if pending || self.state.is_action_spent(&action_id) {
+                return Err(ChainError::ActionAlreadyMinted(action_id));
+            }
+            return Ok(());
+        }
+
+        if transaction.chain_id != self.chain_id {
+            return Err(ChainError::WrongChain {
+                expected: self.chain_id.clone(),
+                found: transaction.chain_id.clone(),
+            });
+        }
+
+        if !transaction.verify_authorization(self.state.multisig_of(&transaction.from_address)) {
+            return Err(ChainError::InvalidSignature);
+        }
+
+        // Nonces must continue the sender's sequence without gaps or repeats
+        let expected_nonce = self.get_next_nonce(&transaction.from_address);
+        if transaction.nonce != expected_nonce {
+            return Err(ChainError::InvalidNonce {
+                expected: expected_nonce,
+                found: transaction.nonce,
+            });
+        }
+
+        // Debits already queued in the pool count against the spendable balance
+        let available = self.get_spendable_balance(&transaction.from_address)
+            .saturating_sub(self.pending_debits(&transaction.from_address));
+        let required = transaction.total_debit().unwrap_or(u64::MAX);
+        if available < required {
+            return Err(ChainError::InsufficientBalance {
+                available,
+                required,
+            });
+        }
+
+        check_kind_state(&self.state, transaction).map_err(ChainError::InvalidKind)?;
+        match self.pending_kind_conflict(transaction) {
+            Some(conflict) => Err(ChainError::InvalidKind(conflict)),
+            None => Ok(()),
+        }
     }
 
-    fn validate_transaction(&self, transaction: &Transaction) -> bool {
-        if transaction.from_address == "" {
-            return true; // Mining reward or genesis
+    // Kind rules are checked against confirmed state only, so the pool must
+    // not hold anything that depends on another pending transaction: unstakes
+    // stay within the confirmed stake and each NFT moves at most once
+    fn pending_kind_conflict(&self, transaction: &Transaction) -> Option<String> {
+        if transaction.kind == TransactionKind::Unstake {
+            let stake = self.state.stake_of(&transaction.from_address);
+            let unstaking = self.pending_transactions
+                .transactions()
+                .filter(|tx| tx.kind == TransactionKind::Unstake && tx.from_address == transaction.from_address)
+                .fold(transaction.amount, |total, tx| total.saturating_add(tx.amount));
+            if unstaking > stake {
+                return Some(format!("pending unstakes of {} exceed the stake of {}", unstaking, stake));
+            }
         }
 
-        let balance = self.get_balance(&transaction.from_address);
-        balance >= transaction.amount
+        if let TransactionKind::RegisterMultisig(_) = transaction.kind {
+            let pending = self.pending_transactions
+                .transactions()
+                .any(|tx| matches!(tx.kind, TransactionKind::RegisterMultisig(_)) && tx.to_address == transaction.to_address);
+            if pending {
+                return Some(format!("multisig {} is already being registered", transaction.to_address));
+            }
+        }
+
+        let token_id = transaction.kind.nft_token_id()?;
+        self.pending_transactions
+            .transactions()
+            .any(|tx| tx.kind.nft_token_id() == Some(token_id))
+            .then(|| format!("token {} already moves in a pending transaction", token_id))
+    }
+
+    fn pending_debits(&self, address: &str) -> u64 {
+        self.pending_transactions
+            .transactions()
+            .filter(|tx| tx.from_address == address)
+            .fold(0u64, |total, tx| total.saturating_add(tx.total_debit().unwrap_or(u64::MAX)))
+    }
+
+    fn evict_invalid_pending(&mut self) {
+        self.prune_partial_transactions();
+        self.last_evictions.clear();
+        for transaction in self.pending_transactions.expire(runtime::now_millis()) {
+            console_log!("Evicted pending transaction {}: {:?}", transaction.id, EvictionReason::Expired);
+            self.last_evictions.push(EvictedTransaction {
+                transaction_id: transaction.id,
+                reason: EvictionReason::Expired,
+            });
+        }
+
+        let pending = self.pending_transactions.take();
+        let mut senders: HashMap<String, (u64, u64)> = HashMap::new(); // (debited, next nonce)
+        let mut actions: HashSet<String> = HashSet::new();
+        let mut unstaking: HashMap<String, u64> = HashMap::new();
+        let mut tokens: HashSet<String> = HashSet::new();
+        let mut registrations: HashSet<String> = HashSet::new();
+        let (height, now) = (self.blocks.len() as u64, runtime::now_millis() as u64);
+        let proof_window = self.next_proof_window();
+
+        for entry in pending {
+            let transaction = &entry.transaction;
+            let reason = if !transaction.is_valid() {
+                Some(EvictionReason::Malformed)
+            } else if transaction.is_system() {
+                match self.check_action_proof(transaction, proof_window).map(|proof| proof.action_id()) {
+                    Ok(action_id) if !self.state.is_action_spent(&action_id) => {
+                        if actions.insert(action_id) {
+                            None
+                        } else {
+                            Some(EvictionReason::InvalidActionProof)
+                        }
+                    }
+                    _ => Some(EvictionReason::InvalidActionProof),
+                }
+            } else if transaction.chain_id != self.chain_id {
+                Some(EvictionReason::WrongChain)
+            } else {
+                let (debited, next_nonce) = senders
+                    .entry(transaction.from_address.clone())
+                    .or_insert_with(|| (0, self.state.nonce_of(&transaction.from_address)));
+
+                let unstaked = unstaking.get(&transaction.from_address).copied().unwrap_or(0);
+                let kind_conflict = check_kind_state(&self.state, transaction).is_err()
+                    || (transaction.kind == TransactionKind::Unstake
+                        && unstaked.saturating_add(transaction.amount) > self.state.stake_of(&transaction.from_address))
+                    || transaction.kind.nft_token_id().is_some_and(|token_id| tokens.contains(token_id))
+                    || (matches!(transaction.kind, TransactionKind::RegisterMultisig(_))
+                        && registrations.contains(&transaction.to_address));
+
+                if transaction.nonce != *next_nonce {
+                    Some(EvictionReason::InvalidNonce)
+                } else if kind_conflict {
+                    Some(EvictionReason::InvalidKind)
+                } else {
+                    match transaction.total_debit().and_then(|debit| debited.checked_add(debit)) {
+                        Some(total) if total <= self.state.spendable_of(&transaction.from_address, height, now) => {
+                            *debited = total;
+                            *next_nonce += 1;
+                            None
+                        }
+                        _ => Some(EvictionReason::InsufficientBalance),
+                    }
+                }
+            };
+
+            match reason {
+                Some(reason) => {
+                    console_log!("Evicted pending transaction {}: {:?}", transaction.id, reason);
+                    self.last_evictions.push(EvictedTransaction {
+                        transaction_id: entry.transaction.id,
+                        reason,
+                    });
+                }
+                None => {
+                    if transaction.kind == TransactionKind::Unstake {
+                        *unstaking.entry(transaction.from_address.clone()).or_default() += transaction.amount;
+                    }
+                    if let Some(token_id) = transaction.kind.nft_token_id() {
+                        tokens.insert(token_id.to_string());
+                    }
+                    if let TransactionKind::RegisterMultisig(_) = transaction.kind {
+                        registrations.insert(transaction.to_address.clone());
+                    }
+                    self.pending_transactions.push(entry);
+                }
+            }
+        }
+    }
+
+    fn scan_balance(&self, address: &str) -> u64 {
+        let mut balance = self.base_state.balance_of(address);
+
+        for block in &self.blocks[self.pruned_height as usize..] {
+            for transaction in &block.transactions {
+                if transaction.from_address == address {
+                    balance = balance.saturating_sub(transaction.total_debit().unwrap_or(u64::MAX));
+                    if transaction.kind == TransactionKind::Unstake {
+                        balance = balance.saturating_add(transaction.amount);
+                    }
+                }
+                for (recipient, amount) in transaction.credits() {
+                    if recipient == address {
+                        balance = balance.saturating_add(amount);
+                    }
+                }
+            }
+        }
+
+        balance
+    }
+
+    // Adds an already validated block on top of the active chain
+    fn append_block(&mut self, block: Block) {
+        // Calculate carbon offset (each block saves CO2)
+        self.carbon_offset += 10; // 10g CO2 per block
+
+        let included: HashSet<&str> = block.transactions.iter().map(|tx| tx.id.as_str()).collect();
+        self.pending_transactions.retain(|tx| !included.contains(tx.id.as_str()));
+
+        let undo = self.state.undo_for(&block);
+        self.state.apply_block(&block, self.emission.epoch_start(block.index));
+        self.record_undo(undo);
+        self.index.add_block(&block);
+        self.blocks.push(block);
+        self.prune_side_blocks();
+        if let Err(error) = self.persist_blocks_from(self.blocks.len() - 1, &HashSet::new()) {
+            console_log!("Failed to persist block: {}", error);
+        }
+
+        let next_bits = self.difficulty_config.next_bits(&self.blocks);
+        if next_bits != self.mining_bits {
+            console_log!("Target retargeted from {:#010x} to {:#010x}", self.mining_bits, next_bits);
+            self.mining_bits = next_bits;
+        }
+    }
+
+    // Account state after the first `height` blocks, replayed from the pruned base
+    fn state_at(&self, height: usize) -> AccountStateIndex {
+        let mut state = self.base_state.clone();
+        for block in &self.blocks[self.pruned_height as usize..height] {
+            state.apply_block(block, self.emission.epoch_start(block.index));
+        }
+        state
     }
 
     fn get_latest_block(&self) -> &Block {
@@ -146,11 +726,233 @@ impl BitnunBlockchain {
     }
 }
 
-#[derive(Serialize, Deserialize)]
-struct ChainStats {
-    total_blocks: usize,
-    total_transactions: usize,
-    total_supply: u64,
-    carbon_offset: u64,
-    mining_difficulty: u32,
-}
\ No newline at end of file
+impl BitnunBlockchain {
+    pub fn from_genesis_config(config: &GenesisConfig) -> BitnunResult<BitnunBlockchain> {
+        let genesis_block = config.genesis_block()?;
+        console_log!("Genesis block created: {}", genesis_block.hash);
+
+        let mut chain = BitnunBlockchain::from_genesis(&config.chain_id, genesis_block);
+        chain.configure(&config.difficulty, config.mining_reward, &config.emission);
+        chain.genesis_validators = config.validators.clone();
+        chain.genesis_allocations = config.allocations.clone();
+        Ok(chain)
+    }
+
+    pub fn genesis_validators(&self) -> &[GenesisValidator] {
+        &self.genesis_validators
+    }
+
+    pub(crate) fn from_genesis(chain_id: &str, genesis_block: Block) -> BitnunBlockchain {
+        let mut state = AccountStateIndex::new();
+        state.apply_block(&genesis_block, 0);
+
+        let difficulty_config = DifficultyConfig::default();
+        let mining_bits = difficulty_config.next_bits(std::slice::from_ref(&genesis_block));
+        let index = ChainIndex::build(std::slice::from_ref(&genesis_block));
+        
+        BitnunBlockchain {
+            chain_id: chain_id.to_string(),
+            blocks: vec![genesis_block],
+            side_blocks: HashMap::new(),
+            reorg_events: Vec::new(),
+            pruned_height: 0,
+            base_state: AccountStateIndex::new(),
+            index,
+            pending_transactions: Mempool::default(),
+            partial_transactions: HashMap::new(),
+            state,
+            undo_log: VecDeque::new(),
+            last_evictions: Vec::new(),
+            difficulty_config,
+            mining_bits,
+            mining_reward: 1000, // 10.00 BTN in cents
+            emission: EmissionSchedule::default(),
+            genesis_validators: Vec::new(),
+            genesis_allocations: Vec::new(),
+            carbon_offset: 0,
+            store: None,
+        }
+    }
+
+    pub fn account_state(&self, address: &str) -> AccountState {
+        self.state.get(address).cloned().unwrap_or_default()
+    }
+
+    pub fn last_evictions(&self) -> &[EvictedTransaction] {
+        &self.last_evictions
+    }
+
+    pub fn difficulty_config(&self) -> &DifficultyConfig {
+        &self.difficulty_config
+    }
+
+    pub fn headers(&self) -> Vec<BlockHeader> {
+        self.blocks.iter().map(|block| block.header()).collect()
+    }
+
+    pub fn chain_stats(&self) -> ChainStats {
+        ChainStats {
+            total_blocks: self.blocks.len(),
+            total_transactions: self.blocks.iter().map(|b| b.transactions.len()).sum(),
+            total_supply: self.state.circulating_supply(),
+            max_supply: self.emission.max_supply,
+            carbon_offset: self.carbon_offset,
+            mining_bits: self.mining_bits,
+            chain_work: format!("{:x}", self.chain_work()),
+        }
+    }
+
+    // Total expected hashes behind the chain, used to compare competing chains
+    pub fn chain_work(&self) -> u128 {
+        self.blocks.iter().fold(0u128, |total, block| total.saturating_add(block.work()))
+    }
+
+    // Thorough mode: replays the chain from genesis and reports the first violation
+    pub fn validation_report(&self) -> ValidationReport {
+        match self.replay_chain() {
+            Ok(state) => ValidationReport {
+                valid: true,
+                blocks_checked: state.height,
+                violation: None,
+            },
+            Err(violation) => {
+                console_log!("Chain validation failed at block {}: {:?} ({})",
+                    violation.block_index, violation.rule, violation.detail);
+                ValidationReport {
+                    valid: false,
+                    blocks_checked: violation.block_index,
+                    violation: Some(violation),
+                }
+            }
+        }
+    }
+
+    pub fn validate_header_chain(headers: &[BlockHeader]) -> bool {
+        for i in 1..headers.len() {
+            let current = &headers[i];
+            let previous = &headers[i - 1];
+
+            if current.index != previous.index + 1 {
+                console_log!("Non-consecutive header index {}", current.index);
+                return false;
+            }
+
+            if current.previous_hash != previous.calculate_hash() {
+                console_log!("Invalid previous hash at index {}", current.index);
+                return false;
+            }
+
+            if !current.meets_target() {
+                console_log!("Insufficient proof-of-work at index {}", current.index);
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChainStats {
+    pub total_blocks: usize,
+    pub total_transactions: usize,
+    pub total_supply: u64,
+    pub max_supply: u64,
+    pub carbon_offset: u64,
+    pub mining_bits: u32,
+    pub chain_work: String, // Hex-encoded cumulative work
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EvictedTransaction {
+    pub transaction_id: String,
+    pub reason: EvictionReason,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvictionReason {
+    Malformed,
+    InsufficientBalance,
+    InvalidNonce,
+    WrongChain,
+    PoolFull, // Outbid by a higher fee rate
+    Expired,
+    InvalidActionProof, // Invalid, or the action was minted since
+    InvalidKind, // Breaks a rule of its kind, e.g. unstaking more than is staked
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::test_support::{dev_keypair, signed};
+    use crate::error::BitnunError;
+
+    fn transfer(from: &KeyPair, to_address: &str, amount: u64, nonce: u64) -> Transaction {
+        let mut transfer = Transaction::new(&from.address(), to_address, amount);
+        transfer.set_nonce(nonce);
+        signed(transfer, from)
+    }
+
+    // A dev chain where `holder` has 1000 confirmed
+    fn funded_chain(holder: &KeyPair) -> BitnunBlockchain {
+        let mut chain = BitnunBlockchain::from_preset("dev").unwrap();
+        chain.add_transaction(&transfer(&dev_keypair(), &holder.address(), 1_000, 0)).unwrap();
+        chain.mine_pending_transactions("btnminer");
+        chain
+    }
+
+    #[test]
+    fn pending_debits_count_against_admission() {
+        let holder = KeyPair::generate();
+        let mut chain = funded_chain(&holder);
+
+        chain.add_transaction(&transfer(&holder, "btnpayee", 600, 0)).unwrap();
+        assert_eq!(chain.get_pending_balance(&holder.address()), 400);
+        match chain.add_transaction(&transfer(&holder, "btnpayee", 600, 1)) {
+            Err(BitnunError::Chain(ChainError::InsufficientBalance { available, required })) => {
+                assert_eq!((available, required), (400, 600));
+            }
+            other => panic!("expected an insufficient balance error, got {:?}", other),
+        }
+
+        chain.add_transaction(&transfer(&holder, "btnpayee", 400, 1)).unwrap();
+        chain.mine_pending_transactions("btnminer");
+        assert_eq!(chain.get_balance(&holder.address()), 0);
+        assert_eq!(chain.get_balance("btnpayee"), 1_000);
+        assert!(chain.last_evictions().is_empty());
+    }
+
+    #[test]
+    fn mining_evicts_pending_transactions_a_received_block_invalidated() {
+        let holder = KeyPair::generate();
+        let mut chain = funded_chain(&holder);
+        let mut other = chain.clone();
+
+        let spent_nonce = transfer(&holder, "btnpayee", 100, 0);
+        let overdrawn = transfer(&holder, "btnpayee", 850, 1);
+        let unaffected = transfer(&dev_keypair(), "btnpayee", 5, 1);
+        for transaction in [&spent_nonce, &overdrawn, &unaffected] {
+            chain.add_transaction(transaction).unwrap();
+        }
+
+        // Another node confirms a different spend of the same nonce first
+        other.add_transaction(&transfer(&holder, "btnelsewhere", 800, 0)).unwrap();
+        other.mine_pending_transactions("btnother");
+        chain.accept_block(other.get_latest_block().clone()).unwrap();
+
+        chain.mine_pending_transactions("btnminer");
+        let evicted: Vec<(&str, EvictionReason)> = chain.last_evictions()
+            .iter()
+            .map(|eviction| (eviction.transaction_id.as_str(), eviction.reason))
+            .collect();
+        assert_eq!(evicted, vec![
+            (spent_nonce.id.as_str(), EvictionReason::InvalidNonce),
+            (overdrawn.id.as_str(), EvictionReason::InsufficientBalance),
+        ]);
+
+        let mined: Vec<&str> = chain.get_latest_block().transactions.iter().map(|tx| tx.id.as_str()).collect();
+        assert!(mined.contains(&unaffected.id.as_str()));
+        assert_eq!(chain.get_balance(&holder.address()), 200);
+        assert_eq!(chain.get_pending_count(), 0);
+    }
+}
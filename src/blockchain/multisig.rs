@@ -0,0 +1,313 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use crate::blockchain::{BitnunBlockchain, PoolEntry, Transaction};
+use crate::blockchain::keys::{address_from_bytes, derive_address, verify_signature};
+use crate::blockchain::encoding::{DecodeError, Decoder, Encoder};
+use crate::error::{BitnunResult, ChainError};
+use crate::runtime;
+
+pub const MAX_MULTISIG_KEYS: usize = 16;
+
+// Bounds on spends held while they gather co-signatures; entries also expire
+// with the mempool's expiry_ms or once their nonce is used
+pub const MAX_PARTIALS_PER_ACCOUNT: usize = 16;
+pub const MAX_PARTIAL_TRANSACTIONS: usize = 1024;
+
+// An M-of-N account, registered on-chain with a RegisterMultisig
+// transaction. Its address is derived from the policy, so nobody holds a
+// single key for it; spending needs `threshold` co-signatures.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MultisigPolicy {
+    pub threshold: u32,
+    pub public_keys: Vec<String>, // Hex-encoded Ed25519 keys, in registration order
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CoSignature {
+    pub public_key: String,
+    pub signature: String, // Over the transaction's signing_payload
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SignatureStatus {
+    pub transaction_id: String,
+    pub collected: usize,
+    pub required: usize,
+    pub admitted: bool, // Reached the threshold and entered the pool
+}
+
+impl MultisigPolicy {
+    pub fn check(&self) -> Result<(), String> {
+        if self.public_keys.is_empty() || self.public_keys.len() > MAX_MULTISIG_KEYS {
+            return Err(format!("a multisig needs 1 to {} keys", MAX_MULTISIG_KEYS));
+        }
+        if self.threshold == 0 || self.threshold as usize > self.public_keys.len() {
+            return Err(format!("threshold {} of {} keys", self.threshold, self.public_keys.len()));
+        }
+
+        // Compared as bytes, so differently cased hex can't pose as a second member
+        let mut seen = HashSet::new();
+        for public_key in &self.public_keys {
+            if derive_address(public_key).is_err() {
+                return Err(format!("invalid key {}", public_key));
+            }
+            if !seen.insert(normalized_key(public_key)) {
+                return Err(format!("key {} appears twice", public_key));
+            }
+        }
+        Ok(())
+    }
+
+    // Prefix plus the first 20 bytes of SHA-256 over the threshold and the
+    // sorted, normalized keys, so neither key order nor hex case changes
+    // the address
+    pub fn address(&self) -> String {
+        let mut public_keys: Vec<String> = self.public_keys.iter().map(|key| normalized_key(key)).collect();
+        public_keys.sort();
+
+        let mut preimage = b"multisig".to_vec();
+        preimage.extend_from_slice(&self.threshold.to_be_bytes());
+        for public_key in public_keys {
+            preimage.extend_from_slice(public_key.as_bytes());
+        }
+        address_from_bytes(&preimage)
+    }
+
+    pub fn is_member(&self, public_key: &str) -> bool {
+        let public_key = normalized_key(public_key);
+        self.public_keys.iter().any(|member| normalized_key(member) == public_key)
+    }
+
+    pub fn encode_fields(&self, encoder: &mut Encoder) {
+        encoder.put_u32(self.threshold);
+        encoder.put_u32(self.public_keys.len() as u32);
+        for public_key in &self.public_keys {
+            encoder.put_str(public_key);
+        }
+    }
+
+    pub fn decode_fields(decoder: &mut Decoder) -> Result<MultisigPolicy, DecodeError> {
+        let threshold = decoder.get_u32()?;
+        let mut public_keys = Vec::new();
+        for _ in 0..decoder.get_u32()? {
+            public_keys.push(decoder.get_string()?);
+        }
+        Ok(MultisigPolicy { threshold, public_keys })
+    }
+}
+
+impl Transaction {
+    // Single-key signature, or co-signatures when the sender is a multisig
+    pub fn verify_authorization(&self, policy: Option<&MultisigPolicy>) -> bool {
+        match policy {
+            Some(policy) => self.verify_multisig(policy),
+            None => self.verify(),
+        }
+    }
+
+    // Every co-signature must be a member's valid signature, each member at
+    // most once, and there must be at least `threshold` of them
+    pub fn verify_multisig(&self, policy: &MultisigPolicy) -> bool {
+        let payload = self.signing_payload();
+        let mut signers = HashSet::new();
+
+        for co_signature in &self.co_signatures {
+            if !policy.is_member(&co_signature.public_key)
+                || !signers.insert(normalized_key(&co_signature.public_key))
+                || !verify_signature(&co_signature.public_key, &payload, &co_signature.signature)
+            {
+                return false;
+            }
+        }
+
+        signers.len() >= policy.threshold as usize
+    }
+}
+
+impl BitnunBlockchain {
+    // Gathers co-signatures for a spend from a multisig account. Each call
+    // may carry any subset of signatures over the same transaction; once the
+    // threshold is met it is admitted to the pool like any other.
+    pub fn collect_signatures(&mut self, transaction: &Transaction) -> BitnunResult<SignatureStatus> {
+        let policy = self.state
+            .multisig_of(&transaction.from_address)
+            .cloned()
+            .ok_or_else(|| ChainError::NotMultisig(transaction.from_address.clone()))?;
+
+        transaction.check_fields().map_err(ChainError::MalformedTransaction)?;
+        if transaction.chain_id != self.chain_id {
+            return Err(ChainError::WrongChain {
+                expected: self.chain_id.clone(),
+                found: transaction.chain_id.clone(),
+            }.into());
+        }
+        let expected_nonce = self.state.nonce_of(&transaction.from_address);
+        if transaction.nonce < expected_nonce {
+            return Err(ChainError::InvalidNonce {
+                expected: expected_nonce,
+                found: transaction.nonce,
+            }.into());
+        }
+        if transaction.co_signatures.is_empty() {
+            return Err(ChainError::MalformedTransaction("no co-signatures to collect".to_string()).into());
+        }
+
+        let payload = transaction.signing_payload();
+        for co_signature in &transaction.co_signatures {
+            if !policy.is_member(&co_signature.public_key)
+                || !verify_signature(&co_signature.public_key, &payload, &co_signature.signature)
+            {
+                return Err(ChainError::InvalidSignature.into());
+            }
+        }
+
+        self.prune_partial_transactions();
+        if !self.partial_transactions.contains_key(&transaction.id) {
+            let for_account = self.partial_transactions
+                .values()
+                .filter(|entry| entry.transaction.from_address == transaction.from_address)
+                .count();
+            if for_account >= MAX_PARTIALS_PER_ACCOUNT || self.partial_transactions.len() >= MAX_PARTIAL_TRANSACTIONS {
+                return Err(ChainError::PartialSignaturesFull(transaction.from_address.clone()).into());
+            }
+        }
+
+        let collected = &mut self.partial_transactions
+            .entry(transaction.id.clone())
+            .or_insert_with(|| PoolEntry::new(Transaction {
+                co_signatures: Vec::new(),
+                ..transaction.clone()
+            }))
+            .transaction;
+        if collected.signing_payload() != payload {
+            return Err(ChainError::PartialSignatureMismatch(transaction.id.clone()).into());
+        }
+        for co_signature in &transaction.co_signatures {
+            let signer = normalized_key(&co_signature.public_key);
+            if !collected.co_signatures.iter().any(|existing| normalized_key(&existing.public_key) == signer) {
+                collected.co_signatures.push(co_signature.clone());
+            }
+        }
+
+        let mut status = SignatureStatus {
+            transaction_id: transaction.id.clone(),
+            collected: collected.co_signatures.len(),
+            required: policy.threshold as usize,
+            admitted: false,
+        };
+        if status.collected >= status.required {
+            let complete = collected.clone();
+            self.add_transaction(&complete)?;
+            self.partial_transactions.remove(&transaction.id);
+            status.admitted = true;
+        }
+        Ok(status)
+    }
+
+    pub fn partial_transaction(&self, id: &str) -> Option<&Transaction> {
+        self.partial_transactions.get(id).map(|entry| &entry.transaction)
+    }
+
+    // Drops partial spends that expired or whose nonce was used meanwhile
+    pub(crate) fn prune_partial_transactions(&mut self) {
+        let cutoff = runtime::now_millis() - self.pending_transactions.config().expiry_ms as f64;
+        let state = &self.state;
+        self.partial_transactions.retain(|_, entry| {
+            entry.received_at >= cutoff && entry.transaction.nonce >= state.nonce_of(&entry.transaction.from_address)
+        });
+    }
+
+    pub fn multisig_policy(&self, address: &str) -> Option<&MultisigPolicy> {
+        self.state.multisig_of(address)
+    }
+}
+
+// Lower-case hex, the form keys are compared and hashed in
+fn normalized_key(public_key: &str) -> String {
+    public_key.to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::KeyPair;
+    use crate::blockchain::test_support::{dev_keypair, signed, DEV_CHAIN_ID};
+    use crate::error::BitnunError;
+
+    // A dev chain with a registered 2-of-3 multisig, and its member keys
+    fn chain_with_multisig() -> (BitnunBlockchain, MultisigPolicy, Vec<KeyPair>) {
+        let dev = dev_keypair();
+        let members: Vec<KeyPair> = (0..3).map(|_| KeyPair::generate()).collect();
+        let policy = MultisigPolicy {
+            threshold: 2,
+            public_keys: members.iter().map(|member| member.public_key()).collect(),
+        };
+
+        let mut chain = BitnunBlockchain::from_preset("dev").unwrap();
+        let registration = signed(Transaction::new_multisig_registration(&dev.address(), 10000, policy.clone()), &dev);
+        chain.add_transaction(&registration).unwrap();
+        chain.mine_pending_transactions("btnminer");
+        (chain, policy, members)
+    }
+
+    fn partial(policy: &MultisigPolicy, signer: &KeyPair) -> Transaction {
+        let mut spend = Transaction::new(&policy.address(), "btnpayee", 100);
+        spend.set_chain_id(DEV_CHAIN_ID);
+        spend.co_sign(signer);
+        spend
+    }
+
+    #[test]
+    fn differently_cased_copies_of_a_key_are_one_member() {
+        let first = KeyPair::generate().public_key();
+        let second = KeyPair::generate().public_key();
+
+        let duplicated = MultisigPolicy {
+            threshold: 2,
+            public_keys: vec![first.clone(), first.to_ascii_uppercase(), second.clone()],
+        };
+        assert!(duplicated.check().is_err());
+
+        let lower = MultisigPolicy { threshold: 2, public_keys: vec![first.clone(), second.clone()] };
+        let upper = MultisigPolicy { threshold: 2, public_keys: vec![second.to_ascii_uppercase(), first.to_ascii_uppercase()] };
+        assert_eq!(lower.check(), Ok(()));
+        assert_eq!(lower.address(), upper.address());
+        assert!(lower.is_member(&first.to_ascii_uppercase()));
+    }
+
+    #[test]
+    fn partial_signatures_are_validated_and_bounded() {
+        let (mut chain, policy, members) = chain_with_multisig();
+
+        let mut unsigned = Transaction::new(&policy.address(), "btnpayee", 100);
+        unsigned.set_chain_id(DEV_CHAIN_ID);
+        assert!(chain.collect_signatures(&unsigned).is_err());
+        let mut wrong_chain = partial(&policy, &members[0]);
+        wrong_chain.set_chain_id("elsewhere");
+        wrong_chain.co_sign(&members[0]);
+        assert!(chain.collect_signatures(&wrong_chain).is_err());
+        assert!(chain.partial_transactions.is_empty());
+
+        for _ in 0..MAX_PARTIALS_PER_ACCOUNT {
+            let status = chain.collect_signatures(&partial(&policy, &members[0])).unwrap();
+            assert!(!status.admitted);
+        }
+        match chain.collect_signatures(&partial(&policy, &members[1])) {
+            Err(BitnunError::Chain(ChainError::PartialSignaturesFull(address))) => assert_eq!(address, policy.address()),
+            other => panic!("expected the account's partials to be full, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn stale_partial_signatures_expire() {
+        let (mut chain, policy, members) = chain_with_multisig();
+        let spend = partial(&policy, &members[0]);
+        chain.collect_signatures(&spend).unwrap();
+        assert!(chain.partial_transaction(&spend.id).is_some());
+
+        chain.configure_mempool(5000, 0);
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        chain.prune_partial_transactions();
+        assert!(chain.partial_transaction(&spend.id).is_none());
+    }
+}
@@ -0,0 +1,180 @@
+// Proof-of-work targets in compact "bits" form: the top byte is the target's
+// length in bytes and the low 23 bits its leading digits, so the 256-bit
+// target is mantissa * 256^(exponent - 3). A hash meets the target when its
+// raw digest, read as a big-endian number, is not above it.
+
+pub const EASIEST_BITS: u32 = 0x207fffff; // Roughly every other hash qualifies
+
+pub fn target_from_compact(bits: u32) -> [u8; 32] {
+    let exponent = (bits >> 24) as i64;
+    let mantissa = bits & 0x007fffff;
+    let mut target = [0u8; 32];
+
+    for i in 0..3 {
+        let byte = ((mantissa >> (8 * i)) & 0xff) as u8;
+        let position = 34 - exponent - i;
+        if position < 0 {
+            if byte != 0 {
+                return [0xff; 32]; // Overflows 256 bits
+            }
+        } else if position < 32 {
+            target[position as usize] = byte;
+        }
+    }
+
+    target
+}
+
+pub fn compact_from_target(target: &[u8; 32]) -> u32 {
+    let first = match target.iter().position(|byte| *byte != 0) {
+        Some(first) => first,
+        None => return 0,
+    };
+
+    let mut size = (32 - first) as u32;
+    let mut mantissa = 0u32;
+    for i in 0..3 {
+        mantissa = (mantissa << 8) | *target.get(first + i).unwrap_or(&0) as u32;
+    }
+
+    // The top mantissa bit is a sign bit in the compact form
+    if mantissa & 0x00800000 != 0 {
+        mantissa >>= 8;
+        size += 1;
+    }
+
+    (size << 24) | mantissa
+}
+
+pub fn meets_target(digest: &[u8; 32], bits: u32) -> bool {
+    bits & 0x007fffff != 0 && digest <= &target_from_compact(bits)
+}
+
+// Expected number of hashes to find a block at this target, about 2^256 / target
+pub fn work_from_compact(bits: u32) -> u128 {
+    let mantissa = (bits & 0x007fffff) as u128;
+    if mantissa == 0 {
+        return 0;
+    }
+
+    let shift = 256 - 8 * ((bits >> 24) as i64 - 3);
+    if shift <= 0 {
+        return 1;
+    }
+    if shift <= 127 {
+        return ((1u128 << shift) / mantissa).max(1);
+    }
+
+    let base = (1u128 << 127) / mantissa;
+    let extra = (shift - 127) as u32;
+    if base.leading_zeros() >= extra {
+        base << extra
+    } else {
+        u128::MAX
+    }
+}
+
+// Multiplies the target by numerator / denominator, keeping compact precision
+pub fn scale_compact(bits: u32, numerator: u64, denominator: u64) -> u32 {
+    let mut exponent = (bits >> 24) as i64 - 5;
+    let mut mantissa = (((bits & 0x007fffff) as u128) << 40) * numerator as u128 / denominator.max(1) as u128;
+    if mantissa == 0 {
+        return 0;
+    }
+
+    while mantissa > 0x007fffff {
+        mantissa >>= 8;
+        exponent += 1;
+    }
+    while mantissa < 0x00008000 && exponent > 3 {
+        mantissa <<= 8;
+        exponent -= 1;
+    }
+    if exponent < 3 {
+        let shift = 8 * (3 - exponent);
+        mantissa = if shift < 128 { mantissa >> shift } else { 0 };
+        exponent = 3;
+    }
+    if exponent > 0xff {
+        return compact_from_target(&[0xff; 32]);
+    }
+
+    ((exponent as u32) << 24) | mantissa as u32
+}
+
+// Whether `bits` asks for less work than `limit`
+pub fn is_easier_than(bits: u32, limit: u32) -> bool {
+    target_from_compact(bits) > target_from_compact(limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target_with(bytes: &[(usize, u8)]) -> [u8; 32] {
+        let mut target = [0u8; 32];
+        for (position, byte) in bytes {
+            target[*position] = *byte;
+        }
+        target
+    }
+
+    #[test]
+    fn compact_targets_expand_to_their_256_bit_value() {
+        assert_eq!(target_from_compact(0x1d00ffff), target_with(&[(4, 0xff), (5, 0xff)]));
+        assert_eq!(target_from_compact(EASIEST_BITS), target_with(&[(0, 0x7f), (1, 0xff), (2, 0xff)]));
+        assert_eq!(target_from_compact(0x03123456), target_with(&[(29, 0x12), (30, 0x34), (31, 0x56)]));
+        // Digits below the last byte are dropped, digits above 256 bits saturate
+        assert_eq!(target_from_compact(0x02123456), target_with(&[(30, 0x12), (31, 0x34)]));
+        assert_eq!(target_from_compact(0x2300ffff), [0xff; 32]);
+    }
+
+    #[test]
+    fn compact_form_round_trips() {
+        for bits in [0x1d00ffff, 0x1b0404cb, 0x1f00ffff, 0x2000ffff, EASIEST_BITS, 0x03123456, 0x17008000] {
+            assert_eq!(compact_from_target(&target_from_compact(bits)), bits, "bits {:#010x}", bits);
+        }
+
+        // A leading digit with the top bit set moves into the next byte
+        let target = target_with(&[(10, 0x80)]);
+        assert_eq!(compact_from_target(&target), 0x17008000);
+        assert_eq!(target_from_compact(0x17008000), target);
+
+        // Only three digits survive, and never rounding the target up
+        let precise = target_with(&[(4, 0x12), (5, 0x34), (6, 0x56), (7, 0x78)]);
+        let bits = compact_from_target(&precise);
+        assert_eq!(bits, 0x1c123456);
+        assert!(target_from_compact(bits) < precise);
+
+        assert_eq!(compact_from_target(&[0u8; 32]), 0);
+    }
+
+    #[test]
+    fn digests_meet_targets_as_big_endian_numbers() {
+        let target = target_from_compact(0x1f00ffff);
+        assert!(meets_target(&target, 0x1f00ffff));
+        let mut above = target;
+        above[1] = 1;
+        assert!(!meets_target(&above, 0x1f00ffff));
+        assert!(meets_target(&[0u8; 32], 0x1f00ffff));
+        // A zero mantissa is no target at all
+        assert!(!meets_target(&[0u8; 32], 0x1f000000));
+    }
+
+    #[test]
+    fn work_is_the_expected_hash_count() {
+        assert_eq!(work_from_compact(0x1d00ffff), 0x100010001);
+        assert_eq!(work_from_compact(0x1c00ffff), 0x100010001 * 256);
+        assert_eq!(work_from_compact(0x2000ffff), 256);
+        assert_eq!(work_from_compact(EASIEST_BITS), 2);
+        assert_eq!(work_from_compact(0x1f000000), 0);
+        assert_eq!(work_from_compact(0x0300ffff), u128::MAX);
+
+        // Halving the target doubles the work
+        let halved = scale_compact(0x1d00ffff, 1, 2);
+        assert_eq!(target_from_compact(halved), target_with(&[(4, 0x7f), (5, 0xff), (6, 0x80)]));
+        assert_eq!(work_from_compact(halved), 2 * work_from_compact(0x1d00ffff));
+        assert!(is_easier_than(0x1d00ffff, halved));
+        assert!(!is_easier_than(halved, 0x1d00ffff));
+    }
+}
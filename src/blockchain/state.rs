@@ -0,0 +1,559 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use crate::blockchain::{nft_key, nft_token_id, Block, MultisigPolicy, Transaction, TransactionKind};
+use crate::blockchain::encoding::{DecodeError, Decoder, Encoder, KIND_ACCOUNT_STATE, KIND_STATE_SUMMARY};
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccountState {
+    pub balance: u64,
+    pub nonce: u64, // Number of transactions sent from this account
+    pub last_activity: u64, // Index of the last block touching this account
+    #[serde(default)]
+    pub stake: u64, // Locked with validators, not spendable
+    #[serde(default)]
+    pub token_balances: BTreeMap<String, u64>, // By token id
+    #[serde(default)]
+    pub locked: Vec<LockedFunds>, // Part of balance not yet spendable
+    #[serde(default)]
+    pub multisig: Option<MultisigPolicy>, // Spends need co-signatures instead of one key
+}
+
+// A time-locked credit, held until both unlock conditions are met
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LockedFunds {
+    pub amount: u64,
+    pub unlock_height: u64, // 0 for none
+    pub unlock_time: u64, // Unix milliseconds; 0 for none
+}
+
+impl LockedFunds {
+    // Spendable in a block at `height` with timestamp `time`
+    pub fn is_mature(&self, height: u64, time: u64) -> bool {
+        height >= self.unlock_height && time >= self.unlock_time
+    }
+}
+
+impl AccountState {
+    // Balance a block at `height` with timestamp `time` may spend
+    pub fn spendable(&self, height: u64, time: u64) -> u64 {
+        let locked = self.locked
+            .iter()
+            .filter(|lock| !lock.is_mature(height, time))
+            .fold(0u64, |total, lock| total.saturating_add(lock.amount));
+        self.balance.saturating_sub(locked)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new(KIND_ACCOUNT_STATE);
+        encoder.put_u64(self.balance);
+        encoder.put_u64(self.nonce);
+        encoder.put_u64(self.last_activity);
+        encoder.put_u64(self.stake);
+        encoder.put_u32(self.token_balances.len() as u32);
+        for (token, balance) in &self.token_balances {
+            encoder.put_str(token);
+            encoder.put_u64(*balance);
+        }
+        encoder.put_u32(self.locked.len() as u32);
+        for lock in &self.locked {
+            encoder.put_u64(lock.amount);
+            encoder.put_u64(lock.unlock_height);
+            encoder.put_u64(lock.unlock_time);
+        }
+        match &self.multisig {
+            Some(policy) => {
+                encoder.put_u8(1);
+                policy.encode_fields(&mut encoder);
+            }
+            None => encoder.put_u8(0),
+        }
+        encoder.finish()
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<AccountState, DecodeError> {
+        let mut decoder = Decoder::new(bytes, KIND_ACCOUNT_STATE)?;
+        let mut account = AccountState {
+            balance: decoder.get_u64()?,
+            nonce: decoder.get_u64()?,
+            last_activity: decoder.get_u64()?,
+            stake: decoder.get_u64()?,
+            token_balances: BTreeMap::new(),
+            locked: Vec::new(),
+            multisig: None,
+        };
+        for _ in 0..decoder.get_u32()? {
+            let token = decoder.get_string()?;
+            account.token_balances.insert(token, decoder.get_u64()?);
+        }
+        for _ in 0..decoder.get_u32()? {
+            account.locked.push(LockedFunds {
+                amount: decoder.get_u64()?,
+                unlock_height: decoder.get_u64()?,
+                unlock_time: decoder.get_u64()?,
+            });
+        }
+        if decoder.get_u8()? != 0 {
+            account.multisig = Some(MultisigPolicy::decode_fields(&mut decoder)?);
+        }
+        decoder.finish()?;
+        Ok(account)
+    }
+}
+
+// What applying one block changes, taken before it is applied, so a
+// reorganization can roll the block back instead of replaying the chain
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StateUndo {
+    accounts: Vec<(String, Option<AccountState>)>, // None if the block creates the account
+    nft_owners: Vec<(String, Option<String>)>,
+    spent_action_proofs: Vec<String>, // Spent by the block
+    height: u64,
+    supply: u64,
+    action_epoch: u64,
+    epoch_action_minted: u64,
+}
+
+// The part of an AccountStateIndex not kept per account, which a store
+// writes next to its account column
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StateSummary {
+    pub height: u64,
+    pub spent_action_proofs: BTreeSet<String>,
+    pub supply: u64,
+    pub action_epoch: u64,
+    pub epoch_action_minted: u64,
+}
+
+impl StateSummary {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new(KIND_STATE_SUMMARY);
+        encoder.put_u64(self.height);
+        encoder.put_u32(self.spent_action_proofs.len() as u32);
+        for action_id in &self.spent_action_proofs {
+            encoder.put_str(action_id);
+        }
+        encoder.put_u64(self.supply);
+        encoder.put_u64(self.action_epoch);
+        encoder.put_u64(self.epoch_action_minted);
+        encoder.finish()
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<StateSummary, DecodeError> {
+        let mut decoder = Decoder::new(bytes, KIND_STATE_SUMMARY)?;
+        let height = decoder.get_u64()?;
+        let mut spent_action_proofs = BTreeSet::new();
+        for _ in 0..decoder.get_u32()? {
+            spent_action_proofs.insert(decoder.get_string()?);
+        }
+        let summary = StateSummary {
+            height,
+            spent_action_proofs,
+            supply: decoder.get_u64()?,
+            action_epoch: decoder.get_u64()?,
+            epoch_action_minted: decoder.get_u64()?,
+        };
+        decoder.finish()?;
+        Ok(summary)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccountStateIndex {
+    pub accounts: HashMap<String, AccountState>,
+    pub height: u64, // Number of blocks applied so far
+    #[serde(default)]
+    pub spent_action_proofs: BTreeSet<String>, // Action ids already minted
+    #[serde(default)]
+    nft_owners: HashMap<String, String>, // Token id to owning address
+    #[serde(default)]
+    pub supply: u64, // Coins in existence, held as balances and stakes
+    #[serde(default)]
+    pub action_epoch: u64, // Start height of the epoch epoch_action_minted counts
+    #[serde(default)]
+    pub epoch_action_minted: u64,
+}
+
+impl AccountStateIndex {
+    pub fn new() -> Self {
+        AccountStateIndex {
+            accounts: HashMap::new(),
+            height: 0,
+            spent_action_proofs: BTreeSet::new(),
+            nft_owners: HashMap::new(),
+            supply: 0,
+            action_epoch: 0,
+            epoch_action_minted: 0,
+        }
+    }
+
+    // Reassembles a state a store kept as its accounts plus their summary
+    pub fn from_parts(accounts: Vec<(String, AccountState)>, summary: StateSummary) -> Self {
+        let mut state = AccountStateIndex::new();
+        for (address, account) in accounts {
+            state.insert_account(address, account);
+        }
+        state.height = summary.height;
+        state.spent_action_proofs = summary.spent_action_proofs;
+        state.supply = summary.supply;
+        state.action_epoch = summary.action_epoch;
+        state.epoch_action_minted = summary.epoch_action_minted;
+        state
+    }
+
+    pub fn summary(&self) -> StateSummary {
+        StateSummary {
+            height: self.height,
+            spent_action_proofs: self.spent_action_proofs.clone(),
+            supply: self.supply,
+            action_epoch: self.action_epoch,
+            epoch_action_minted: self.epoch_action_minted,
+        }
+    }
+
+    // Adds a stored account, keeping the NFT ownership index in step
+    pub fn insert_account(&mut self, address: String, account: AccountState) {
+        for key in account.token_balances.keys() {
+            if let Some(token_id) = nft_token_id(key) {
+                self.nft_owners.insert(token_id.to_string(), address.clone());
+            }
+        }
+        self.accounts.insert(address, account);
+    }
+
+    // `epoch_start` is where the action-mining epoch of `block` begins
+    pub fn apply_block(&mut self, block: &Block, epoch_start: u64) {
+        self.enter_epoch(epoch_start);
+        for transaction in &block.transactions {
+            self.apply_transaction(transaction, block);
+        }
+        self.height = block.index + 1;
+    }
+
+    // Taken just before `block` is applied
+    pub fn undo_for(&self, block: &Block) -> StateUndo {
+        let mut undo = StateUndo {
+            height: self.height,
+            supply: self.supply,
+            action_epoch: self.action_epoch,
+            epoch_action_minted: self.epoch_action_minted,
+            ..StateUndo::default()
+        };
+        let mut addresses = HashSet::new();
+        let mut token_ids = HashSet::new();
+
+        for transaction in &block.transactions {
+            for address in std::iter::once(transaction.from_address.as_str()).chain(transaction.recipients()) {
+                if !address.is_empty() && addresses.insert(address) {
+                    undo.accounts.push((address.to_string(), self.accounts.get(address).cloned()));
+                }
+            }
+            if let Some(token_id) = transaction.kind.nft_token_id() {
+                if token_ids.insert(token_id) {
+                    undo.nft_owners.push((token_id.to_string(), self.nft_owners.get(token_id).cloned()));
+                }
+            }
+            if let Some(proof) = transaction.proof_of_action() {
+                let action_id = proof.action_id();
+                if !self.is_action_spent(&action_id) && !undo.spent_action_proofs.contains(&action_id) {
+                    undo.spent_action_proofs.push(action_id);
+                }
+            }
+        }
+
+        undo
+    }
+
+    // Rolls back the last block applied, given the undo taken for it
+    pub fn revert(&mut self, undo: &StateUndo) {
+        for (address, account) in &undo.accounts {
+            match account {
+                Some(account) => self.accounts.insert(address.clone(), account.clone()),
+                None => self.accounts.remove(address),
+            };
+        }
+        for (token_id, owner) in &undo.nft_owners {
+            match owner {
+                Some(owner) => self.nft_owners.insert(token_id.clone(), owner.clone()),
+                None => self.nft_owners.remove(token_id),
+            };
+        }
+        for action_id in &undo.spent_action_proofs {
+            self.spent_action_proofs.remove(action_id);
+        }
+        self.height = undo.height;
+        self.supply = undo.supply;
+        self.action_epoch = undo.action_epoch;
+        self.epoch_action_minted = undo.epoch_action_minted;
+    }
+
+    // Starts counting action mining afresh when a block opens a new epoch
+    pub fn enter_epoch(&mut self, epoch_start: u64) {
+        if self.action_epoch != epoch_start {
+            self.action_epoch = epoch_start;
+            self.epoch_action_minted = 0;
+        }
+    }
+
+    // Action mining so far in the epoch starting at `epoch_start`
+    pub fn action_minted_in(&self, epoch_start: u64) -> u64 {
+        if self.action_epoch == epoch_start {
+            self.epoch_action_minted
+        } else {
+            0
+        }
+    }
+
+    pub fn apply_transaction(&mut self, transaction: &Transaction, block: &Block) {
+        let block_index = block.index;
+        let block_time = block_time(block);
+
+        // The empty address is the system source for rewards and genesis, the
+        // only coins minted; fees leave the sender and return in the reward
+        if transaction.from_address.is_empty() {
+            self.supply = self.supply.saturating_add(transaction.amount);
+            if matches!(transaction.kind, TransactionKind::ActionReward { .. }) {
+                self.epoch_action_minted = self.epoch_action_minted.saturating_add(transaction.amount);
+            }
+        } else {
+            self.supply = self.supply.saturating_sub(transaction.fee);
+            let sender = self.accounts.entry(transaction.from_address.clone()).or_default();
+            sender.locked.retain(|lock| !lock.is_mature(block_index, block_time));
+            sender.balance = sender.balance.saturating_sub(transaction.total_debit().unwrap_or(u64::MAX));
+            sender.nonce += 1;
+            sender.last_activity = block_index;
+
+            match &transaction.kind {
+                TransactionKind::Stake => sender.stake = sender.stake.saturating_add(transaction.amount),
+                TransactionKind::Unstake => {
+                    sender.stake = sender.stake.saturating_sub(transaction.amount);
+                    sender.balance = sender.balance.saturating_add(transaction.amount);
+                }
+                TransactionKind::NftTransfer { token_id } => {
+                    sender.token_balances.remove(&nft_key(token_id));
+                }
+                _ => {}
+            }
+        }
+
+        // Staking only moves coins within the sender's own account
+        if matches!(transaction.kind, TransactionKind::Stake | TransactionKind::Unstake) {
+            return;
+        }
+
+        if let TransactionKind::BatchTransfer { outputs } = &transaction.kind {
+            for output in outputs {
+                let recipient = self.accounts.entry(output.to_address.clone()).or_default();
+                recipient.locked.retain(|lock| !lock.is_mature(block_index, block_time));
+                recipient.balance = recipient.balance.saturating_add(output.amount);
+                recipient.last_activity = block_index;
+            }
+            return;
+        }
+
+        let recipient = self.accounts.entry(transaction.to_address.clone()).or_default();
+        recipient.locked.retain(|lock| !lock.is_mature(block_index, block_time));
+        if transaction.kind.credits_recipient() {
+            recipient.balance = recipient.balance.saturating_add(transaction.amount);
+            if transaction.is_locked() && transaction.amount > 0 {
+                recipient.locked.push(LockedFunds {
+                    amount: transaction.amount,
+                    unlock_height: transaction.unlock_height,
+                    unlock_time: transaction.unlock_time,
+                });
+            }
+        }
+        match &transaction.kind {
+            TransactionKind::GenesisStake => recipient.stake = recipient.stake.saturating_add(transaction.amount),
+            TransactionKind::NftMint { token_id, .. } | TransactionKind::NftTransfer { token_id } => {
+                recipient.token_balances.insert(nft_key(token_id), 1);
+                self.nft_owners.insert(token_id.clone(), transaction.to_address.clone());
+            }
+            TransactionKind::RegisterMultisig(policy) => recipient.multisig = Some(policy.clone()),
+            _ => {}
+        }
+        recipient.last_activity = block_index;
+
+        if let Some(proof) = transaction.proof_of_action() {
+            self.spent_action_proofs.insert(proof.action_id());
+        }
+    }
+
+    pub fn get(&self, address: &str) -> Option<&AccountState> {
+        self.accounts.get(address)
+    }
+
+    pub fn balance_of(&self, address: &str) -> u64 {
+        self.accounts.get(address).map(|account| account.balance).unwrap_or(0)
+    }
+
+    pub fn spendable_of(&self, address: &str, height: u64, time: u64) -> u64 {
+        self.accounts.get(address).map(|account| account.spendable(height, time)).unwrap_or(0)
+    }
+
+    pub fn multisig_of(&self, address: &str) -> Option<&MultisigPolicy> {
+        self.accounts.get(address).and_then(|account| account.multisig.as_ref())
+    }
+
+    pub fn is_action_spent(&self, action_id: &str) -> bool {
+        self.spent_action_proofs.contains(action_id)
+    }
+
+    pub fn stake_of(&self, address: &str) -> u64 {
+        self.accounts.get(address).map(|account| account.stake).unwrap_or(0)
+    }
+
+    pub fn nft_owner(&self, token_id: &str) -> Option<&str> {
+        self.nft_owners.get(token_id).map(String::as_str)
+    }
+
+    pub fn nonce_of(&self, address: &str) -> u64 {
+        self.accounts.get(address).map(|account| account.nonce).unwrap_or(0)
+    }
+}
+
+// A block's timestamp as the Unix milliseconds time locks compare against.
+// Header checks keep it past the median of recent blocks and within
+// MAX_FUTURE_DRIFT_MS of the validating clock, so a miner can't date a block
+// ahead to release a lock early.
+pub fn block_time(block: &Block) -> u64 {
+    block.timestamp_millis().unwrap_or(0).max(0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::{BitnunBlockchain, KeyPair, TransferOutput, ValidationRule};
+    use crate::blockchain::test_support::{dev_keypair, signed};
+    use crate::error::{BitnunError, ChainError};
+    use crate::runtime;
+
+    #[test]
+    fn account_index_matches_a_full_rescan() {
+        let dev = dev_keypair();
+        let holder = KeyPair::generate();
+        let mut chain = BitnunBlockchain::from_preset("dev").unwrap();
+
+        let mut funding = Transaction::new(&dev.address(), &holder.address(), 1_000);
+        funding.fee = 7;
+        chain.add_transaction(&signed(funding, &dev)).unwrap();
+        chain.mine_pending_transactions("btnminer");
+
+        let mut stake = Transaction::new_stake(&dev.address(), &dev.address(), 500);
+        stake.set_nonce(1);
+        chain.add_transaction(&signed(stake, &dev)).unwrap();
+        chain.add_transaction(&signed(Transaction::new(&holder.address(), "btnpayee", 300), &holder)).unwrap();
+        let outputs = vec![
+            TransferOutput { to_address: "btnpayee".to_string(), amount: 40 },
+            TransferOutput { to_address: dev.address(), amount: 60 },
+        ];
+        let mut batch = Transaction::new_batch(&holder.address(), outputs);
+        batch.set_nonce(1);
+        chain.add_transaction(&signed(batch, &holder)).unwrap();
+        chain.mine_pending_transactions("btnother");
+        chain.mine_pending_transactions("btnminer");
+        assert!(chain.verify_state_consistency());
+
+        let tip = chain.blocks.len() as u64 - 1;
+        for (address, account) in &chain.state.accounts {
+            assert_eq!(account.balance, chain.scan_balance(address), "balance of {}", address);
+            let sent = chain.blocks
+                .iter()
+                .flat_map(|block| &block.transactions)
+                .filter(|tx| &tx.from_address == address)
+                .count() as u64;
+            assert_eq!(account.nonce, sent, "nonce of {}", address);
+        }
+        assert_eq!(chain.state.get(&holder.address()).unwrap().last_activity, tip - 1);
+        assert_eq!(chain.state.get("btnpayee").unwrap().last_activity, tip - 1);
+        assert_eq!(chain.state.get("btnminer").unwrap().last_activity, tip);
+
+        // A drifted index is caught and rebuilt from the blocks
+        chain.state.accounts.get_mut("btnpayee").unwrap().balance += 1;
+        assert!(!chain.verify_state_consistency());
+        chain.rebuild_state();
+        assert!(chain.verify_state_consistency());
+        assert_eq!(chain.get_balance("btnpayee"), 340);
+    }
+
+    #[test]
+    fn future_dated_block_cannot_release_a_time_lock() {
+        let dev = dev_keypair();
+        let holder = KeyPair::generate();
+        let mut chain = BitnunBlockchain::from_preset("dev").unwrap();
+        let unlock_time = runtime::now_millis() as u64 + 60 * 60 * 1000;
+
+        let mut locked = Transaction::new(&dev.address(), &holder.address(), 500);
+        locked.set_lock(0, unlock_time);
+        let locked = signed(locked, &dev);
+        chain.add_transaction(&locked).unwrap();
+        chain.mine_pending_transactions("btnminer");
+        assert_eq!(chain.get_spendable_balance(&holder.address()), 0);
+
+        let spend = signed(Transaction::new(&holder.address(), "btnpayee", 400), &holder);
+        let subsidy = chain.block_subsidy(chain.blocks.len() as u64, chain.state.circulating_supply());
+        let reward = Transaction::new_mining_reward("btnminer", subsidy);
+
+        let mut block = Block::new(chain.blocks.len() as u64, chain.get_latest_block().hash.clone(), vec![spend, reward]);
+        block.set_timestamp_millis(unlock_time as i64);
+        let mut state = chain.state.clone();
+        state.apply_block(&block, chain.emission.epoch_start(block.index));
+        block.state_root = state.state_root();
+        block.mine_block(chain.mining_bits);
+
+        match chain.accept_block(block) {
+            Err(BitnunError::Chain(ChainError::InvalidBlock(violation))) => assert_eq!(violation.rule, ValidationRule::Timestamp),
+            other => panic!("expected a timestamp violation, got {:?}", other.map(|_| ())),
+        }
+        assert_eq!(chain.get_balance("btnpayee"), 0);
+    }
+
+    #[test]
+    fn nft_ownership_is_indexed_and_rebuilt_from_accounts() {
+        let dev = dev_keypair();
+        let mut chain = BitnunBlockchain::from_preset("dev").unwrap();
+
+        let mint = signed(Transaction::new_nft_mint(&dev.address(), &dev.address(), "art-1", "ipfs://art-1"), &dev);
+        chain.add_transaction(&mint).unwrap();
+        chain.mine_pending_transactions("btnminer");
+        assert_eq!(chain.state.nft_owner("art-1"), Some(dev.address().as_str()));
+
+        let mut transfer = Transaction::new_nft_transfer(&dev.address(), "btncollector", "art-1");
+        transfer.set_nonce(1);
+        let transfer = signed(transfer, &dev);
+        chain.add_transaction(&transfer).unwrap();
+        chain.mine_pending_transactions("btnminer");
+        assert_eq!(chain.state.nft_owner("art-1"), Some("btncollector"));
+        assert_eq!(chain.state.nft_owner("art-2"), None);
+
+        let mut rebuilt = AccountStateIndex::new();
+        for (address, account) in chain.state.accounts.clone() {
+            rebuilt.insert_account(address, account);
+        }
+        rebuilt.height = chain.state.height;
+        rebuilt.spent_action_proofs = chain.state.spent_action_proofs.clone();
+        rebuilt.supply = chain.state.supply;
+        assert_eq!(rebuilt, chain.state);
+    }
+
+    #[test]
+    fn batch_debits_once_and_credits_every_output() {
+        let dev = dev_keypair();
+        let mut chain = BitnunBlockchain::from_preset("dev").unwrap();
+        let before = chain.get_balance(&dev.address());
+
+        let outputs = vec![
+            TransferOutput { to_address: "btnone".to_string(), amount: 30 },
+            TransferOutput { to_address: "btntwo".to_string(), amount: 20 },
+            TransferOutput { to_address: "btnone".to_string(), amount: 5 },
+        ];
+        let batch = signed(Transaction::new_batch(&dev.address(), outputs), &dev);
+        chain.add_transaction(&batch).unwrap();
+        chain.mine_pending_transactions("btnminer");
+
+        assert_eq!(chain.get_balance(&dev.address()), before - 55);
+        assert_eq!(chain.get_balance("btnone"), 35);
+        assert_eq!(chain.get_balance("btntwo"), 20);
+        assert_eq!(chain.state.nonce_of(&dev.address()), 1);
+    }
+}
+
@@ -0,0 +1,248 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use crate::blockchain::{AccountState, AccountStateIndex, BitnunBlockchain};
+use crate::blockchain::encoding::sha256_digest;
+use crate::blockchain::merkle::{leaf_hash, node_hash};
+
+const KEY_BITS: usize = 256;
+
+// Digest of any subtree holding no accounts, at every depth. Two empty
+// children hash to it as well, so the 2^256 empty leaves cost nothing.
+pub const EMPTY_SUBTREE: [u8; 32] = [0u8; 32];
+
+// Sparse merkle tree over accounts, keyed by the SHA-256 of the address. A
+// leaf commits to the key and the account's canonical encoding. Spent action
+// proofs are leaves too, under keys no address can hash to.
+#[derive(Clone, Debug, Default)]
+pub struct SparseMerkleTree {
+    leaves: BTreeMap<[u8; 32], [u8; 32]>, // Key to leaf hash, in key order
+}
+
+// Non-empty siblings on the path from the root down to the leaf; absent
+// depths are EMPTY_SUBTREE. Also proves absence when the account is None.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StateProof {
+    pub siblings: Vec<StateProofSibling>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StateProofSibling {
+    pub depth: u16, // 0 is the root's children
+    pub hash: String, // Hex-encoded
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BalanceProof {
+    pub address: String,
+    pub account: Option<AccountState>, // None proves the address has no state
+    pub block_height: u64,
+    pub state_root: String, // The state_root of the block at block_height
+    pub proof: StateProof,
+}
+
+impl SparseMerkleTree {
+    pub fn from_state(state: &AccountStateIndex) -> Self {
+        let accounts = state.accounts.iter().map(|(address, account)| {
+            let key = account_key(address);
+            (key, account_leaf(&key, account))
+        });
+        let spent_action_proofs = state.spent_action_proofs.iter().map(|action_id| {
+            let key = spent_action_key(action_id);
+            (key, spent_action_leaf(&key))
+        });
+        SparseMerkleTree { leaves: accounts.chain(spent_action_proofs).collect() }
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        let leaves: Vec<(&[u8; 32], &[u8; 32])> = self.leaves.iter().collect();
+        subtree_hash(&leaves, 0)
+    }
+
+    pub fn root_hex(&self) -> String {
+        hex::encode(self.root())
+    }
+
+    pub fn prove(&self, address: &str) -> StateProof {
+        let key = account_key(address);
+        let mut leaves: Vec<(&[u8; 32], &[u8; 32])> = self.leaves.iter().collect();
+        let mut siblings = Vec::new();
+
+        for depth in 0..KEY_BITS {
+            let split = leaves.partition_point(|(leaf_key, _)| !bit(leaf_key, depth));
+            let (left, right) = leaves.split_at(split);
+            let (path, other) = if bit(&key, depth) { (right, left) } else { (left, right) };
+
+            let sibling = subtree_hash(other, depth + 1);
+            if sibling != EMPTY_SUBTREE {
+                siblings.push(StateProofSibling {
+                    depth: depth as u16,
+                    hash: hex::encode(sibling),
+                });
+            }
+            if path.is_empty() {
+                break; // Everything further down the path is empty
+            }
+            leaves = path.to_vec();
+        }
+
+        StateProof { siblings }
+    }
+}
+
+impl StateProof {
+    pub fn verify(&self, address: &str, account: Option<&AccountState>, root: &str) -> bool {
+        let key = account_key(address);
+        let mut siblings: BTreeMap<usize, [u8; 32]> = BTreeMap::new();
+        for sibling in &self.siblings {
+            let hash = match hex::decode(&sibling.hash).ok().and_then(|bytes| <[u8; 32]>::try_from(bytes).ok()) {
+                Some(hash) => hash,
+                None => return false,
+            };
+            if sibling.depth as usize >= KEY_BITS || siblings.insert(sibling.depth as usize, hash).is_some() {
+                return false;
+            }
+        }
+
+        let mut hash = match account {
+            Some(account) => account_leaf(&key, account),
+            None => EMPTY_SUBTREE,
+        };
+        for depth in (0..KEY_BITS).rev() {
+            let sibling = siblings.get(&depth).copied().unwrap_or(EMPTY_SUBTREE);
+            hash = if bit(&key, depth) {
+                combine(&sibling, &hash)
+            } else {
+                combine(&hash, &sibling)
+            };
+        }
+
+        hex::encode(hash) == root
+    }
+}
+
+impl BalanceProof {
+    pub fn verify(&self) -> bool {
+        self.proof.verify(&self.address, self.account.as_ref(), &self.state_root)
+    }
+}
+
+impl AccountStateIndex {
+    pub fn state_root(&self) -> String {
+        SparseMerkleTree::from_state(self).root_hex()
+    }
+}
+
+impl BitnunBlockchain {
+    // Account state at the tip, proven against the tip block's state_root
+    pub fn balance_with_proof(&self, address: &str) -> BalanceProof {
+        let tip = self.get_latest_block();
+        BalanceProof {
+            address: address.to_string(),
+            account: self.state.get(address).cloned(),
+            block_height: tip.index,
+            state_root: tip.state_root.clone(),
+            proof: SparseMerkleTree::from_state(&self.state).prove(address),
+        }
+    }
+}
+
+fn account_key(address: &str) -> [u8; 32] {
+    sha256_digest(address.as_bytes())
+}
+
+fn account_leaf(key: &[u8; 32], account: &AccountState) -> [u8; 32] {
+    let mut bytes = key.to_vec();
+    bytes.extend_from_slice(&account.to_bytes());
+    leaf_hash(&bytes)
+}
+
+// 0xff never occurs in UTF-8, so no address hashes from the same preimage
+fn spent_action_key(action_id: &str) -> [u8; 32] {
+    let mut preimage = b"\xffspent_action".to_vec();
+    preimage.extend_from_slice(action_id.as_bytes());
+    sha256_digest(&preimage)
+}
+
+fn spent_action_leaf(key: &[u8; 32]) -> [u8; 32] {
+    let mut bytes = key.to_vec();
+    bytes.extend_from_slice(b"spent");
+    leaf_hash(&bytes)
+}
+
+// Bit `depth` of the key, most significant first; set means the right branch
+fn bit(key: &[u8; 32], depth: usize) -> bool {
+    key[depth / 8] & (0x80 >> (depth % 8)) != 0
+}
+
+fn combine(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    if *left == EMPTY_SUBTREE && *right == EMPTY_SUBTREE {
+        EMPTY_SUBTREE
+    } else {
+        node_hash(left, right)
+    }
+}
+
+// `leaves` are sorted by key and all share the path above `depth`
+fn subtree_hash(leaves: &[(&[u8; 32], &[u8; 32])], depth: usize) -> [u8; 32] {
+    match leaves {
+        [] => EMPTY_SUBTREE,
+        [(_, leaf)] if depth == KEY_BITS => **leaf,
+        _ => {
+            let split = leaves.partition_point(|(key, _)| !bit(key, depth));
+            let (left, right) = leaves.split_at(split);
+            combine(&subtree_hash(left, depth + 1), &subtree_hash(right, depth + 1))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_root_commits_to_spent_action_proofs() {
+        let mut state = AccountStateIndex::new();
+        state.insert_account("btnholder".to_string(), AccountState { balance: 5, ..AccountState::default() });
+        let accounts_only = state.state_root();
+
+        state.spent_action_proofs.insert("action-1".to_string());
+        let spent_one = state.state_root();
+        assert_ne!(spent_one, accounts_only);
+
+        state.spent_action_proofs.insert("action-2".to_string());
+        assert_ne!(state.state_root(), spent_one);
+
+        // Account proofs still verify against the combined root
+        let proof = SparseMerkleTree::from_state(&state).prove("btnholder");
+        assert!(proof.verify("btnholder", state.get("btnholder"), &state.state_root()));
+    }
+
+    #[test]
+    fn balance_proofs_verify_against_the_tip_header() {
+        let mut chain = BitnunBlockchain::from_preset("dev").unwrap();
+        chain.mine_pending_transactions("btnminer");
+        let header = chain.get_latest_block().header();
+
+        let present = chain.balance_with_proof("btnminer");
+        assert_eq!(present.state_root, header.state_root);
+        assert_eq!(present.block_height, header.index);
+        assert_eq!(present.account.as_ref().map(|account| account.balance), Some(chain.get_balance("btnminer")));
+        assert!(present.verify());
+
+        let mut inflated = present.clone();
+        inflated.account.as_mut().unwrap().balance += 1;
+        assert!(!inflated.verify());
+        let mut hidden = present.clone();
+        hidden.account = None;
+        assert!(!hidden.verify());
+
+        let absent = chain.balance_with_proof("btnnobody");
+        assert_eq!(absent.state_root, header.state_root);
+        assert!(absent.account.is_none());
+        assert!(absent.verify());
+
+        let mut invented = absent.clone();
+        invented.account = Some(AccountState { balance: 1, ..AccountState::default() });
+        assert!(!invented.verify());
+    }
+}
@@ -0,0 +1,135 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use crate::blockchain::{AccountState, Block, StateSummary};
+use crate::blockchain::store::BlockStore;
+use crate::error::{BitnunResult, StoreError};
+
+// One file per entry under a directory per column:
+//   blocks/<hash>, heights/<height>, accounts/<hex address>, summary, tip
+// Files are written to a temporary name and renamed into place.
+#[derive(Clone, Debug)]
+pub struct FileStore {
+    root: PathBuf,
+}
+
+impl FileStore {
+    pub fn open(root: impl AsRef<Path>) -> BitnunResult<FileStore> {
+        let root = root.as_ref().to_path_buf();
+        for column in ["blocks", "heights", "accounts"] {
+            fs::create_dir_all(root.join(column)).map_err(io_error)?;
+        }
+        Ok(FileStore { root })
+    }
+
+    fn write(&self, path: PathBuf, bytes: &[u8]) -> BitnunResult<()> {
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, bytes).map_err(io_error)?;
+        fs::rename(&temporary, &path).map_err(io_error)?;
+        Ok(())
+    }
+
+    fn read(&self, path: PathBuf) -> BitnunResult<Option<Vec<u8>>> {
+        match fs::read(path) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(io_error(error).into()),
+        }
+    }
+
+    fn read_string(&self, path: PathBuf) -> BitnunResult<Option<String>> {
+        match self.read(path)? {
+            Some(bytes) => String::from_utf8(bytes)
+                .map(Some)
+                .map_err(|_| StoreError::Corrupt("non UTF-8 entry".to_string()).into()),
+            None => Ok(None),
+        }
+    }
+
+    fn block_path(&self, hash: &str) -> PathBuf {
+        self.root.join("blocks").join(hex::encode(hash)) // Never trust a hash as a file name
+    }
+
+    fn account_path(&self, address: &str) -> PathBuf {
+        self.root.join("accounts").join(hex::encode(address))
+    }
+}
+
+impl BlockStore for FileStore {
+    fn put_block(&mut self, block: &Block) -> BitnunResult<()> {
+        self.write(self.block_path(&block.hash), &block.to_bytes())
+    }
+
+    fn get_block(&self, hash: &str) -> BitnunResult<Option<Block>> {
+        match self.read(self.block_path(hash))? {
+            Some(bytes) => Ok(Some(Block::decode(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn put_height(&mut self, height: u64, hash: &str) -> BitnunResult<()> {
+        self.write(self.root.join("heights").join(height.to_string()), hash.as_bytes())
+    }
+
+    fn get_hash_at(&self, height: u64) -> BitnunResult<Option<String>> {
+        self.read_string(self.root.join("heights").join(height.to_string()))
+    }
+
+    fn put_tip(&mut self, height: u64) -> BitnunResult<()> {
+        self.write(self.root.join("tip"), height.to_string().as_bytes())
+    }
+
+    fn tip(&self) -> BitnunResult<Option<u64>> {
+        match self.read_string(self.root.join("tip"))? {
+            Some(tip) => tip.trim().parse()
+                .map(Some)
+                .map_err(|_| StoreError::Corrupt(format!("invalid tip '{}'", tip)).into()),
+            None => Ok(None),
+        }
+    }
+
+    fn put_account(&mut self, address: &str, account: &AccountState) -> BitnunResult<()> {
+        self.write(self.account_path(address), &account.to_bytes())
+    }
+
+    fn delete_account(&mut self, address: &str) -> BitnunResult<()> {
+        match fs::remove_file(self.account_path(address)) {
+            Err(error) if error.kind() != ErrorKind::NotFound => Err(io_error(error).into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn accounts(&self) -> BitnunResult<Vec<(String, AccountState)>> {
+        let mut accounts = Vec::new();
+        for entry in fs::read_dir(self.root.join("accounts")).map_err(io_error)? {
+            let entry = entry.map_err(io_error)?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.ends_with(".tmp") {
+                continue; // Left over from an interrupted write
+            }
+
+            let address = hex::decode(&name)
+                .ok()
+                .and_then(|bytes| String::from_utf8(bytes).ok())
+                .ok_or_else(|| StoreError::Corrupt(format!("invalid account file '{}'", name)))?;
+            let bytes = fs::read(entry.path()).map_err(io_error)?;
+            accounts.push((address, AccountState::decode(&bytes)?));
+        }
+        Ok(accounts)
+    }
+
+    fn put_summary(&mut self, summary: &StateSummary) -> BitnunResult<()> {
+        self.write(self.root.join("summary"), &summary.to_bytes())
+    }
+
+    fn summary(&self) -> BitnunResult<Option<StateSummary>> {
+        match self.read(self.root.join("summary"))? {
+            Some(bytes) => Ok(Some(StateSummary::decode(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+fn io_error(error: std::io::Error) -> StoreError {
+    StoreError::Io(error.to_string())
+}
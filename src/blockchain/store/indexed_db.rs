@@ -0,0 +1,238 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use js_sys::{Array, Promise, Uint8Array};
+use web_sys::{IdbDatabase, IdbOpenDbRequest, IdbRequest, IdbTransaction, IdbTransactionMode};
+use crate::blockchain::{AccountState, BitnunBlockchain, Block, GenesisConfig, StateSummary};
+use crate::blockchain::store::{BlockStore, MemoryStore};
+use crate::error::{BitnunResult, StoreError};
+
+const DB_VERSION: u32 = 1;
+const COLUMNS: [&str; 4] = ["blocks", "heights", "accounts", "meta"];
+
+// IndexedDB only has asynchronous reads, so every column is loaded into a
+// MemoryStore when the database opens. Writes update that cache at once and
+// are batched until commit, which writes the batch in one transaction;
+// `committed` settles when the last one completes.
+pub struct IndexedDbStore {
+    db: IdbDatabase,
+    cache: MemoryStore,
+    batch: Vec<(&'static str, String, Option<Vec<u8>>)>, // Column, key, and the value or None to delete
+    last_commit: Option<Promise>,
+}
+
+impl IndexedDbStore {
+    pub async fn open(name: &str) -> Result<IndexedDbStore, JsValue> {
+        let factory = web_sys::window()
+            .ok_or_else(|| JsValue::from_str("No window"))?
+            .indexed_db()?
+            .ok_or_else(|| JsValue::from_str("IndexedDB is unavailable"))?;
+
+        let request = factory.open_with_u32(name, DB_VERSION)?;
+        let on_upgrade = Closure::once(move |event: web_sys::Event| {
+            let request: IdbOpenDbRequest = event.target().unwrap().unchecked_into();
+            let db: IdbDatabase = request.result().unwrap().unchecked_into();
+            for column in COLUMNS {
+                if !db.object_store_names().contains(column) {
+                    let _ = db.create_object_store(column);
+                }
+            }
+        });
+        request.set_onupgradeneeded(Some(on_upgrade.as_ref().unchecked_ref()));
+        let db: IdbDatabase = request_result(&request).await?.unchecked_into();
+
+        let mut cache = MemoryStore::new();
+        for column in COLUMNS {
+            // One transaction per request; a transaction closes once its callbacks return
+            let keys = db.transaction_with_str(column)?.object_store(column)?.get_all_keys()?;
+            let keys: Array = request_result(&keys).await?.unchecked_into();
+            let values = db.transaction_with_str(column)?.object_store(column)?.get_all()?;
+            let values: Array = request_result(&values).await?.unchecked_into();
+
+            for (key, value) in keys.iter().zip(values.iter()) {
+                let key = key.as_string().unwrap_or_default();
+                load_entry(&mut cache, column, key, Uint8Array::new(&value).to_vec())?;
+            }
+        }
+
+        Ok(IndexedDbStore { db, cache, batch: Vec::new(), last_commit: None })
+    }
+
+    fn write(&mut self, column: &'static str, key: &str, value: Option<&[u8]>) -> BitnunResult<()> {
+        self.batch.push((column, key.to_string(), value.map(<[u8]>::to_vec)));
+        Ok(())
+    }
+}
+
+impl BlockStore for IndexedDbStore {
+    fn put_block(&mut self, block: &Block) -> BitnunResult<()> {
+        self.cache.put_block(block)?;
+        self.write("blocks", &block.hash, Some(&block.to_bytes()))
+    }
+
+    fn get_block(&self, hash: &str) -> BitnunResult<Option<Block>> {
+        self.cache.get_block(hash)
+    }
+
+    fn put_height(&mut self, height: u64, hash: &str) -> BitnunResult<()> {
+        self.cache.put_height(height, hash)?;
+        self.write("heights", &height.to_string(), Some(hash.as_bytes()))
+    }
+
+    fn get_hash_at(&self, height: u64) -> BitnunResult<Option<String>> {
+        self.cache.get_hash_at(height)
+    }
+
+    fn put_tip(&mut self, height: u64) -> BitnunResult<()> {
+        self.cache.put_tip(height)?;
+        self.write("meta", "tip", Some(height.to_string().as_bytes()))
+    }
+
+    fn tip(&self) -> BitnunResult<Option<u64>> {
+        self.cache.tip()
+    }
+
+    fn put_account(&mut self, address: &str, account: &AccountState) -> BitnunResult<()> {
+        self.cache.put_account(address, account)?;
+        self.write("accounts", address, Some(&account.to_bytes()))
+    }
+
+    fn delete_account(&mut self, address: &str) -> BitnunResult<()> {
+        self.cache.delete_account(address)?;
+        self.write("accounts", address, None)
+    }
+
+    fn accounts(&self) -> BitnunResult<Vec<(String, AccountState)>> {
+        self.cache.accounts()
+    }
+
+    fn put_summary(&mut self, summary: &StateSummary) -> BitnunResult<()> {
+        self.cache.put_summary(summary)?;
+        self.write("meta", "summary", Some(&summary.to_bytes()))
+    }
+
+    fn summary(&self) -> BitnunResult<Option<StateSummary>> {
+        self.cache.summary()
+    }
+
+    // Transactions over the same stores run in the order they are created,
+    // so batches land in order without waiting on each other
+    fn commit(&mut self) -> BitnunResult<()> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+
+        let batch = std::mem::take(&mut self.batch);
+        let write = || -> Result<Promise, JsValue> {
+            let columns: Array = COLUMNS.iter().map(|column| JsValue::from_str(column)).collect();
+            let transaction = self.db.transaction_with_str_sequence_and_mode(&columns, IdbTransactionMode::Readwrite)?;
+            for (column, key, value) in &batch {
+                let store = transaction.object_store(column)?;
+                match value {
+                    Some(bytes) => store.put_with_key(&Uint8Array::from(bytes.as_slice()), &JsValue::from_str(key))?,
+                    None => store.delete(&JsValue::from_str(key))?,
+                };
+            }
+            Ok(transaction_done(&transaction))
+        };
+        let done = write().map_err(|error| StoreError::Io(format!("{:?}", error)))?;
+        self.last_commit = Some(match self.last_commit.take() {
+            Some(previous) => Promise::all(&Array::of2(&previous, &done)),
+            None => done,
+        });
+        Ok(())
+    }
+
+    // Rejects with the error that aborted a batch, if one did
+    fn committed(&self) -> Promise {
+        self.last_commit.clone().unwrap_or_else(|| Promise::resolve(&JsValue::UNDEFINED))
+    }
+}
+
+// Opens the chain persisted in the named IndexedDB database, creating it from
+// the genesis preset on first use
+#[wasm_bindgen]
+pub async fn open_indexed_db_chain(db_name: String, preset: String) -> Result<BitnunBlockchain, JsValue> {
+    let config = GenesisConfig::preset(&preset)?;
+    let store = IndexedDbStore::open(&db_name).await?;
+    Ok(BitnunBlockchain::open(Box::new(store), &config)?)
+}
+
+#[wasm_bindgen]
+impl BitnunBlockchain {
+    // Settles once the chain's writes so far are durable in its store
+    pub fn store_committed(&self) -> Promise {
+        match &self.store {
+            Some(store) => store.0.borrow().committed(),
+            None => Promise::resolve(&JsValue::UNDEFINED),
+        }
+    }
+}
+
+fn load_entry(cache: &mut MemoryStore, column: &str, key: String, bytes: Vec<u8>) -> BitnunResult<()> {
+    let corrupt = || StoreError::Corrupt(format!("invalid {} entry '{}'", column, key));
+    match column {
+        "blocks" => {
+            cache.blocks.insert(key, bytes);
+        }
+        "heights" => {
+            let height = key.parse().map_err(|_| corrupt())?;
+            cache.heights.insert(height, String::from_utf8(bytes).map_err(|_| corrupt())?);
+        }
+        "accounts" => {
+            cache.accounts.insert(key, AccountState::decode(&bytes)?);
+        }
+        _ if key == "summary" => {
+            cache.summary = Some(StateSummary::decode(&bytes)?);
+        }
+        _ if key == "tip" => {
+            let tip = String::from_utf8(bytes).ok().and_then(|tip| tip.parse().ok()).ok_or_else(corrupt)?;
+            cache.tip = Some(tip);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// Resolves with the request's result once IndexedDB fires success or error
+async fn request_result(request: &IdbRequest) -> Result<JsValue, JsValue> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        let success_request = request.clone();
+        let on_success = Closure::once_into_js(move |_event: web_sys::Event| {
+            let result = success_request.result().unwrap_or(JsValue::UNDEFINED);
+            let _ = resolve.call1(&JsValue::NULL, &result);
+        });
+
+        let error_request = request.clone();
+        let on_error = Closure::once_into_js(move |_event: web_sys::Event| {
+            let error = error_request.error().ok().flatten()
+                .map(JsValue::from)
+                .unwrap_or_else(|| JsValue::from_str("IndexedDB request failed"));
+            let _ = reject.call1(&JsValue::NULL, &error);
+        });
+
+        request.set_onsuccess(Some(on_success.unchecked_ref()));
+        request.set_onerror(Some(on_error.unchecked_ref()));
+    });
+    JsFuture::from(promise).await
+}
+
+// Resolves once the transaction completes, or rejects if it errors or aborts
+fn transaction_done(transaction: &IdbTransaction) -> Promise {
+    Promise::new(&mut |resolve, reject| {
+        let on_complete = Closure::once_into_js(move |_event: web_sys::Event| {
+            let _ = resolve.call0(&JsValue::NULL);
+        });
+
+        let error_transaction = transaction.clone();
+        let on_error = Closure::once_into_js(move |_event: web_sys::Event| {
+            let error = error_transaction.error()
+                .map(JsValue::from)
+                .unwrap_or_else(|| JsValue::from_str("IndexedDB transaction aborted"));
+            let _ = reject.call1(&JsValue::NULL, &error);
+        });
+
+        transaction.set_oncomplete(Some(on_complete.unchecked_ref()));
+        transaction.set_onabort(Some(on_error.unchecked_ref()));
+    })
+}
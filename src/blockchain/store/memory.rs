@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use crate::blockchain::{AccountState, Block, StateSummary};
+use crate::blockchain::store::BlockStore;
+use crate::error::BitnunResult;
+
+// Keeps every column in memory; also the read cache of the IndexedDB store
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStore {
+    pub(crate) blocks: HashMap<String, Vec<u8>>,
+    pub(crate) heights: HashMap<u64, String>,
+    pub(crate) tip: Option<u64>,
+    pub(crate) accounts: HashMap<String, AccountState>,
+    pub(crate) summary: Option<StateSummary>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+}
+
+impl BlockStore for MemoryStore {
+    fn put_block(&mut self, block: &Block) -> BitnunResult<()> {
+        self.blocks.insert(block.hash.clone(), block.to_bytes());
+        Ok(())
+    }
+
+    fn get_block(&self, hash: &str) -> BitnunResult<Option<Block>> {
+        match self.blocks.get(hash) {
+            Some(bytes) => Ok(Some(Block::decode(bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn put_height(&mut self, height: u64, hash: &str) -> BitnunResult<()> {
+        self.heights.insert(height, hash.to_string());
+        Ok(())
+    }
+
+    fn get_hash_at(&self, height: u64) -> BitnunResult<Option<String>> {
+        Ok(self.heights.get(&height).cloned())
+    }
+
+    fn put_tip(&mut self, height: u64) -> BitnunResult<()> {
+        self.tip = Some(height);
+        Ok(())
+    }
+
+    fn tip(&self) -> BitnunResult<Option<u64>> {
+        Ok(self.tip)
+    }
+
+    fn put_account(&mut self, address: &str, account: &AccountState) -> BitnunResult<()> {
+        self.accounts.insert(address.to_string(), account.clone());
+        Ok(())
+    }
+
+    fn delete_account(&mut self, address: &str) -> BitnunResult<()> {
+        self.accounts.remove(address);
+        Ok(())
+    }
+
+    fn accounts(&self) -> BitnunResult<Vec<(String, AccountState)>> {
+        Ok(self.accounts.iter().map(|(address, account)| (address.clone(), account.clone())).collect())
+    }
+
+    fn put_summary(&mut self, summary: &StateSummary) -> BitnunResult<()> {
+        self.summary = Some(summary.clone());
+        Ok(())
+    }
+
+    fn summary(&self) -> BitnunResult<Option<StateSummary>> {
+        Ok(self.summary.clone())
+    }
+}
@@ -0,0 +1,365 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::rc::Rc;
+use crate::blockchain::{AccountState, AccountStateIndex, BitnunBlockchain, Block, ChainIndex, GenesisConfig, StateSummary};
+use crate::console_log;
+use crate::error::{BitnunResult, StoreError};
+
+pub mod memory;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod file;
+#[cfg(feature = "wasm")]
+pub mod indexed_db;
+
+pub use memory::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use file::*;
+#[cfg(feature = "wasm")]
+pub use indexed_db::*;
+
+// Persistent columns behind a chain: blocks by hash, the active chain's hash
+// at each height, the tip height, and the account state at the base of the
+// reorg window, by address plus a summary of the rest. Blocks are kept in
+// their canonical encoding.
+pub trait BlockStore {
+    fn put_block(&mut self, block: &Block) -> BitnunResult<()>;
+    fn get_block(&self, hash: &str) -> BitnunResult<Option<Block>>;
+    fn put_height(&mut self, height: u64, hash: &str) -> BitnunResult<()>;
+    fn get_hash_at(&self, height: u64) -> BitnunResult<Option<String>>;
+    fn put_tip(&mut self, height: u64) -> BitnunResult<()>;
+    fn tip(&self) -> BitnunResult<Option<u64>>;
+    fn put_account(&mut self, address: &str, account: &AccountState) -> BitnunResult<()>;
+    fn delete_account(&mut self, address: &str) -> BitnunResult<()>;
+    fn accounts(&self) -> BitnunResult<Vec<(String, AccountState)>>;
+    fn put_summary(&mut self, summary: &StateSummary) -> BitnunResult<()>;
+    fn summary(&self) -> BitnunResult<Option<StateSummary>>;
+
+    // Ends a batch of writes; stores that buffer them write the batch at once
+    fn commit(&mut self) -> BitnunResult<()> {
+        Ok(())
+    }
+
+    // Settles once every committed batch is durable
+    #[cfg(feature = "wasm")]
+    fn committed(&self) -> js_sys::Promise {
+        js_sys::Promise::resolve(&wasm_bindgen::JsValue::UNDEFINED)
+    }
+
+    fn get_block_at(&self, height: u64) -> BitnunResult<Option<Block>> {
+        match self.get_hash_at(height)? {
+            Some(hash) => self.get_block(&hash),
+            None => Ok(None),
+        }
+    }
+}
+
+// Shared handle so the chain stays cheap to clone; clones write to the same store
+#[derive(Clone)]
+pub struct StoreHandle(Rc<RefCell<Box<dyn BlockStore>>>);
+
+impl StoreHandle {
+    pub fn new(store: Box<dyn BlockStore>) -> Self {
+        StoreHandle(Rc::new(RefCell::new(store)))
+    }
+}
+
+impl fmt::Debug for StoreHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "StoreHandle")
+    }
+}
+
+impl BitnunBlockchain {
+    // Opens the chain kept in `store`, or starts one from `config` if the
+    // store is empty. The stored accounts are the state at the base of the
+    // reorg window, so only the blocks after it are applied again; blocks
+    // below it are held as headers and their bodies stay in the store.
+    pub fn open(store: Box<dyn BlockStore>, config: &GenesisConfig) -> BitnunResult<BitnunBlockchain> {
+        let mut chain = BitnunBlockchain::from_genesis_config(config)?;
+        let handle = StoreHandle::new(store);
+
+        let tip = handle.0.borrow().tip()?;
+        let tip = match tip {
+            Some(tip) => tip,
+            None => {
+                chain.store = Some(handle);
+                chain.persist_blocks_from(0, &HashSet::new())?;
+                return Ok(chain);
+            }
+        };
+
+        let mut index = ChainIndex::default();
+        let mut undo_log = VecDeque::new();
+        let (blocks, base_state, state) = {
+            let store = handle.0.borrow();
+            let genesis_hash = store.get_hash_at(0)?.unwrap_or_default();
+            if genesis_hash != chain.blocks[0].hash {
+                return Err(StoreError::GenesisMismatch {
+                    expected: chain.blocks[0].hash.clone(),
+                    found: genesis_hash,
+                }.into());
+            }
+
+            let summary = store.summary()?
+                .ok_or_else(|| StoreError::Corrupt("no state summary".to_string()))?;
+            if summary.height == 0 || summary.height > tip + 1 {
+                return Err(StoreError::Corrupt(format!("state summary at height {} past tip {}", summary.height, tip)).into());
+            }
+            let base_state = AccountStateIndex::from_parts(store.accounts()?, summary);
+
+            // Every body is still read for the transaction index, but only
+            // the window's are applied
+            let mut state = base_state.clone();
+            let mut blocks = Vec::new();
+            for height in 0..=tip {
+                let block = store.get_block_at(height)?
+                    .ok_or_else(|| StoreError::Corrupt(format!("no block at height {}", height)))?;
+                index.add_block(&block);
+                if height < base_state.height {
+                    blocks.push(Block::from_header(&block.header()));
+                } else {
+                    undo_log.push_back(state.undo_for(&block));
+                    state.apply_block(&block, chain.emission.epoch_start(height));
+                    blocks.push(block);
+                }
+            }
+            (blocks, base_state, state)
+        };
+
+        let base_height = base_state.height;
+        if base_state.state_root() != blocks[base_height as usize - 1].state_root {
+            return Err(StoreError::Corrupt(format!("stored accounts do not match the state root at height {}", base_height - 1)).into());
+        }
+        if state.state_root() != blocks[blocks.len() - 1].state_root {
+            return Err(StoreError::Corrupt(format!("account state does not match the state root at height {}", tip)).into());
+        }
+
+        // Headers are re-checked for linkage and proof-of-work; bodies were validated when stored
+        chain.pruned_height = base_height;
+        for position in 1..blocks.len() {
+            chain.check_block_header(&blocks[..position], &blocks[position])
+                .map_err(|violation| StoreError::Corrupt(violation.detail))?;
+        }
+
+        chain.blocks = blocks;
+        chain.index = index;
+        chain.base_state = base_state;
+        chain.state = state;
+        chain.undo_log = undo_log;
+        chain.carbon_offset = tip * 10; // 10g CO2 per block
+        chain.mining_bits = chain.difficulty_config.next_bits(&chain.blocks);
+        chain.store = Some(handle);
+
+        console_log!("Opened chain {} at height {}", chain.chain_id, tip);
+        Ok(chain)
+    }
+
+    // Full block at a height of the active chain, read from the store if
+    // only its header is held in memory
+    pub fn block_at(&self, height: u64) -> BitnunResult<Option<Block>> {
+        Ok(self.block_body_at(height)?.map(Cow::into_owned))
+    }
+
+    // As block_at, borrowing the block when its body is held in memory
+    pub(crate) fn block_body_at(&self, height: u64) -> BitnunResult<Option<Cow<'_, Block>>> {
+        let block = match self.blocks.get(height as usize) {
+            Some(block) => block,
+            None => return Ok(None),
+        };
+        if height >= self.pruned_height {
+            return Ok(Some(Cow::Borrowed(block)));
+        }
+
+        Ok(self.stored_block(&block.hash)?.map(Cow::Owned))
+    }
+
+    // Block with its body from the store, on any branch
+    pub(crate) fn stored_block(&self, hash: &str) -> BitnunResult<Option<Block>> {
+        match &self.store {
+            Some(store) => store.0.borrow().get_block(hash),
+            None => Ok(None),
+        }
+    }
+
+    // Writes blocks of the active chain from `position` up, then the state at
+    // the base of the reorg window, and finally the tip, so an interrupted
+    // write keeps the old tip. `also_touched` names accounts that blocks
+    // detached from above `position` affected.
+    pub(crate) fn persist_blocks_from(&self, position: usize, also_touched: &HashSet<String>) -> BitnunResult<()> {
+        let store = match &self.store {
+            Some(store) => store,
+            None => return Ok(()),
+        };
+        let mut store = store.0.borrow_mut();
+
+        for block in &self.blocks[position..] {
+            store.put_block(block)?;
+            store.put_height(block.index, &block.hash)?;
+        }
+
+        // The undo log always covers the newest blocks, so the base is where it starts
+        let mut base = self.state.clone();
+        for undo in self.undo_log.iter().rev() {
+            base.revert(undo);
+        }
+
+        // Only accounts of blocks between the stored base and the new one, on
+        // either branch, can differ from what the store holds
+        let mut touched: HashSet<&str> = match store.summary()? {
+            Some(stored) => {
+                let (stored, height) = (stored.height as usize, base.height as usize);
+                let end = stored.max(height).min(self.blocks.len());
+                let mut touched: HashSet<&str> = also_touched.iter().map(|address| address.as_str()).collect();
+                for block in &self.blocks[position.min(stored).min(height)..end] {
+                    for transaction in &block.transactions {
+                        touched.insert(transaction.from_address.as_str());
+                        touched.extend(transaction.recipients());
+                    }
+                }
+                touched
+            }
+            None => base.accounts.keys().map(|address| address.as_str()).collect(),
+        };
+        touched.remove(""); // System source, never an account
+
+        for address in touched {
+            match base.get(address) {
+                Some(account) => store.put_account(address, account)?,
+                None => store.delete_account(address)?,
+            }
+        }
+
+        store.put_summary(&base.summary())?;
+        store.put_tip(self.blocks.len() as u64 - 1)?;
+        store.commit()
+    }
+
+    pub(crate) fn persist_side_block(&self, block: &Block) -> BitnunResult<()> {
+        match &self.store {
+            Some(store) => store.0.borrow_mut().put_block(block),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::{Transaction, EASIEST_BITS, REORG_WINDOW};
+    use crate::consensus::ProofOfAction;
+    use crate::error::{BitnunError, ChainError};
+    use crate::runtime;
+
+    fn store_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("bitnun-store-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn reopened_chain_keeps_spent_action_proofs() {
+        let dir = store_dir("spent");
+        let config = GenesisConfig::dev();
+        // Dated ahead of the block so it is still inside the window after it
+        let proof = ProofOfAction::new("btnactor", "form_submit", runtime::now_millis() + 60_000.0, EASIEST_BITS);
+        let mint = || Transaction::new_action_mining("btnactor", "form_submit", proof.calculate_reward(), &proof.to_json());
+        {
+            let mut chain = BitnunBlockchain::open(Box::new(FileStore::open(&dir).unwrap()), &config).unwrap();
+            chain.add_transaction(&mint()).unwrap();
+            chain.mine_pending_transactions("btnminer");
+            assert!(chain.state.is_action_spent(&proof.action_id()));
+        }
+
+        let mut chain = BitnunBlockchain::open(Box::new(FileStore::open(&dir).unwrap()), &config).unwrap();
+        assert!(chain.state.is_action_spent(&proof.action_id()));
+        match chain.add_transaction(&mint()) {
+            Err(BitnunError::Chain(ChainError::ActionAlreadyMinted(_))) => {}
+            other => panic!("expected the proof to be spent, got {:?}", other),
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn reopened_chain_reorganizes_within_the_window() {
+        let dir = store_dir("reorg");
+        let config = GenesisConfig::dev();
+        {
+            let mut chain = BitnunBlockchain::open(Box::new(FileStore::open(&dir).unwrap()), &config).unwrap();
+            for _ in 0..3 {
+                chain.mine_pending_transactions("btnminer");
+            }
+        }
+
+        let mut rival = BitnunBlockchain::from_genesis_config(&config).unwrap();
+        for _ in 0..4 {
+            rival.mine_pending_transactions("btnrival");
+        }
+
+        let mut chain = BitnunBlockchain::open(Box::new(FileStore::open(&dir).unwrap()), &config).unwrap();
+        for block in &rival.blocks[1..] {
+            chain.accept_block(block.clone()).unwrap();
+        }
+        assert_eq!(chain.get_latest_block().hash, rival.get_latest_block().hash);
+        assert_eq!(chain.get_balance("btnminer"), 0);
+        assert_eq!(chain.state, rival.state);
+
+        let reopened = BitnunBlockchain::open(Box::new(FileStore::open(&dir).unwrap()), &config).unwrap();
+        assert_eq!(reopened.state, rival.state);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn bodies_below_the_window_are_read_from_the_store() {
+        let dir = store_dir("window");
+        let mut config = GenesisConfig::dev();
+        config.difficulty.retarget_interval = 1_000; // Keep the target easy for fast blocks
+        let tip = REORG_WINDOW + 5;
+        {
+            let mut chain = BitnunBlockchain::open(Box::new(FileStore::open(&dir).unwrap()), &config).unwrap();
+            for _ in 0..tip {
+                chain.mine_pending_transactions("btnminer");
+            }
+        }
+
+        let chain = BitnunBlockchain::open(Box::new(FileStore::open(&dir).unwrap()), &config).unwrap();
+        assert_eq!(chain.pruned_height, tip + 1 - REORG_WINDOW);
+        assert!(chain.blocks[1].is_header_only());
+        assert!(!chain.blocks[chain.pruned_height as usize].is_header_only());
+        assert_eq!(chain.block_at(1).unwrap().unwrap().transactions.len(), 1);
+        assert_eq!(chain.replay_chain().unwrap(), chain.state);
+        assert_eq!(chain.circulating_supply_at(tip), Some(chain.state.circulating_supply()));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn reopening_starts_from_the_stored_accounts_at_the_window_base() {
+        let dir = store_dir("base");
+        let mut config = GenesisConfig::dev();
+        config.difficulty.retarget_interval = 1_000;
+        {
+            let mut chain = BitnunBlockchain::open(Box::new(FileStore::open(&dir).unwrap()), &config).unwrap();
+            for _ in 0..REORG_WINDOW + 2 {
+                chain.mine_pending_transactions("btnminer");
+            }
+        }
+
+        let mut store = FileStore::open(&dir).unwrap();
+        let summary = store.summary().unwrap().unwrap();
+        let chain = BitnunBlockchain::open(Box::new(store.clone()), &config).unwrap();
+        assert_eq!(summary.height, chain.pruned_height);
+        assert_eq!(chain.base_state.summary(), summary);
+
+        // The base is not rebuilt from bodies, so a stored account that
+        // disagrees with its header is caught rather than replaced
+        let mut account = store.accounts().unwrap().into_iter().find(|(address, _)| address == "btnminer").unwrap().1;
+        account.balance += 1;
+        store.put_account("btnminer", &account).unwrap();
+        match BitnunBlockchain::open(Box::new(store), &config) {
+            Err(BitnunError::Store(StoreError::Corrupt(_))) => {}
+            other => panic!("expected a corrupt store, got {:?}", other.map(|chain| chain.get_latest_block().index)),
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
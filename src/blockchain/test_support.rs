@@ -0,0 +1,16 @@
+// Fixtures shared by the blockchain test modules
+use super::{KeyPair, Transaction, DEV_SECRET_KEY};
+
+pub(crate) const DEV_CHAIN_ID: &str = "bitnun-dev";
+
+// The funded validator of the dev preset
+pub(crate) fn dev_keypair() -> KeyPair {
+    KeyPair::from_secret_key(DEV_SECRET_KEY).unwrap()
+}
+
+// Stamps the transaction with the dev chain id and signs it as `keypair`
+pub(crate) fn signed(mut transaction: Transaction, keypair: &KeyPair) -> Transaction {
+    transaction.set_chain_id(DEV_CHAIN_ID);
+    transaction.sign(keypair).unwrap();
+    transaction
+}
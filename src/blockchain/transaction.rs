@@ -1,96 +1,564 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+use crate::blockchain::keys::{derive_address, verify_signature, KeyPair};
+use crate::blockchain::encoding::{
+    sha256_hex, DecodeError, Decoder, Encoder, KIND_TRANSACTION, KIND_TRANSACTION_SIGNING,
+};
+use crate::blockchain::{
+    contract_address, CoSignature, MultisigPolicy, TransactionKind, TransferOutput, DEFAULT_CHAIN_ID,
+};
+use crate::consensus::ProofOfAction;
+#[cfg(feature = "wasm")]
+use crate::error::ConfigError;
+use crate::error::{BitnunResult, KeyError};
+use crate::runtime;
 
-#[wasm_bindgen]
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", wasm_bindgen(getter_with_clone))]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Transaction {
     pub id: String,
     pub from_address: String,
     pub to_address: String,
     pub amount: u64,
     pub timestamp: String,
-    pub transaction_type: String,
-    pub action_proof: Option<String>, // Cryptographic proof of user action
+    #[serde(flatten)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
+    pub kind: TransactionKind,
     pub carbon_offset: u64, // CO2 saved in grams
+    #[serde(default)]
+    pub fee: u64, // Paid by the sender to the block producer
+    #[serde(default)]
+    pub nonce: u64, // Per-sender sequence number, starting at 0
+    #[serde(default)]
+    pub chain_id: String, // Chain the transaction is valid on
+    #[serde(default)]
+    pub public_key: Option<String>, // Hex-encoded Ed25519 public key of the sender
+    #[serde(default)]
+    pub signature: Option<String>, // Hex-encoded Ed25519 signature over signing_payload
+    #[serde(default)]
+    pub unlock_height: u64, // The amount can't be spent before this block height; 0 for none
+    #[serde(default)]
+    pub unlock_time: u64, // Nor before this block time, in Unix milliseconds; 0 for none
+    #[serde(default)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
+    pub co_signatures: Vec<CoSignature>, // Spends from a multisig account sign here instead
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl Transaction {
-    #[wasm_bindgen(constructor)]
-    pub fn new(
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
+    pub fn new(from_address: &str, to_address: &str, amount: u64) -> Transaction {
+        Transaction::with_kind(from_address, to_address, amount, TransactionKind::Transfer)
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn new_stake(from_address: &str, validator_address: &str, amount: u64) -> Transaction {
+        Transaction::with_kind(from_address, validator_address, amount, TransactionKind::Stake)
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn new_unstake(from_address: &str, validator_address: &str, amount: u64) -> Transaction {
+        Transaction::with_kind(from_address, validator_address, amount, TransactionKind::Unstake)
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn new_contract_call(
         from_address: &str,
-        to_address: &str,
+        contract_address: &str,
         amount: u64,
-        transaction_type: &str,
+        method: &str,
+        params: &str,
     ) -> Transaction {
-        Transaction {
-            id: Uuid::new_v4().to_string(),
-            from_address: from_address.to_string(),
-            to_address: to_address.to_string(),
+        Transaction::with_kind(from_address, contract_address, amount, TransactionKind::ContractCall {
+            method: method.to_string(),
+            params: params.to_string(),
+        })
+    }
+
+    // The contract's address depends on the nonce, so it is fixed here
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn new_contract_deploy(from_address: &str, amount: u64, code: &str, nonce: u64) -> Transaction {
+        let mut transaction = Transaction::with_kind(
+            from_address,
+            &contract_address(from_address, nonce),
             amount,
-            timestamp: Utc::now().to_rfc3339(),
-            transaction_type: transaction_type.to_string(),
-            action_proof: None,
-            carbon_offset: 10, // Each transaction saves 10g CO2
-        }
+            TransactionKind::ContractDeploy { code: code.to_string() },
+        );
+        transaction.nonce = nonce;
+        transaction
     }
 
+    #[cfg(feature = "wasm")]
     #[wasm_bindgen]
+    pub fn new_register_multisig(from_address: &str, amount: u64, policy: JsValue) -> BitnunResult<Transaction> {
+        let policy: MultisigPolicy = serde_wasm_bindgen::from_value(policy)
+            .map_err(|error| ConfigError::Parse(error.to_string()))?;
+        Ok(Transaction::new_multisig_registration(from_address, amount, policy))
+    }
+
+    // `outputs` is an array of {to_address, amount}
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen]
+    pub fn new_batch_transfer(from_address: &str, outputs: JsValue) -> BitnunResult<Transaction> {
+        let outputs: Vec<TransferOutput> = serde_wasm_bindgen::from_value(outputs)
+            .map_err(|error| ConfigError::Parse(error.to_string()))?;
+        Ok(Transaction::new_batch(from_address, outputs))
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn new_nft_mint(from_address: &str, to_address: &str, token_id: &str, metadata_uri: &str) -> Transaction {
+        Transaction::with_kind(from_address, to_address, 0, TransactionKind::NftMint {
+            token_id: token_id.to_string(),
+            metadata_uri: metadata_uri.to_string(),
+        })
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn new_nft_transfer(from_address: &str, to_address: &str, token_id: &str) -> Transaction {
+        Transaction::with_kind(from_address, to_address, 0, TransactionKind::NftTransfer {
+            token_id: token_id.to_string(),
+        })
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn new_action_mining(
         user_address: &str,
         action_type: &str,
         reward_amount: u64,
         action_proof: &str,
     ) -> Transaction {
+        let kind = TransactionKind::ActionReward {
+            action_type: action_type.to_string(),
+            action_proof: action_proof.to_string(),
+        };
         Transaction {
-            id: Uuid::new_v4().to_string(),
-            from_address: "".to_string(), // System reward
-            to_address: user_address.to_string(),
-            amount: reward_amount,
-            timestamp: Utc::now().to_rfc3339(),
-            transaction_type: format!("action_mining_{}", action_type),
-            action_proof: Some(action_proof.to_string()),
             carbon_offset: 5, // Action mining saves 5g CO2
+            ..Transaction::with_kind("", user_address, reward_amount, kind) // System reward
         }
     }
 
     pub fn new_mining_reward(miner_address: &str, reward_amount: u64) -> Transaction {
         Transaction {
-            id: Uuid::new_v4().to_string(),
-            from_address: "".to_string(),
-            to_address: miner_address.to_string(),
-            amount: reward_amount,
-            timestamp: Utc::now().to_rfc3339(),
-            transaction_type: "mining_reward".to_string(),
-            action_proof: None,
             carbon_offset: 15, // Mining rewards save 15g CO2
+            ..Transaction::with_kind("", miner_address, reward_amount, TransactionKind::MiningReward)
         }
     }
 
     pub fn new_genesis(genesis_address: &str, initial_supply: u64) -> Transaction {
         Transaction {
             id: "genesis".to_string(),
-            from_address: "".to_string(),
-            to_address: genesis_address.to_string(),
-            amount: initial_supply,
-            timestamp: Utc::now().to_rfc3339(),
-            transaction_type: "genesis".to_string(),
-            action_proof: None,
             carbon_offset: 0,
+            ..Transaction::with_kind("", genesis_address, initial_supply, TransactionKind::Genesis)
         }
     }
 
-    #[wasm_bindgen]
-    pub fn set_action_proof(&mut self, proof: &str) {
-        self.action_proof = Some(proof.to_string());
+    // Stake credited to a genesis validator; locked rather than spendable
+    pub fn new_genesis_stake(validator_address: &str, stake: u64) -> Transaction {
+        Transaction {
+            kind: TransactionKind::GenesisStake,
+            ..Transaction::new_genesis(validator_address, stake)
+        }
+    }
+
+    // String tag of the kind, as in the JSON form
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn transaction_type(&self) -> String {
+        self.kind.transaction_type().into_owned()
     }
 
+    #[cfg(feature = "wasm")]
     #[wasm_bindgen]
+    pub fn get_kind(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.kind).unwrap()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn set_fee(&mut self, fee: u64) {
+        self.fee = fee;
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn set_nonce(&mut self, nonce: u64) {
+        self.nonce = nonce;
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn set_chain_id(&mut self, chain_id: &str) {
+        self.chain_id = chain_id.to_string();
+    }
+
+    // Locks the transferred amount in the recipient's account until both
+    // the block height and the block time are reached
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn set_lock(&mut self, unlock_height: u64, unlock_time: u64) {
+        self.unlock_height = unlock_height;
+        self.unlock_time = unlock_time;
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn is_locked(&self) -> bool {
+        self.unlock_height > 0 || self.unlock_time > 0
+    }
+
+    // Adds this member's signature for a spend from a multisig account,
+    // replacing any earlier one from the same key
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn co_sign(&mut self, keypair: &KeyPair) {
+        let public_key = keypair.public_key();
+        let signature = keypair.sign(&self.signing_payload());
+        self.co_signatures.retain(|co_signature| co_signature.public_key != public_key);
+        self.co_signatures.push(CoSignature { public_key, signature });
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn is_system(&self) -> bool {
+        self.kind.is_system()
+    }
+
+    // Canonical encoding of every field except the signature
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn signing_payload(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new(KIND_TRANSACTION_SIGNING);
+        self.encode_fields(&mut encoder);
+        encoder.finish()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new(KIND_TRANSACTION);
+        self.encode_fields(&mut encoder);
+        encoder.put_opt_str(self.signature.as_deref());
+        encoder.put_u32(self.co_signatures.len() as u32);
+        for co_signature in &self.co_signatures {
+            encoder.put_str(&co_signature.public_key);
+            encoder.put_str(&co_signature.signature);
+        }
+        encoder.finish()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn from_bytes(bytes: &[u8]) -> BitnunResult<Transaction> {
+        Ok(Transaction::decode(bytes)?)
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn hash(&self) -> String {
+        sha256_hex(&self.to_bytes())
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn sign(&mut self, keypair: &KeyPair) -> BitnunResult<()> {
+        let address = keypair.address();
+        if address != self.from_address {
+            return Err(KeyError::AddressMismatch {
+                expected: self.from_address.clone(),
+                found: address,
+            }.into());
+        }
+
+        self.public_key = Some(keypair.public_key());
+        self.signature = Some(keypair.sign(&self.signing_payload()));
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn verify(&self) -> bool {
+        let (public_key, signature) = match (&self.public_key, &self.signature) {
+            (Some(public_key), Some(signature)) => (public_key, signature),
+            _ => return false,
+        };
+
+        // The sender address must be the one derived from the signing key
+        if derive_address(public_key).ok().as_deref() != Some(self.from_address.as_str()) {
+            return false;
+        }
+
+        verify_signature(public_key, &self.signing_payload(), signature)
+    }
+
+    // What the sender's balance pays: amount plus fee, or just the fee when
+    // unstaking; None if it overflows
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn total_debit(&self) -> Option<u64> {
+        match self.kind {
+            TransactionKind::Unstake => Some(self.fee),
+            _ => self.amount.checked_add(self.fee),
+        }
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn is_valid(&self) -> bool {
-        !self.id.is_empty() 
-            && !self.to_address.is_empty() 
-            && self.amount > 0
+        self.check_fields().is_ok()
     }
-}
\ No newline at end of file
+}
+
+impl Transaction {
+    // Registers `policy` at its derived address, funded with `amount`
+    pub fn new_multisig_registration(from_address: &str, amount: u64, policy: MultisigPolicy) -> Transaction {
+        let address = policy.address();
+        Transaction::with_kind(from_address, &address, amount, TransactionKind::RegisterMultisig(policy))
+    }
+
+    // Pays every output from one debit of their total
+    pub fn new_batch(from_address: &str, outputs: Vec<TransferOutput>) -> Transaction {
+        let amount = outputs.iter().fold(0u64, |total, output| total.saturating_add(output.amount));
+        Transaction::with_kind(from_address, "", amount, TransactionKind::BatchTransfer { outputs })
+    }
+
+    // Addresses whose balance this credits, with the amounts, in order
+    pub fn credits(&self) -> Vec<(&str, u64)> {
+        match &self.kind {
+            TransactionKind::BatchTransfer { outputs } =>
+                outputs.iter().map(|output| (output.to_address.as_str(), output.amount)).collect(),
+            kind if kind.credits_recipient() => vec![(self.to_address.as_str(), self.amount)],
+            _ => Vec::new(),
+        }
+    }
+
+    // Every receiving address, each once
+    pub fn recipients(&self) -> Vec<&str> {
+        match &self.kind {
+            TransactionKind::BatchTransfer { outputs } => {
+                let mut seen = HashSet::new();
+                outputs
+                    .iter()
+                    .map(|output| output.to_address.as_str())
+                    .filter(|address| seen.insert(*address))
+                    .collect()
+            }
+            _ => vec![self.to_address.as_str()],
+        }
+    }
+
+    pub fn with_kind(from_address: &str, to_address: &str, amount: u64, kind: TransactionKind) -> Transaction {
+        Transaction {
+            id: Uuid::new_v4().to_string(),
+            from_address: from_address.to_string(),
+            to_address: to_address.to_string(),
+            amount,
+            timestamp: runtime::now_rfc3339(),
+            kind,
+            carbon_offset: 10, // Each transaction saves 10g CO2
+            fee: 0,
+            nonce: 0,
+            chain_id: DEFAULT_CHAIN_ID.to_string(),
+            public_key: None,
+            signature: None,
+            unlock_height: 0,
+            unlock_time: 0,
+            co_signatures: Vec::new(),
+        }
+    }
+
+    // Shape checks that need no chain state
+    pub fn check_fields(&self) -> Result<(), String> {
+        let is_batch = matches!(self.kind, TransactionKind::BatchTransfer { .. });
+        if self.id.is_empty() || self.to_address.is_empty() != is_batch {
+            return Err("missing id or recipient".to_string());
+        }
+        if is_batch && self.kind.batch_total() != Some(self.amount) {
+            return Err(format!("amount {} differs from the total of the outputs", self.amount));
+        }
+        if self.amount == 0 && !self.kind.allows_zero_amount() {
+            return Err(format!("{} needs an amount", self.kind.tag()));
+        }
+        // Only system kinds come from the empty address, and they always do
+        if self.from_address.is_empty() != self.kind.is_system() {
+            return Err(format!("{} from '{}'", self.kind.tag(), self.from_address));
+        }
+        match &self.kind {
+            TransactionKind::ContractDeploy { .. } if self.to_address != contract_address(&self.from_address, self.nonce) =>
+                return Err("deployment to an address other than the one it creates".to_string()),
+            TransactionKind::RegisterMultisig(policy) if self.to_address != policy.address() =>
+                return Err("registration for an address other than the policy's".to_string()),
+            _ => {}
+        }
+        if self.is_locked() && (self.is_system() || !self.kind.credits_recipient()) {
+            return Err(format!("{} can't lock its amount", self.kind.tag()));
+        }
+        self.kind.check_fields()
+    }
+
+    // The ProofOfAction an action-mining transaction carries, if it parses
+    pub fn proof_of_action(&self) -> Option<ProofOfAction> {
+        match &self.kind {
+            TransactionKind::ActionReward { action_proof, .. } => ProofOfAction::from_json(action_proof),
+            _ => None,
+        }
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Transaction, DecodeError> {
+        let mut decoder = Decoder::new(bytes, KIND_TRANSACTION)?;
+        let mut transaction = Transaction {
+            id: decoder.get_string()?,
+            from_address: decoder.get_string()?,
+            to_address: decoder.get_string()?,
+            amount: decoder.get_u64()?,
+            timestamp: decoder.get_string()?,
+            kind: TransactionKind::decode_fields(&mut decoder)?,
+            carbon_offset: decoder.get_u64()?,
+            fee: decoder.get_u64()?,
+            nonce: decoder.get_u64()?,
+            chain_id: decoder.get_string()?,
+            public_key: decoder.get_opt_string()?,
+            unlock_height: decoder.get_u64()?,
+            unlock_time: decoder.get_u64()?,
+            signature: decoder.get_opt_string()?,
+            co_signatures: Vec::new(),
+        };
+        for _ in 0..decoder.get_u32()? {
+            transaction.co_signatures.push(CoSignature {
+                public_key: decoder.get_string()?,
+                signature: decoder.get_string()?,
+            });
+        }
+        decoder.finish()?;
+        Ok(transaction)
+    }
+
+    fn encode_fields(&self, encoder: &mut Encoder) {
+        encoder.put_str(&self.id);
+        encoder.put_str(&self.from_address);
+        encoder.put_str(&self.to_address);
+        encoder.put_u64(self.amount);
+        encoder.put_str(&self.timestamp);
+        self.kind.encode_fields(encoder);
+        encoder.put_u64(self.carbon_offset);
+        encoder.put_u64(self.fee);
+        encoder.put_u64(self.nonce);
+        encoder.put_str(&self.chain_id);
+        encoder.put_opt_str(self.public_key.as_deref());
+        encoder.put_u64(self.unlock_height);
+        encoder.put_u64(self.unlock_time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::{BitnunBlockchain, MAX_BATCH_OUTPUTS};
+    use crate::blockchain::test_support::{dev_keypair, signed, DEV_CHAIN_ID};
+    use crate::error::{BitnunError, ChainError, KeyError};
+
+    fn output(to_address: &str, amount: u64) -> TransferOutput {
+        TransferOutput { to_address: to_address.to_string(), amount }
+    }
+
+    #[test]
+    fn batch_fields_are_checked() {
+        let valid = Transaction::new_batch("btnpayer", vec![output("btnone", 5), output("btnone", 7)]);
+        assert_eq!(valid.amount, 12);
+        assert_eq!(valid.check_fields(), Ok(()));
+
+        let mut mismatched = valid.clone();
+        mismatched.amount = 11;
+        assert!(mismatched.check_fields().is_err());
+
+        assert!(Transaction::new_batch("btnpayer", Vec::new()).check_fields().is_err());
+        assert!(Transaction::new_batch("btnpayer", vec![output("", 5)]).check_fields().is_err());
+        assert!(Transaction::new_batch("btnpayer", vec![output("btnone", 5), output("btntwo", 0)]).check_fields().is_err());
+
+        let too_many = vec![output("btnone", 1); MAX_BATCH_OUTPUTS + 1];
+        assert!(Transaction::new_batch("btnpayer", too_many).check_fields().is_err());
+    }
+
+    #[test]
+    fn batch_is_checked_against_the_whole_debit() {
+        let dev = dev_keypair();
+        let mut chain = BitnunBlockchain::from_preset("dev").unwrap();
+        let balance = chain.get_balance(&dev.address());
+
+        let batch = signed(Transaction::new_batch(&dev.address(), vec![output("btnone", balance), output("btntwo", 1)]), &dev);
+        match chain.add_transaction(&batch) {
+            Err(BitnunError::Chain(ChainError::InsufficientBalance { .. })) => {}
+            other => panic!("expected an insufficient balance error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn signature_covers_every_signed_field() {
+        let dev = dev_keypair();
+        let transaction = signed(Transaction::new(&dev.address(), "btnpayee", 100), &dev);
+        assert!(transaction.verify());
+
+        let tampered: Vec<fn(&mut Transaction)> = vec![
+            |tx| tx.to_address = "btnthief".to_string(),
+            |tx| tx.amount += 1,
+            |tx| tx.fee += 1,
+            |tx| tx.nonce += 1,
+            |tx| tx.chain_id = "bitnun-main".to_string(),
+            |tx| tx.timestamp = "2020-01-01T00:00:00Z".to_string(),
+            |tx| tx.unlock_height = 10,
+        ];
+        for tamper in tampered {
+            let mut copy = transaction.clone();
+            tamper(&mut copy);
+            assert!(!copy.verify());
+        }
+
+        let mut chain = BitnunBlockchain::from_preset("dev").unwrap();
+        let mut forged = transaction.clone();
+        forged.amount = 1000;
+        assert_eq!(chain.add_transaction(&forged).err(), Some(BitnunError::Chain(ChainError::InvalidSignature)));
+    }
+
+    #[test]
+    fn tampered_or_missing_signatures_are_rejected() {
+        let dev = dev_keypair();
+        let transaction = signed(Transaction::new(&dev.address(), "btnpayee", 100), &dev);
+
+        let mut signature = hex::decode(transaction.signature.as_ref().unwrap()).unwrap();
+        signature[0] ^= 1;
+        let mut flipped = transaction.clone();
+        flipped.signature = Some(hex::encode(signature));
+        assert!(!flipped.verify());
+
+        let mut truncated = transaction.clone();
+        truncated.signature = Some(transaction.signature.as_ref().unwrap()[..126].to_string());
+        assert!(!truncated.verify());
+
+        let mut unsigned = transaction.clone();
+        unsigned.signature = None;
+        assert!(!unsigned.verify());
+    }
+
+    #[test]
+    fn signing_key_must_match_the_sender_address() {
+        let dev = dev_keypair();
+        let other = KeyPair::generate();
+
+        let mut transaction = Transaction::new(&dev.address(), "btnpayee", 100);
+        transaction.set_chain_id(DEV_CHAIN_ID);
+        match transaction.sign(&other) {
+            Err(BitnunError::Key(KeyError::AddressMismatch { expected, found })) => {
+                assert_eq!(expected, dev.address());
+                assert_eq!(found, other.address());
+            }
+            other => panic!("expected an address mismatch, got {:?}", other),
+        }
+
+        // A valid signature by another key doesn't authorize the sender
+        transaction.public_key = Some(other.public_key());
+        transaction.signature = Some(other.sign(&transaction.signing_payload()));
+        assert!(!transaction.verify());
+    }
+
+    #[test]
+    fn action_reward_json_flattens_the_action_mining_tag() {
+        let reward = Transaction::with_kind("", "btnuser", 25, TransactionKind::ActionReward {
+            action_type: "recycling".to_string(),
+            action_proof: "{}".to_string(),
+        });
+        let json = serde_json::to_value(&reward).unwrap();
+        assert_eq!(json["transaction_type"], "action_mining_recycling");
+        assert_eq!(reward.transaction_type(), "action_mining_recycling");
+
+        let decoded: Transaction = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded.kind, reward.kind);
+        assert_eq!(decoded.hash(), reward.hash());
+    }
+}
+
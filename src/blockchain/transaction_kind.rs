@@ -0,0 +1,322 @@
+use serde::de::Error as _;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::borrow::Cow;
+use crate::blockchain::MultisigPolicy;
+use crate::blockchain::keys::address_from_bytes;
+use crate::blockchain::encoding::{DecodeError, Decoder, Encoder};
+
+pub const MAX_BATCH_OUTPUTS: usize = 1000;
+
+// JSON tag prefix of action rewards, which carry their action type in the tag
+const ACTION_REWARD_PREFIX: &str = "action_mining_";
+
+// What a transaction does, with the fields only that kind carries. In JSON
+// it flattens into the transaction under the same `transaction_type` string
+// tag the JS side has always used, e.g. {"transaction_type": "transfer", ...}
+// or {"transaction_type": "action_mining_recycling", "action_proof": ...}.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(remote = "Self", tag = "transaction_type", rename_all = "snake_case")]
+pub enum TransactionKind {
+    Transfer,
+    Genesis,
+    GenesisStake, // Locked rather than spendable
+    MiningReward,
+    ActionReward {
+        action_type: String,
+        action_proof: String, // Serialized ProofOfAction
+    },
+    Stake, // Moves amount from the sender's balance into its stake; to_address is the validator
+    Unstake, // Moves amount from the sender's stake back into its balance
+    ContractCall {
+        method: String,
+        params: String, // JSON-encoded arguments
+    },
+    ContractDeploy {
+        code: String,
+    },
+    NftMint {
+        token_id: String,
+        metadata_uri: String,
+    },
+    NftTransfer {
+        token_id: String,
+    },
+    RegisterMultisig(MultisigPolicy), // to_address is the policy's address
+    BatchTransfer {
+        outputs: Vec<TransferOutput>, // Amount is their total; to_address is empty
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TransferOutput {
+    pub to_address: String,
+    pub amount: u64,
+}
+
+impl Serialize for TransactionKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            TransactionKind::ActionReward { action_proof, .. } => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("transaction_type", &self.transaction_type())?;
+                map.serialize_entry("action_proof", action_proof)?;
+                map.end()
+            }
+            _ => TransactionKind::serialize(self, serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for TransactionKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut fields = serde_json::Map::deserialize(deserializer)?;
+        let action_type = fields
+            .get("transaction_type")
+            .and_then(Value::as_str)
+            .and_then(|tag| tag.strip_prefix(ACTION_REWARD_PREFIX))
+            .map(str::to_string);
+        if let Some(action_type) = action_type {
+            fields.insert("transaction_type".to_string(), Value::from("action_reward"));
+            fields.insert("action_type".to_string(), Value::from(action_type));
+        }
+        TransactionKind::deserialize(Value::Object(fields)).map_err(D::Error::custom)
+    }
+}
+
+impl TransactionKind {
+    // The `transaction_type` string of the JSON form
+    pub fn transaction_type(&self) -> Cow<'static, str> {
+        match self {
+            TransactionKind::ActionReward { action_type, .. } =>
+                Cow::Owned(format!("{}{}", ACTION_REWARD_PREFIX, action_type)),
+            _ => Cow::Borrowed(self.tag()),
+        }
+    }
+
+    // Fixed name of the kind, as in the binary encoding
+    pub fn tag(&self) -> &'static str {
+        match self {
+            TransactionKind::Transfer => "transfer",
+            TransactionKind::Genesis => "genesis",
+            TransactionKind::GenesisStake => "genesis_stake",
+            TransactionKind::MiningReward => "mining_reward",
+            TransactionKind::ActionReward { .. } => "action_reward",
+            TransactionKind::Stake => "stake",
+            TransactionKind::Unstake => "unstake",
+            TransactionKind::ContractCall { .. } => "contract_call",
+            TransactionKind::ContractDeploy { .. } => "contract_deploy",
+            TransactionKind::NftMint { .. } => "nft_mint",
+            TransactionKind::NftTransfer { .. } => "nft_transfer",
+            TransactionKind::RegisterMultisig(_) => "register_multisig",
+            TransactionKind::BatchTransfer { .. } => "batch_transfer",
+        }
+    }
+
+    // Created by the chain itself rather than signed by a sender
+    pub fn is_system(&self) -> bool {
+        matches!(self,
+            TransactionKind::Genesis
+                | TransactionKind::GenesisStake
+                | TransactionKind::MiningReward
+                | TransactionKind::ActionReward { .. })
+    }
+
+    pub fn is_genesis(&self) -> bool {
+        matches!(self, TransactionKind::Genesis | TransactionKind::GenesisStake)
+    }
+
+    // Kinds that do something besides moving coins may move none
+    pub fn allows_zero_amount(&self) -> bool {
+        matches!(self,
+            TransactionKind::MiningReward // Empty once emission ends and no fees are paid
+                | TransactionKind::ContractCall { .. }
+                | TransactionKind::ContractDeploy { .. }
+                | TransactionKind::NftMint { .. }
+                | TransactionKind::NftTransfer { .. }
+                | TransactionKind::RegisterMultisig(_))
+    }
+
+    // Whether `amount` lands in the recipient's spendable balance; a batch
+    // credits its outputs instead
+    pub fn credits_recipient(&self) -> bool {
+        !matches!(self,
+            TransactionKind::GenesisStake
+                | TransactionKind::Stake
+                | TransactionKind::Unstake
+                | TransactionKind::BatchTransfer { .. })
+    }
+
+    // Sum of a batch's outputs; None if it overflows or this isn't a batch
+    pub fn batch_total(&self) -> Option<u64> {
+        match self {
+            TransactionKind::BatchTransfer { outputs } =>
+                outputs.iter().try_fold(0u64, |total, output| total.checked_add(output.amount)),
+            _ => None,
+        }
+    }
+
+    pub fn nft_token_id(&self) -> Option<&str> {
+        match self {
+            TransactionKind::NftMint { token_id, .. } | TransactionKind::NftTransfer { token_id } => Some(token_id),
+            _ => None,
+        }
+    }
+
+    // Checks that need nothing but the kind's own fields
+    pub fn check_fields(&self) -> Result<(), String> {
+        let required = |name: &str, value: &str| {
+            if value.is_empty() {
+                Err(format!("{} needs a {}", self.tag(), name))
+            } else {
+                Ok(())
+            }
+        };
+
+        match self {
+            TransactionKind::ActionReward { action_type, action_proof } => {
+                required("action type", action_type)?;
+                required("action proof", action_proof)
+            }
+            TransactionKind::ContractCall { method, .. } => required("method", method),
+            TransactionKind::ContractDeploy { code } => required("code", code),
+            TransactionKind::NftMint { token_id, .. } | TransactionKind::NftTransfer { token_id } =>
+                required("token id", token_id),
+            TransactionKind::RegisterMultisig(policy) => policy.check(),
+            TransactionKind::BatchTransfer { outputs } => {
+                if outputs.is_empty() || outputs.len() > MAX_BATCH_OUTPUTS {
+                    return Err(format!("a batch needs 1 to {} outputs", MAX_BATCH_OUTPUTS));
+                }
+                for output in outputs {
+                    required("output recipient", &output.to_address)?;
+                    if output.amount == 0 {
+                        return Err(format!("output to {} needs an amount", output.to_address));
+                    }
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn encode_fields(&self, encoder: &mut Encoder) {
+        encoder.put_str(self.tag());
+        match self {
+            TransactionKind::ActionReward { action_type, action_proof } => {
+                encoder.put_str(action_type);
+                encoder.put_str(action_proof);
+            }
+            TransactionKind::ContractCall { method, params } => {
+                encoder.put_str(method);
+                encoder.put_str(params);
+            }
+            TransactionKind::ContractDeploy { code } => encoder.put_str(code),
+            TransactionKind::NftMint { token_id, metadata_uri } => {
+                encoder.put_str(token_id);
+                encoder.put_str(metadata_uri);
+            }
+            TransactionKind::NftTransfer { token_id } => encoder.put_str(token_id),
+            TransactionKind::RegisterMultisig(policy) => policy.encode_fields(encoder),
+            TransactionKind::BatchTransfer { outputs } => {
+                encoder.put_u32(outputs.len() as u32);
+                for output in outputs {
+                    encoder.put_str(&output.to_address);
+                    encoder.put_u64(output.amount);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn decode_fields(decoder: &mut Decoder) -> Result<TransactionKind, DecodeError> {
+        let tag = decoder.get_string()?;
+        Ok(match tag.as_str() {
+            "transfer" => TransactionKind::Transfer,
+            "genesis" => TransactionKind::Genesis,
+            "genesis_stake" => TransactionKind::GenesisStake,
+            "mining_reward" => TransactionKind::MiningReward,
+            "action_reward" => TransactionKind::ActionReward {
+                action_type: decoder.get_string()?,
+                action_proof: decoder.get_string()?,
+            },
+            "stake" => TransactionKind::Stake,
+            "unstake" => TransactionKind::Unstake,
+            "contract_call" => TransactionKind::ContractCall {
+                method: decoder.get_string()?,
+                params: decoder.get_string()?,
+            },
+            "contract_deploy" => TransactionKind::ContractDeploy {
+                code: decoder.get_string()?,
+            },
+            "nft_mint" => TransactionKind::NftMint {
+                token_id: decoder.get_string()?,
+                metadata_uri: decoder.get_string()?,
+            },
+            "nft_transfer" => TransactionKind::NftTransfer {
+                token_id: decoder.get_string()?,
+            },
+            "register_multisig" => TransactionKind::RegisterMultisig(MultisigPolicy::decode_fields(decoder)?),
+            "batch_transfer" => {
+                let mut outputs = Vec::new();
+                for _ in 0..decoder.get_u32()? {
+                    outputs.push(TransferOutput {
+                        to_address: decoder.get_string()?,
+                        amount: decoder.get_u64()?,
+                    });
+                }
+                TransactionKind::BatchTransfer { outputs }
+            }
+            _ => return Err(DecodeError::UnknownTransactionKind(tag)),
+        })
+    }
+}
+
+// Address a deployment creates, fixed by the deployer and its nonce
+pub fn contract_address(deployer: &str, nonce: u64) -> String {
+    let mut preimage = b"contract".to_vec();
+    preimage.extend_from_slice(&nonce.to_be_bytes());
+    preimage.extend_from_slice(deployer.as_bytes());
+    address_from_bytes(&preimage)
+}
+
+const NFT_KEY_PREFIX: &str = "nft:";
+
+// Key an NFT is held under in AccountState::token_balances
+pub fn nft_key(token_id: &str) -> String {
+    format!("{}{}", NFT_KEY_PREFIX, token_id)
+}
+
+// Token id held under a token_balances key, if the key is an NFT
+pub fn nft_token_id(key: &str) -> Option<&str> {
+    key.strip_prefix(NFT_KEY_PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn action_rewards_keep_the_action_mining_tag() {
+        let kind = TransactionKind::ActionReward {
+            action_type: "recycling".to_string(),
+            action_proof: "{}".to_string(),
+        };
+        let value = serde_json::to_value(&kind).unwrap();
+        assert_eq!(value, json!({"transaction_type": "action_mining_recycling", "action_proof": "{}"}));
+        assert_eq!(serde_json::from_value::<TransactionKind>(value).unwrap(), kind);
+    }
+
+    #[test]
+    fn other_kinds_use_their_snake_case_tag() {
+        let kind = TransactionKind::NftTransfer { token_id: "art-1".to_string() };
+        let value = serde_json::to_value(&kind).unwrap();
+        assert_eq!(value, json!({"transaction_type": "nft_transfer", "token_id": "art-1"}));
+        assert_eq!(serde_json::from_value::<TransactionKind>(value).unwrap(), kind);
+
+        let value = json!({"transaction_type": "mining_reward"});
+        assert_eq!(serde_json::from_value::<TransactionKind>(value).unwrap(), TransactionKind::MiningReward);
+        assert!(serde_json::from_value::<TransactionKind>(json!({"transaction_type": "teleport"})).is_err());
+    }
+}
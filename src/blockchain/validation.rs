@@ -0,0 +1,551 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use crate::consensus::ProofOfAction;
+use crate::blockchain::{
+    block_time, is_action_mint, median_time_past, AccountStateIndex, BitnunBlockchain, Block, Transaction, TransactionKind, MAX_BLOCK_BYTES,
+    MAX_BLOCK_TRANSACTIONS, MAX_FUTURE_DRIFT_MS,
+};
+use crate::runtime;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationRule {
+    BlockIndex,
+    BlockHash,
+    PreviousHash,
+    MerkleRoot,
+    StateRoot,
+    BlockSize,
+    Emission,
+    Difficulty,
+    ProofOfWork,
+    Timestamp,
+    MiningReward,
+    ActionProof,
+    UnexpectedGenesis,
+    DuplicateTransaction,
+    MalformedTransaction,
+    WrongChain,
+    Signature,
+    Nonce,
+    InsufficientBalance,
+    TransactionKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChainViolation {
+    pub block_index: u64,
+    pub transaction_id: Option<String>,
+    pub rule: ValidationRule,
+    pub detail: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub valid: bool,
+    pub blocks_checked: u64,
+    pub violation: Option<ChainViolation>,
+}
+
+impl ChainViolation {
+    fn block(block: &Block, rule: ValidationRule, detail: String) -> Self {
+        ChainViolation {
+            block_index: block.index,
+            transaction_id: None,
+            rule,
+            detail,
+        }
+    }
+
+    fn transaction(block: &Block, transaction: &Transaction, rule: ValidationRule, detail: String) -> Self {
+        ChainViolation {
+            block_index: block.index,
+            transaction_id: Some(transaction.id.clone()),
+            rule,
+            detail,
+        }
+    }
+}
+
+impl BitnunBlockchain {
+    // Replays every block from genesis, re-checking consensus rules and
+    // rebuilding account state, and stops at the first violation found
+    pub fn replay_chain(&self) -> Result<AccountStateIndex, ChainViolation> {
+        self.replay_blocks(&self.blocks)
+    }
+
+    // Same checks for an arbitrary candidate chain, e.g. a competing branch
+    pub fn replay_blocks(&self, blocks: &[Block]) -> Result<AccountStateIndex, ChainViolation> {
+        let pruned = self.pruned_height as usize;
+        let mut state = AccountStateIndex::new();
+        let mut seen_transactions: HashSet<String> = HashSet::new();
+
+        for (position, block) in blocks.iter().enumerate() {
+            // Bodies held only in the store are read back one at a time
+            let stored = if position < pruned {
+                self.stored_block(&block.hash).ok().flatten()
+            } else {
+                None
+            };
+            let block = stored.as_ref().unwrap_or(block);
+            self.check_block_header(&blocks[..position], block)?;
+
+            // Snapshot blocks have no bodies; replay resumes from the snapshot state
+            if block.is_header_only() {
+                if position + 1 == pruned {
+                    state = self.base_state.clone();
+                }
+                continue;
+            }
+            self.check_block_transactions(&mut state, &mut seen_transactions, &blocks[..position], block)?;
+        }
+
+        Ok(state)
+    }
+
+    // Applies the block's transactions to `state` as they pass
+    pub(crate) fn check_block_transactions(
+        &self,
+        state: &mut AccountStateIndex,
+        seen_transactions: &mut HashSet<String>,
+        previous_blocks: &[Block],
+        block: &Block,
+    ) -> Result<(), ChainViolation> {
+        // Proofs of action must be made inside the window the block's own timestamp had to fall in
+        let proof_window = (median_time_past(previous_blocks), block_time(block) as i64 + MAX_FUTURE_DRIFT_MS);
+        let supply = state.circulating_supply();
+        state.enter_epoch(self.emission.epoch_start(block.index));
+        let epoch_minted = state.epoch_action_minted;
+        for transaction in &block.transactions {
+            if !seen_transactions.insert(transaction.id.clone()) {
+                return Err(ChainViolation::transaction(block, transaction, ValidationRule::DuplicateTransaction,
+                    "transaction id already included".to_string()));
+            }
+            self.check_transaction(state, proof_window, block, transaction)?;
+            state.apply_transaction(transaction, block);
+        }
+        state.height = block.index + 1;
+
+        if block.index > 0 {
+            self.check_emission(supply, epoch_minted, block)?;
+        }
+
+        let state_root = state.state_root();
+        if block.state_root != state_root {
+            return Err(ChainViolation::block(block, ValidationRule::StateRoot,
+                format!("state root {} but transactions produce {}", block.state_root, state_root)));
+        }
+        Ok(())
+    }
+
+    // Rejects transactions the active chain already includes below `height`
+    pub(crate) fn check_not_included(&self, block: &Block, height: u64) -> Result<(), ChainViolation> {
+        for transaction in &block.transactions {
+            if let Some(location) = self.index.locate(&transaction.id).filter(|location| location.height < height) {
+                return Err(ChainViolation::transaction(block, transaction, ValidationRule::DuplicateTransaction,
+                    format!("transaction already included at height {}", location.height)));
+            }
+        }
+        Ok(())
+    }
+
+    // Checks the block against the chain it would extend
+    pub(crate) fn check_block_header(&self, previous_blocks: &[Block], block: &Block) -> Result<(), ChainViolation> {
+        let position = previous_blocks.len();
+        if block.index != position as u64 {
+            return Err(ChainViolation::block(block, ValidationRule::BlockIndex,
+                format!("expected index {}", position)));
+        }
+
+        if block.hash != block.calculate_hash() {
+            return Err(ChainViolation::block(block, ValidationRule::BlockHash,
+                "stored hash does not match header".to_string()));
+        }
+
+        if position == 0 {
+            return self.check_block_body(position, block);
+        }
+
+        let previous = &previous_blocks[position - 1];
+        if block.previous_hash != previous.hash {
+            return Err(ChainViolation::block(block, ValidationRule::PreviousHash,
+                format!("expected previous hash {}", previous.hash)));
+        }
+
+        let median = median_time_past(previous_blocks);
+        let max_time = runtime::now_millis() as i64 + MAX_FUTURE_DRIFT_MS;
+        match block.timestamp_millis() {
+            Some(time) if time > median && time <= max_time => {}
+            Some(time) if time <= median => return Err(ChainViolation::block(block, ValidationRule::Timestamp,
+                format!("timestamp {} is not after the median {} of recent blocks", time, median))),
+            Some(time) => return Err(ChainViolation::block(block, ValidationRule::Timestamp,
+                format!("timestamp {} is more than {} ms ahead of the local clock", time, MAX_FUTURE_DRIFT_MS))),
+            None => return Err(ChainViolation::block(block, ValidationRule::Timestamp,
+                format!("unreadable timestamp {}", block.timestamp))),
+        }
+
+        let expected_bits = self.difficulty_config.next_bits(previous_blocks);
+        if block.bits != expected_bits {
+            return Err(ChainViolation::block(block, ValidationRule::Difficulty,
+                format!("target {:#010x} but expected {:#010x}", block.bits, expected_bits)));
+        }
+
+        if !block.meets_target() {
+            return Err(ChainViolation::block(block, ValidationRule::ProofOfWork,
+                format!("hash does not meet target {:#010x}", block.bits)));
+        }
+
+        self.check_block_body(position, block)
+    }
+
+    fn check_block_body(&self, position: usize, block: &Block) -> Result<(), ChainViolation> {
+        if position < self.pruned_height as usize && block.is_header_only() {
+            return Ok(());
+        }
+
+        if !block.has_valid_merkle_root() {
+            return Err(ChainViolation::block(block, ValidationRule::MerkleRoot,
+                "merkle root does not match transactions".to_string()));
+        }
+
+        if position == 0 {
+            return Ok(());
+        }
+
+        if block.transactions.len() > MAX_BLOCK_TRANSACTIONS {
+            return Err(ChainViolation::block(block, ValidationRule::BlockSize,
+                format!("{} transactions, limit {}", block.transactions.len(), MAX_BLOCK_TRANSACTIONS)));
+        }
+        let body_bytes: usize = block.transactions.iter().map(|tx| tx.to_bytes().len()).sum();
+        if body_bytes > MAX_BLOCK_BYTES {
+            return Err(ChainViolation::block(block, ValidationRule::BlockSize,
+                format!("{} bytes of transactions, limit {}", body_bytes, MAX_BLOCK_BYTES)));
+        }
+
+        let rewards: Vec<&Transaction> = block.transactions
+            .iter()
+            .filter(|tx| tx.kind == TransactionKind::MiningReward)
+            .collect();
+        if rewards.len() != 1 {
+            return Err(ChainViolation::block(block, ValidationRule::MiningReward,
+                format!("expected exactly one mining reward, found {}", rewards.len())));
+        }
+
+        Ok(())
+    }
+
+    // Everything a block after genesis mints, against `supply` and the epoch's
+    // `epoch_minted` before it: the reward pays the capped subsidy plus fees,
+    // and action mining stays within the epoch budget and the cap
+    fn check_emission(&self, supply: u64, epoch_minted: u64, block: &Block) -> Result<(), ChainViolation> {
+        let subsidy = self.block_subsidy(block.index, supply);
+        let mut action_minted = 0u64;
+
+        for transaction in block.transactions.iter().filter(|tx| tx.is_system()) {
+            if is_action_mint(transaction) {
+                action_minted = action_minted.saturating_add(transaction.amount);
+            } else if transaction.kind == TransactionKind::MiningReward {
+                // The producer collects the subsidy plus every fee in the block
+                let expected = block_fees(block).and_then(|fees| fees.checked_add(subsidy));
+                if Some(transaction.amount) != expected {
+                    return Err(ChainViolation::transaction(block, transaction, ValidationRule::MiningReward,
+                        format!("reward {} differs from subsidy {} plus fees", transaction.amount, subsidy)));
+                }
+            } else {
+                return Err(ChainViolation::transaction(block, transaction, ValidationRule::Emission,
+                    format!("{} cannot mint", transaction.kind.tag())));
+            }
+        }
+
+        let budget = self.emission.action_budget(block.index);
+        let epoch_minted = epoch_minted.saturating_add(action_minted);
+        if epoch_minted > budget {
+            return Err(ChainViolation::block(block, ValidationRule::Emission,
+                format!("action mining of {} exceeds the epoch budget of {}", epoch_minted, budget)));
+        }
+
+        let minted = subsidy.saturating_add(action_minted);
+        if supply.saturating_add(minted) > self.emission.max_supply {
+            return Err(ChainViolation::block(block, ValidationRule::Emission,
+                format!("minting {} on a supply of {} exceeds the cap of {}", minted, supply, self.emission.max_supply)));
+        }
+
+        Ok(())
+    }
+
+    fn check_transaction(
+        &self,
+        state: &AccountStateIndex,
+        proof_window: (i64, i64),
+        block: &Block,
+        transaction: &Transaction,
+    ) -> Result<(), ChainViolation> {
+        let violation = |rule, detail: String| Err(ChainViolation::transaction(block, transaction, rule, detail));
+
+        if let Err(detail) = transaction.check_fields() {
+            return violation(ValidationRule::MalformedTransaction, detail);
+        }
+
+        if transaction.is_system() {
+            if transaction.kind.is_genesis() && block.index != 0 {
+                return violation(ValidationRule::UnexpectedGenesis, "genesis allocation outside block 0".to_string());
+            }
+            if is_action_mint(transaction) {
+                let action_id = match self.check_action_proof(transaction, proof_window) {
+                    Ok(proof) => proof.action_id(),
+                    Err(detail) => return violation(ValidationRule::ActionProof, detail),
+                };
+                if state.is_action_spent(&action_id) {
+                    return violation(ValidationRule::ActionProof, format!("action {} was already minted", action_id));
+                }
+            }
+            return Ok(());
+        }
+
+        if transaction.chain_id != self.chain_id {
+            return violation(ValidationRule::WrongChain, format!("chain id {}", transaction.chain_id));
+        }
+
+        if !transaction.verify_authorization(state.multisig_of(&transaction.from_address)) {
+            return violation(ValidationRule::Signature, "missing or invalid signature".to_string());
+        }
+
+        let expected_nonce = state.nonce_of(&transaction.from_address);
+        if transaction.nonce != expected_nonce {
+            return violation(ValidationRule::Nonce,
+                format!("nonce {} but expected {}", transaction.nonce, expected_nonce));
+        }
+
+        let balance = state.spendable_of(&transaction.from_address, block.index, block_time(block));
+        match transaction.total_debit() {
+            Some(debit) if debit <= balance => {}
+            _ => return violation(ValidationRule::InsufficientBalance,
+                format!("spends {} plus fee {} with spendable balance {}", transaction.amount, transaction.fee, balance)),
+        }
+
+        check_kind_state(state, transaction).or_else(|detail| violation(ValidationRule::TransactionKind, detail))
+    }
+
+    // An action mint must carry a valid ProofOfAction for its recipient and
+    // action type that meets the chain's action target, was made inside
+    // `(after, until]` in milliseconds, and mints the action type's reward
+    pub(crate) fn check_action_proof(
+        &self,
+        transaction: &Transaction,
+        (after, until): (i64, i64),
+    ) -> Result<ProofOfAction, String> {
+        let proof = transaction
+            .proof_of_action()
+            .ok_or_else(|| "action_proof is not a serialized proof of action".to_string())?;
+
+        if !proof.proves_work(self.difficulty_config.action_bits) {
+            return Err(format!("proof of action does not meet target {:#010x}", self.difficulty_config.action_bits));
+        }
+        // The timestamp feeds the action id, so a proof may not pick one
+        // outside the window blocks are held to
+        if !(proof.timestamp > after as f64 && proof.timestamp <= until as f64) {
+            return Err(format!("proof made at {} outside ({}, {}]", proof.timestamp, after, until));
+        }
+        if proof.user_id != transaction.to_address {
+            return Err(format!("proof is for {}, not the recipient", proof.user_id));
+        }
+        match &transaction.kind {
+            TransactionKind::ActionReward { action_type, .. } if *action_type == proof.action_type => {}
+            _ => return Err(format!("proof is for a {} action", proof.action_type)),
+        }
+        if transaction.amount != proof.calculate_reward() {
+            return Err(format!("amount {} but the proof earns {}", transaction.amount, proof.calculate_reward()));
+        }
+
+        Ok(proof)
+    }
+}
+
+// None if the fees overflow
+pub fn block_fees(block: &Block) -> Option<u64> {
+    block.transactions
+        .iter()
+        .filter(|tx| !tx.is_system())
+        .try_fold(0u64, |total, tx| total.checked_add(tx.fee))
+}
+
+// Rules a kind places on the sender's state: what is unstaked must be staked,
+// and an NFT is minted once and moved only by its owner
+pub fn check_kind_state(state: &AccountStateIndex, transaction: &Transaction) -> Result<(), String> {
+    match &transaction.kind {
+        TransactionKind::Unstake if transaction.amount > state.stake_of(&transaction.from_address) => {
+            return Err(format!("unstakes {} with a stake of {}",
+                transaction.amount, state.stake_of(&transaction.from_address)));
+        }
+        TransactionKind::NftMint { token_id, .. } => {
+            if let Some(owner) = state.nft_owner(token_id) {
+                return Err(format!("token {} is already held by {}", token_id, owner));
+            }
+        }
+        TransactionKind::NftTransfer { token_id } if state.nft_owner(token_id) != Some(transaction.from_address.as_str()) => {
+            return Err(format!("sender does not hold token {}", token_id));
+        }
+        TransactionKind::RegisterMultisig(_) if state.multisig_of(&transaction.to_address).is_some() => {
+            return Err(format!("multisig {} is already registered", transaction.to_address));
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::{GenesisConfig, KeyPair, EASIEST_BITS};
+    use crate::blockchain::test_support::{dev_keypair, signed, DEV_CHAIN_ID};
+    use crate::error::{BitnunError, ChainError};
+
+    fn retimed(chain: &BitnunBlockchain, millis: i64) -> Block {
+        let mut block = chain.blocks[chain.blocks.len() - 1].clone();
+        block.set_timestamp_millis(millis);
+        block.mine_block(block.bits);
+        block
+    }
+
+    fn action_mint(proof: &ProofOfAction) -> Transaction {
+        Transaction::new_action_mining(&proof.user_id, &proof.action_type, proof.calculate_reward(), &proof.to_json())
+    }
+
+    // The next block with `transaction` in it, built without pool admission
+    // the way a dishonest miner would
+    fn forced_block(chain: &BitnunBlockchain, transaction: Transaction) -> Block {
+        let height = chain.blocks.len() as u64;
+        let subsidy = chain.block_subsidy(height, chain.state.circulating_supply());
+        let reward = Transaction::new_mining_reward("btnminer", subsidy);
+        let previous_hash = chain.blocks[chain.blocks.len() - 1].hash.clone();
+        let mut block = Block::new(height, previous_hash, vec![transaction, reward]);
+
+        let mut state = chain.state.clone();
+        state.apply_block(&block, chain.emission.epoch_start(height));
+        block.state_root = state.state_root();
+        block.mine_block(chain.difficulty_config.next_bits(&chain.blocks));
+        block
+    }
+
+    fn replay_with(chain: &BitnunBlockchain, transaction: Transaction) -> Result<AccountStateIndex, ChainViolation> {
+        let mut blocks = chain.blocks.clone();
+        blocks.push(forced_block(chain, transaction));
+        chain.replay_blocks(&blocks)
+    }
+
+    fn assert_rejected(chain: &mut BitnunBlockchain, proof: &ProofOfAction) {
+        match chain.add_transaction(&action_mint(proof)) {
+            Err(BitnunError::Chain(ChainError::InvalidActionProof(_))) => {}
+            other => panic!("expected an invalid action proof, got {:?}", other),
+        }
+        assert_eq!(replay_with(chain, action_mint(proof)).unwrap_err().rule, ValidationRule::ActionProof);
+    }
+
+    #[test]
+    fn action_proofs_must_meet_the_chain_target() {
+        let mut config = GenesisConfig::dev();
+        config.difficulty.action_bits = 0x1e00ffff;
+        let mut chain = BitnunBlockchain::from_genesis_config(&config).unwrap();
+
+        // Valid against the target it names, but that target is the submitter's choice
+        let proof = ProofOfAction::new("btnactor", "form_submit", runtime::now_millis(), EASIEST_BITS);
+        assert!(proof.validate());
+        assert!(!proof.proves_work(config.difficulty.action_bits));
+        assert_rejected(&mut chain, &proof);
+    }
+
+    #[test]
+    fn action_proofs_mint_the_same_whatever_score_they_claim() {
+        let mut proof = ProofOfAction::new("btnactor", "form_submit", runtime::now_millis(), EASIEST_BITS);
+        let reward = proof.calculate_reward();
+        proof.set_authenticity_score(0.0);
+        assert_eq!(proof.calculate_reward(), reward);
+        proof.set_authenticity_score(1.0);
+        proof.bits = 0x1d00ffff;
+        assert_eq!(proof.calculate_reward(), reward);
+    }
+
+    #[test]
+    fn restamped_action_proofs_outside_the_block_window_are_rejected() {
+        let mut chain = BitnunBlockchain::from_preset("dev").unwrap();
+        let minted = ProofOfAction::new("btnactor", "form_submit", runtime::now_millis(), EASIEST_BITS);
+        chain.add_transaction(&action_mint(&minted)).unwrap();
+        chain.mine_pending_transactions("btnminer");
+
+        // Each new timestamp is a new action id, so the window bounds how
+        // far a replay of the same action can move it
+        let ahead = runtime::now_millis() + (MAX_FUTURE_DRIFT_MS + 60_000) as f64;
+        assert_rejected(&mut chain, &ProofOfAction::new("btnactor", "form_submit", ahead, EASIEST_BITS));
+        let stale = median_time_past(&chain.blocks) as f64;
+        assert_rejected(&mut chain, &ProofOfAction::new("btnactor", "form_submit", stale, EASIEST_BITS));
+
+        let fresh = ProofOfAction::new("btnactor", "form_submit", runtime::now_millis() + 1.0, EASIEST_BITS);
+        assert!(replay_with(&chain, action_mint(&fresh)).is_ok());
+        chain.add_transaction(&action_mint(&fresh)).unwrap();
+    }
+
+    fn transfer(from: &KeyPair, nonce: u64) -> Transaction {
+        let mut transfer = Transaction::new(&from.address(), "btnpayee", 100);
+        transfer.set_nonce(nonce);
+        signed(transfer, from)
+    }
+
+    #[test]
+    fn nonces_must_continue_the_sender_sequence() {
+        let dev = dev_keypair();
+        let mut chain = BitnunBlockchain::from_preset("dev").unwrap();
+
+        assert_eq!(chain.add_transaction(&transfer(&dev, 1)).err(),
+            Some(BitnunError::Chain(ChainError::InvalidNonce { expected: 0, found: 1 })));
+        chain.add_transaction(&transfer(&dev, 0)).unwrap();
+        // Pending transactions already hold their nonces
+        assert_eq!(chain.add_transaction(&transfer(&dev, 0)).err(),
+            Some(BitnunError::Chain(ChainError::InvalidNonce { expected: 1, found: 0 })));
+        chain.add_transaction(&transfer(&dev, 1)).unwrap();
+        chain.mine_pending_transactions("btnminer");
+        assert_eq!(chain.state.nonce_of(&dev.address()), 2);
+
+        for nonce in [1, 3] {
+            assert_eq!(replay_with(&chain, transfer(&dev, nonce)).unwrap_err().rule, ValidationRule::Nonce);
+        }
+        assert!(replay_with(&chain, transfer(&dev, 2)).is_ok());
+    }
+
+    #[test]
+    fn transactions_signed_for_another_chain_are_rejected() {
+        let dev = dev_keypair();
+        let mut chain = BitnunBlockchain::from_preset("dev").unwrap();
+
+        let mut foreign = Transaction::new(&dev.address(), "btnpayee", 100);
+        foreign.set_chain_id("bitnun-main");
+        foreign.sign(&dev).unwrap();
+        assert!(foreign.verify());
+
+        assert_eq!(chain.add_transaction(&foreign).err(), Some(BitnunError::Chain(ChainError::WrongChain {
+            expected: DEV_CHAIN_ID.to_string(),
+            found: "bitnun-main".to_string(),
+        })));
+        assert_eq!(replay_with(&chain, foreign).unwrap_err().rule, ValidationRule::WrongChain);
+    }
+
+    #[test]
+    fn block_timestamps_must_pass_median_and_stay_near_the_clock() {
+        let mut chain = BitnunBlockchain::from_preset("dev").unwrap();
+        for _ in 0..3 {
+            chain.mine_pending_transactions("btnminer");
+        }
+        let previous = &chain.blocks[..chain.blocks.len() - 1];
+        let median = median_time_past(previous);
+
+        let stale = retimed(&chain, median);
+        let violation = chain.check_block_header(previous, &stale).unwrap_err();
+        assert_eq!(violation.rule, ValidationRule::Timestamp);
+
+        let future = retimed(&chain, runtime::now_millis() as i64 + MAX_FUTURE_DRIFT_MS + 60_000);
+        let violation = chain.check_block_header(previous, &future).unwrap_err();
+        assert_eq!(violation.rule, ValidationRule::Timestamp);
+
+        let on_time = retimed(&chain, median + 1);
+        assert!(chain.check_block_header(previous, &on_time).is_ok());
+    }
+}
@@ -1,6 +1,7 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
-use sha2::{Sha256, Digest};
+use crate::blockchain::{meets_target, sha256_digest, work_from_compact};
 use crate::console_log;
 
 pub mod proof_of_action;
@@ -9,33 +10,34 @@ pub mod validator;
 pub use proof_of_action::*;
 pub use validator::*;
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen(getter_with_clone))]
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ProofOfAction {
     pub user_id: String,
     pub action_type: String,
     pub timestamp: f64,
     pub proof_hash: String,
-    pub difficulty: u32,
+    pub bits: u32, // Compact proof-of-work target
     pub nonce: u64,
     pub authenticity_score: f64, // AI-calculated authenticity (0-1)
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl ProofOfAction {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new(
         user_id: &str,
         action_type: &str,
         timestamp: f64,
-        difficulty: u32,
+        bits: u32,
     ) -> ProofOfAction {
         let mut proof = ProofOfAction {
             user_id: user_id.to_string(),
             action_type: action_type.to_string(),
-            timestamp,
+            // Whole milliseconds, so the hashed text survives the JSON round trip
+            timestamp: timestamp.trunc(),
             proof_hash: String::new(),
-            difficulty,
+            bits,
             nonce: 0,
             authenticity_score: 1.0,
         };
@@ -44,69 +46,91 @@ impl ProofOfAction {
         proof
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn generate_proof(&mut self) {
-        let target = "0".repeat(self.difficulty as usize);
         let base_data = format!("{}{}{}",
             self.user_id,
             self.action_type,
             self.timestamp
         );
 
-        while !self.proof_hash.starts_with(&target) {
+        loop {
             self.nonce += 1;
             let data = format!("{}{}", base_data, self.nonce);
-            
-            let mut hasher = Sha256::new();
-            hasher.update(data.as_bytes());
-            self.proof_hash = format!("{:x}", hasher.finalize());
+            let digest = sha256_digest(data.as_bytes());
+
+            if meets_target(&digest, self.bits) {
+                self.proof_hash = hex::encode(digest);
+                break;
+            }
         }
 
         console_log!("Proof-of-Action generated for {} action with nonce: {}", 
                     self.action_type, self.nonce);
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn validate(&self) -> bool {
-        let target = "0".repeat(self.difficulty as usize);
-        let data = format!("{}{}{}{}",
-            self.user_id,
-            self.action_type,
-            self.timestamp,
-            self.nonce
-        );
+        self.proves_work(self.bits) &&
+        self.authenticity_score >= 0.7 // Minimum authenticity threshold
+    }
 
-        let mut hasher = Sha256::new();
-        hasher.update(data.as_bytes());
-        let calculated_hash = format!("{:x}", hasher.finalize());
+    // The form action-mining transactions carry in action_proof
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("proof of action serializes")
+    }
 
-        calculated_hash == self.proof_hash && 
-        calculated_hash.starts_with(&target) &&
-        self.authenticity_score >= 0.7 // Minimum authenticity threshold
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn work(&self) -> u64 {
+        work_from_compact(self.bits).min(u64::MAX as u128) as u64
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn set_authenticity_score(&mut self, score: f64) {
         self.authenticity_score = score.clamp(0.0, 1.0);
     }
 
-    #[wasm_bindgen]
+    // Fixed per action type: the submitter picks both the score and the
+    // target inside the proof, so neither may change what it mints
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn calculate_reward(&self) -> u64 {
-        let base_reward = match self.action_type.as_str() {
+        match self.action_type.as_str() {
             "click" => 5,
             "scroll" => 2,
             "share" => 25,
             "form_submit" => 50,
             "referral" => 100,
             "daily_login" => 20,
-            _ => 1,
-        };
+            _ => 1, // Minimum 1 cent reward
+        }
+    }
+}
 
-        // Adjust reward based on authenticity score and difficulty
-        let reward = (base_reward as f64 
-            * self.authenticity_score 
-            * (1.0 + (self.difficulty as f64 / 10.0))) as u64;
+impl ProofOfAction {
+    pub fn from_json(json: &str) -> Option<ProofOfAction> {
+        serde_json::from_str(json).ok()
+    }
 
-        reward.max(1) // Minimum 1 cent reward
+    // The recorded hash is this proof's and meets `bits`, whatever target
+    // the proof claims for itself
+    pub fn proves_work(&self, bits: u32) -> bool {
+        let data = format!("{}{}{}{}",
+            self.user_id,
+            self.action_type,
+            self.timestamp,
+            self.nonce
+        );
+
+        let digest = sha256_digest(data.as_bytes());
+        hex::encode(digest) == self.proof_hash && meets_target(&digest, bits)
     }
-}
\ No newline at end of file
+
+    // Identifies the action itself rather than the proof, so re-grinding the
+    // nonce for the same action yields the same id
+    pub fn action_id(&self) -> String {
+        let action = serde_json::to_vec(&(&self.user_id, &self.action_type, self.timestamp))
+            .expect("action fields serialize");
+        hex::encode(sha256_digest(&action))
+    }
+}
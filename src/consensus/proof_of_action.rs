@@ -1,8 +1,9 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
 use crate::console_log;
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ActionValidator {
     min_time_between_actions: f64, // milliseconds
@@ -10,9 +11,15 @@ pub struct ActionValidator {
     fraud_detection_enabled: bool,
 }
 
-#[wasm_bindgen]
+impl Default for ActionValidator {
+    fn default() -> Self {
+        ActionValidator::new()
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl ActionValidator {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new() -> ActionValidator {
         ActionValidator {
             min_time_between_actions: 100.0, // 100ms minimum between actions
@@ -21,7 +28,7 @@ impl ActionValidator {
         }
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn validate_action_timing(&self, 
         action_type: &str,
         current_timestamp: f64,
@@ -51,7 +58,7 @@ impl ActionValidator {
         }
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn calculate_authenticity_score(&self,
         action_type: &str,
         timing_pattern: &[f64],
@@ -108,9 +115,9 @@ impl ActionValidator {
         // Sweet spot is moderate variance
         let normalized_variance = (variance / (mean + 1.0)).clamp(0.0, 10.0);
         
-        if normalized_variance < 0.1 || normalized_variance > 5.0 {
+        if !(0.1..=5.0).contains(&normalized_variance) {
             0.3 // Likely bot behavior
-        } else if normalized_variance >= 0.3 && normalized_variance <= 2.0 {
+        } else if (0.3..=2.0).contains(&normalized_variance) {
             1.0 // Human-like behavior
         } else {
             0.7 // Suspicious but not definitely bot
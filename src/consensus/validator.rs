@@ -1,6 +1,7 @@
-use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use crate::blockchain::GenesisValidator;
+use crate::error::{BitnunResult, ConsensusError};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct NetworkValidator {
@@ -18,6 +19,12 @@ pub struct ValidatorInfo {
     pub accuracy_score: f64,
 }
 
+impl Default for NetworkValidator {
+    fn default() -> Self {
+        NetworkValidator::new()
+    }
+}
+
 impl NetworkValidator {
     pub fn new() -> Self {
         NetworkValidator {
@@ -27,9 +34,20 @@ impl NetworkValidator {
         }
     }
 
-    pub fn add_validator(&mut self, address: String, stake: u64) -> bool {
+    pub fn from_genesis(validators: &[GenesisValidator]) -> BitnunResult<Self> {
+        let mut network_validator = NetworkValidator::new();
+        for validator in validators {
+            network_validator.add_validator(validator.address.clone(), validator.stake)?;
+        }
+        Ok(network_validator)
+    }
+
+    pub fn add_validator(&mut self, address: String, stake: u64) -> BitnunResult<()> {
         if stake < self.min_stake {
-            return false;
+            return Err(ConsensusError::StakeBelowMinimum {
+                minimum: self.min_stake,
+                stake,
+            }.into());
         }
 
         let validator = ValidatorInfo {
@@ -42,7 +60,7 @@ impl NetworkValidator {
 
         self.validators.insert(address, validator);
         self.total_stake += stake;
-        true
+        Ok(())
     }
 
     pub fn select_validators(&self, count: usize) -> Vec<String> {
@@ -67,7 +85,7 @@ impl NetworkValidator {
     }
 
     pub fn validate_action_consensus(&mut self, 
-        action_id: &str,
+        _action_id: &str,
         validations: Vec<(String, bool)> // (validator_address, is_valid)
     ) -> bool {
         let mut valid_count = 0u64;
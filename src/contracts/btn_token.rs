@@ -1,24 +1,28 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use crate::console_log;
+use crate::error::{BitnunResult, TokenError};
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen(getter_with_clone))]
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BTNToken {
     pub name: String,
     pub symbol: String,
     pub decimals: u8,
     pub total_supply: u64,
+    #[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
     pub balances: HashMap<String, u64>,
+    #[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
     pub allowances: HashMap<String, HashMap<String, u64>>,
     pub owner: String,
     pub minting_enabled: bool,
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl BTNToken {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new(owner: &str, initial_supply: u64) -> BTNToken {
         let mut balances = HashMap::new();
         balances.insert(owner.to_string(), initial_supply);
@@ -35,17 +39,19 @@ impl BTNToken {
         }
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn balance_of(&self, account: &str) -> u64 {
         *self.balances.get(account).unwrap_or(&0)
     }
 
-    #[wasm_bindgen]
-    pub fn transfer(&mut self, from: &str, to: &str, amount: u64) -> bool {
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn transfer(&mut self, from: &str, to: &str, amount: u64) -> BitnunResult<()> {
         let from_balance = self.balance_of(from);
         if from_balance < amount {
-            console_log!("Transfer failed: Insufficient balance");
-            return false;
+            return Err(TokenError::InsufficientBalance {
+                available: from_balance,
+                required: amount,
+            }.into());
         }
 
         let to_balance = self.balance_of(to);
@@ -54,25 +60,19 @@ impl BTNToken {
         self.balances.insert(to.to_string(), to_balance + amount);
 
         console_log!("Transferred {} BTN from {} to {}", amount, from, to);
-        true
+        Ok(())
     }
 
-    #[wasm_bindgen]
-    pub fn approve(&mut self, owner: &str, spender: &str, amount: u64) -> bool {
-        if !self.allowances.contains_key(owner) {
-            self.allowances.insert(owner.to_string(), HashMap::new());
-        }
-
-        if let Some(owner_allowances) = self.allowances.get_mut(owner) {
-            owner_allowances.insert(spender.to_string(), amount);
-            console_log!("Approved {} BTN from {} to {}", amount, owner, spender);
-            true
-        } else {
-            false
-        }
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn approve(&mut self, owner: &str, spender: &str, amount: u64) {
+        self.allowances
+            .entry(owner.to_string())
+            .or_default()
+            .insert(spender.to_string(), amount);
+        console_log!("Approved {} BTN from {} to {}", amount, owner, spender);
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn allowance(&self, owner: &str, spender: &str) -> u64 {
         self.allowances
             .get(owner)
@@ -81,81 +81,88 @@ impl BTNToken {
             .unwrap_or(0)
     }
 
-    #[wasm_bindgen]
-    pub fn transfer_from(&mut self, spender: &str, from: &str, to: &str, amount: u64) -> bool {
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn transfer_from(&mut self, spender: &str, from: &str, to: &str, amount: u64) -> BitnunResult<()> {
         let allowance = self.allowance(from, spender);
         if allowance < amount {
-            console_log!("Transfer from failed: Insufficient allowance");
-            return false;
+            return Err(TokenError::InsufficientAllowance {
+                available: allowance,
+                required: amount,
+            }.into());
         }
 
-        if !self.transfer(from, to, amount) {
-            return false;
-        }
+        self.transfer(from, to, amount)?;
 
         // Decrease allowance
         if let Some(owner_allowances) = self.allowances.get_mut(from) {
             owner_allowances.insert(spender.to_string(), allowance - amount);
         }
 
-        true
+        Ok(())
     }
 
-    #[wasm_bindgen]
-    pub fn mint(&mut self, to: &str, amount: u64) -> bool {
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn mint(&mut self, to: &str, amount: u64) -> BitnunResult<()> {
         if !self.minting_enabled {
-            console_log!("Minting disabled");
-            return false;
+            return Err(TokenError::MintingDisabled.into());
         }
 
+        let total_supply = self.total_supply.checked_add(amount).ok_or(TokenError::SupplyOverflow)?;
         let to_balance = self.balance_of(to);
         self.balances.insert(to.to_string(), to_balance + amount);
-        self.total_supply += amount;
+        self.total_supply = total_supply;
 
         console_log!("Minted {} BTN to {}", amount, to);
-        true
+        Ok(())
     }
 
-    #[wasm_bindgen]
-    pub fn burn(&mut self, from: &str, amount: u64) -> bool {
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn burn(&mut self, from: &str, amount: u64) -> BitnunResult<()> {
         let from_balance = self.balance_of(from);
         if from_balance < amount {
-            console_log!("Burn failed: Insufficient balance");
-            return false;
+            return Err(TokenError::InsufficientBalance {
+                available: from_balance,
+                required: amount,
+            }.into());
         }
 
         self.balances.insert(from.to_string(), from_balance - amount);
         self.total_supply -= amount;
 
         console_log!("Burned {} BTN from {}", amount, from);
-        true
+        Ok(())
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn disable_minting(&mut self) {
         self.minting_enabled = false;
         console_log!("Minting disabled permanently");
     }
 
+    #[cfg(feature = "wasm")]
     #[wasm_bindgen]
     pub fn get_token_info(&self) -> JsValue {
-        let info = TokenInfo {
+        serde_wasm_bindgen::to_value(&self.token_info()).unwrap()
+    }
+}
+
+impl BTNToken {
+    pub fn token_info(&self) -> TokenInfo {
+        TokenInfo {
             name: self.name.clone(),
             symbol: self.symbol.clone(),
             decimals: self.decimals,
             total_supply: self.total_supply,
             minting_enabled: self.minting_enabled,
-        };
-
-        serde_wasm_bindgen::to_value(&info).unwrap()
+        }
     }
 }
 
-#[derive(Serialize, Deserialize)]
-struct TokenInfo {
-    name: String,
-    symbol: String,
-    decimals: u8,
-    total_supply: u64,
-    minting_enabled: bool,
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TokenInfo {
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u8,
+    pub total_supply: u64,
+    pub minting_enabled: bool,
 }
\ No newline at end of file
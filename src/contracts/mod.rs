@@ -1,8 +1,10 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
 use crate::console_log;
+use crate::error::{BitnunResult, ContractError};
 
 pub mod btn_token;
 pub mod nft_contract;
@@ -12,19 +14,20 @@ pub use btn_token::*;
 pub use nft_contract::*;
 pub use staking::*;
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen(getter_with_clone))]
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SmartContract {
     pub address: String,
     pub owner: String,
     pub code: String,
+    #[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
     pub storage: HashMap<String, String>,
     pub gas_limit: u64,
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl SmartContract {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new(owner: &str, code: &str) -> SmartContract {
         SmartContract {
             address: format!("0x{}", Uuid::new_v4().to_string().replace("-", "")),
@@ -35,46 +38,43 @@ impl SmartContract {
         }
     }
 
-    #[wasm_bindgen]
-    pub fn execute(&mut self, function: &str, params: &str) -> String {
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn execute(&mut self, function: &str, params: &str) -> BitnunResult<()> {
         console_log!("Executing contract function: {} with params: {}", function, params);
         
-        match function {
+        let result = match function {
             "transfer" => self.handle_transfer(params),
             "mint" => self.handle_mint(params),
             "approve" => self.handle_approve(params),
             "stake" => self.handle_stake(params),
-            _ => "Function not found".to_string(),
-        }
+            _ => Err(ContractError::FunctionNotFound(function.to_string())),
+        };
+
+        result.map_err(Into::into)
     }
 
-    fn handle_transfer(&mut self, params: &str) -> String {
+    fn handle_transfer(&mut self, params: &str) -> Result<(), ContractError> {
         // Parse transfer parameters: from,to,amount
         let parts: Vec<&str> = params.split(',').collect();
         if parts.len() != 3 {
-            return "Invalid parameters".to_string();
+            return Err(ContractError::InvalidParameters);
         }
 
         let from = parts[0];
         let to = parts[1];
-        let amount: u64 = parts[2].parse().unwrap_or(0);
+        let amount = Self::parse_amount(parts[2])?;
 
         // Get current balances
-        let from_balance: u64 = self.storage
-            .get(&format!("balance_{}", from))
-            .unwrap_or(&"0".to_string())
-            .parse()
-            .unwrap_or(0);
+        let from_balance = self.storage_u64(&format!("balance_{}", from));
 
         if from_balance < amount {
-            return "Insufficient balance".to_string();
+            return Err(ContractError::InsufficientBalance {
+                available: from_balance,
+                required: amount,
+            });
         }
 
-        let to_balance: u64 = self.storage
-            .get(&format!("balance_{}", to))
-            .unwrap_or(&"0".to_string())
-            .parse()
-            .unwrap_or(0);
+        let to_balance = self.storage_u64(&format!("balance_{}", to));
 
         // Update balances
         self.storage.insert(
@@ -87,23 +87,19 @@ impl SmartContract {
         );
 
         console_log!("Transfer: {} BTN from {} to {}", amount, from, to);
-        "Success".to_string()
+        Ok(())
     }
 
-    fn handle_mint(&mut self, params: &str) -> String {
+    fn handle_mint(&mut self, params: &str) -> Result<(), ContractError> {
         let parts: Vec<&str> = params.split(',').collect();
         if parts.len() != 2 {
-            return "Invalid parameters".to_string();
+            return Err(ContractError::InvalidParameters);
         }
 
         let to = parts[0];
-        let amount: u64 = parts[1].parse().unwrap_or(0);
+        let amount = Self::parse_amount(parts[1])?;
 
-        let current_balance: u64 = self.storage
-            .get(&format!("balance_{}", to))
-            .unwrap_or(&"0".to_string())
-            .parse()
-            .unwrap_or(0);
+        let current_balance = self.storage_u64(&format!("balance_{}", to));
 
         self.storage.insert(
             format!("balance_{}", to),
@@ -111,27 +107,23 @@ impl SmartContract {
         );
 
         // Update total supply
-        let total_supply: u64 = self.storage
-            .get("total_supply")
-            .unwrap_or(&"0".to_string())
-            .parse()
-            .unwrap_or(0);
+        let total_supply = self.storage_u64("total_supply");
 
-        self.storage.insert("total_supply", (total_supply + amount).to_string());
+        self.storage.insert("total_supply".to_string(), (total_supply + amount).to_string());
 
         console_log!("Minted {} BTN to {}", amount, to);
-        "Success".to_string()
+        Ok(())
     }
 
-    fn handle_approve(&mut self, params: &str) -> String {
+    fn handle_approve(&mut self, params: &str) -> Result<(), ContractError> {
         let parts: Vec<&str> = params.split(',').collect();
         if parts.len() != 3 {
-            return "Invalid parameters".to_string();
+            return Err(ContractError::InvalidParameters);
         }
 
         let owner = parts[0];
         let spender = parts[1];
-        let amount: u64 = parts[2].parse().unwrap_or(0);
+        let amount = Self::parse_amount(parts[2])?;
 
         self.storage.insert(
             format!("allowance_{}_{}", owner, spender),
@@ -139,23 +131,19 @@ impl SmartContract {
         );
 
         console_log!("Approved {} BTN from {} to {}", amount, owner, spender);
-        "Success".to_string()
+        Ok(())
     }
 
-    fn handle_stake(&mut self, params: &str) -> String {
+    fn handle_stake(&mut self, params: &str) -> Result<(), ContractError> {
         let parts: Vec<&str> = params.split(',').collect();
         if parts.len() != 2 {
-            return "Invalid parameters".to_string();
+            return Err(ContractError::InvalidParameters);
         }
 
         let user = parts[0];
-        let amount: u64 = parts[1].parse().unwrap_or(0);
+        let amount = Self::parse_amount(parts[1])?;
 
-        let current_stake: u64 = self.storage
-            .get(&format!("stake_{}", user))
-            .unwrap_or(&"0".to_string())
-            .parse()
-            .unwrap_or(0);
+        let current_stake = self.storage_u64(&format!("stake_{}", user));
 
         self.storage.insert(
             format!("stake_{}", user),
@@ -163,15 +151,26 @@ impl SmartContract {
         );
 
         console_log!("Staked {} BTN for {}", amount, user);
-        "Success".to_string()
+        Ok(())
+    }
+
+    fn parse_amount(value: &str) -> Result<u64, ContractError> {
+        value.trim().parse().map_err(|_| ContractError::InvalidParameters)
+    }
+
+    fn storage_u64(&self, key: &str) -> u64 {
+        self.storage
+            .get(key)
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0)
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn get_storage(&self, key: &str) -> String {
         self.storage.get(key).unwrap_or(&"".to_string()).clone()
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn set_storage(&mut self, key: &str, value: &str) {
         self.storage.insert(key.to_string(), value.to_string());
     }
@@ -1,18 +1,24 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use uuid::Uuid;
 use crate::console_log;
+use crate::error::{BitnunResult, NftError};
+use crate::runtime;
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen(getter_with_clone))]
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BitnunNFT {
     pub name: String,
     pub symbol: String,
     pub next_token_id: u64,
+    #[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
     pub tokens: HashMap<u64, NFTMetadata>,
+    #[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
     pub owners: HashMap<u64, String>,
+    #[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
     pub approved: HashMap<u64, String>,
+    #[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
     pub operator_approvals: HashMap<String, HashMap<String, bool>>,
     pub carbon_offset_per_nft: u64, // CO2 offset in grams
 }
@@ -29,9 +35,15 @@ pub struct NFTMetadata {
     pub creator: String,
 }
 
-#[wasm_bindgen]
+impl Default for BitnunNFT {
+    fn default() -> Self {
+        BitnunNFT::new()
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl BitnunNFT {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new() -> BitnunNFT {
         BitnunNFT {
             name: "Bitnun Sustainable NFT".to_string(),
@@ -45,7 +57,7 @@ impl BitnunNFT {
         }
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn mint(&mut self, to: &str, name: &str, description: &str, image: &str) -> u64 {
         let token_id = self.next_token_id;
         self.next_token_id += 1;
@@ -62,7 +74,7 @@ impl BitnunNFT {
             image: image.to_string(),
             attributes,
             carbon_offset: self.carbon_offset_per_nft,
-            minted_at: js_sys::Date::now(),
+            minted_at: runtime::now_millis(),
             creator: to.to_string(),
         };
 
@@ -74,33 +86,37 @@ impl BitnunNFT {
         token_id
     }
 
-    #[wasm_bindgen]
-    pub fn transfer(&mut self, from: &str, to: &str, token_id: u64) -> bool {
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn transfer(&mut self, from: &str, to: &str, token_id: u64) -> BitnunResult<()> {
+        if !self.owners.contains_key(&token_id) {
+            return Err(NftError::TokenNotFound(token_id).into());
+        }
+
         if !self.is_approved_or_owner(from, token_id) {
-            console_log!("Transfer failed: Not approved or owner");
-            return false;
+            return Err(NftError::NotApprovedOrOwner.into());
         }
 
         self.owners.insert(token_id, to.to_string());
         self.approved.remove(&token_id); // Clear approval
 
         console_log!("Transferred NFT #{} from {} to {}", token_id, from, to);
-        true
+        Ok(())
     }
 
-    #[wasm_bindgen]
-    pub fn approve(&mut self, owner: &str, approved: &str, token_id: u64) -> bool {
-        if self.owner_of(token_id) != Some(owner.to_string()) {
-            console_log!("Approve failed: Not the owner");
-            return false;
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn approve(&mut self, owner: &str, approved: &str, token_id: u64) -> BitnunResult<()> {
+        match self.owner_of(token_id) {
+            None => return Err(NftError::TokenNotFound(token_id).into()),
+            Some(token_owner) if token_owner != owner => return Err(NftError::NotOwner.into()),
+            Some(_) => {}
         }
 
         self.approved.insert(token_id, approved.to_string());
         console_log!("Approved {} for NFT #{}", approved, token_id);
-        true
+        Ok(())
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn set_approval_for_all(&mut self, owner: &str, operator: &str, approved: bool) {
         if !self.operator_approvals.contains_key(owner) {
             self.operator_approvals.insert(owner.to_string(), HashMap::new());
@@ -113,17 +129,17 @@ impl BitnunNFT {
         console_log!("Set approval for all: {} -> {} = {}", owner, operator, approved);
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn owner_of(&self, token_id: u64) -> Option<String> {
         self.owners.get(&token_id).cloned()
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn get_approved(&self, token_id: u64) -> Option<String> {
         self.approved.get(&token_id).cloned()
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn is_approved_for_all(&self, owner: &str, operator: &str) -> bool {
         self.operator_approvals
             .get(owner)
@@ -141,6 +157,7 @@ impl BitnunNFT {
         false
     }
 
+    #[cfg(feature = "wasm")]
     #[wasm_bindgen]
     pub fn token_metadata(&self, token_id: u64) -> JsValue {
         if let Some(metadata) = self.tokens.get(&token_id) {
@@ -150,12 +167,12 @@ impl BitnunNFT {
         }
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn total_carbon_offset(&self) -> u64 {
         self.tokens.values().map(|nft| nft.carbon_offset).sum()
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn tokens_by_owner(&self, owner: &str) -> Vec<u64> {
         self.owners
             .iter()
@@ -164,23 +181,28 @@ impl BitnunNFT {
             .collect()
     }
 
+    #[cfg(feature = "wasm")]
     #[wasm_bindgen]
     pub fn get_collection_stats(&self) -> JsValue {
-        let stats = CollectionStats {
+        serde_wasm_bindgen::to_value(&self.collection_stats()).unwrap()
+    }
+}
+
+impl BitnunNFT {
+    pub fn collection_stats(&self) -> CollectionStats {
+        CollectionStats {
             total_nfts: self.tokens.len(),
             total_carbon_offset: self.total_carbon_offset(),
             unique_owners: self.owners.values().collect::<std::collections::HashSet<_>>().len(),
             next_token_id: self.next_token_id,
-        };
-
-        serde_wasm_bindgen::to_value(&stats).unwrap()
+        }
     }
 }
 
-#[derive(Serialize, Deserialize)]
-struct CollectionStats {
-    total_nfts: usize,
-    total_carbon_offset: u64,
-    unique_owners: usize,
-    next_token_id: u64,
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CollectionStats {
+    pub total_nfts: usize,
+    pub total_carbon_offset: u64,
+    pub unique_owners: usize,
+    pub next_token_id: u64,
 }
\ No newline at end of file
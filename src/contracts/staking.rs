@@ -1,11 +1,15 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use crate::console_log;
+use crate::error::{BitnunResult, StakingError};
+use crate::runtime;
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct StakingContract {
+    #[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
     pub stakes: HashMap<String, StakeInfo>,
     pub total_staked: u64,
     pub reward_rate: f64, // Annual percentage yield
@@ -21,9 +25,15 @@ pub struct StakeInfo {
     pub total_rewards_claimed: u64,
 }
 
-#[wasm_bindgen]
+impl Default for StakingContract {
+    fn default() -> Self {
+        StakingContract::new()
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl StakingContract {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new() -> StakingContract {
         StakingContract {
             stakes: HashMap::new(),
@@ -34,14 +44,16 @@ impl StakingContract {
         }
     }
 
-    #[wasm_bindgen]
-    pub fn stake(&mut self, user: &str, amount: u64) -> bool {
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn stake(&mut self, user: &str, amount: u64) -> BitnunResult<()> {
         if amount < self.min_stake {
-            console_log!("Stake failed: Amount below minimum {}", self.min_stake);
-            return false;
+            return Err(StakingError::BelowMinimum {
+                minimum: self.min_stake,
+                amount,
+            }.into());
         }
 
-        let current_time = js_sys::Date::now();
+        let current_time = runtime::now_millis();
         
         if let Some(existing_stake) = self.stakes.get_mut(user) {
             // Add to existing stake
@@ -60,118 +72,123 @@ impl StakingContract {
         }
 
         self.total_staked += amount;
-        true
+        Ok(())
     }
 
-    #[wasm_bindgen]
-    pub fn unstake(&mut self, user: &str, amount: u64) -> bool {
-        if let Some(stake_info) = self.stakes.get_mut(user) {
-            let current_time = js_sys::Date::now();
-            
-            // Check lock period
-            if current_time - stake_info.staked_at < self.lock_period {
-                console_log!("Unstake failed: Still in lock period");
-                return false;
-            }
-
-            if stake_info.amount < amount {
-                console_log!("Unstake failed: Insufficient staked amount");
-                return false;
-            }
-
-            stake_info.amount -= amount;
-            self.total_staked -= amount;
-
-            // Remove stake if amount becomes 0
-            if stake_info.amount == 0 {
-                self.stakes.remove(user);
-                console_log!("Removed complete stake for {}", user);
-            }
-
-            console_log!("Unstaked {} BTN for {}", amount, user);
-            true
-        } else {
-            console_log!("Unstake failed: No stake found for {}", user);
-            false
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn unstake(&mut self, user: &str, amount: u64) -> BitnunResult<()> {
+        let stake_info = self.stakes.get_mut(user).ok_or(StakingError::NoStake)?;
+        let current_time = runtime::now_millis();
+
+        // Check lock period
+        if current_time - stake_info.staked_at < self.lock_period {
+            return Err(StakingError::StillLocked {
+                unlocks_at: stake_info.staked_at + self.lock_period,
+            }.into());
         }
+
+        if stake_info.amount < amount {
+            return Err(StakingError::InsufficientStake {
+                staked: stake_info.amount,
+                requested: amount,
+            }.into());
+        }
+
+        stake_info.amount -= amount;
+        self.total_staked -= amount;
+
+        // Remove stake if amount becomes 0
+        if stake_info.amount == 0 {
+            self.stakes.remove(user);
+            console_log!("Removed complete stake for {}", user);
+        }
+
+        console_log!("Unstaked {} BTN for {}", amount, user);
+        Ok(())
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn calculate_rewards(&self, user: &str) -> u64 {
         if let Some(stake_info) = self.stakes.get(user) {
-            let current_time = js_sys::Date::now();
+            let current_time = runtime::now_millis();
             let time_since_last_claim = current_time - stake_info.last_reward_claim;
             
             // Convert milliseconds to years for APY calculation
             let years = time_since_last_claim / (365.25 * 24.0 * 60.0 * 60.0 * 1000.0);
             
-            let rewards = (stake_info.amount as f64 * self.reward_rate * years) as u64;
-            rewards
+            (stake_info.amount as f64 * self.reward_rate * years) as u64
         } else {
             0
         }
     }
 
-    #[wasm_bindgen]
-    pub fn claim_rewards(&mut self, user: &str) -> u64 {
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn claim_rewards(&mut self, user: &str) -> BitnunResult<u64> {
         let rewards = self.calculate_rewards(user);
-        
+        let stake_info = self.stakes.get_mut(user).ok_or(StakingError::NoStake)?;
+
         if rewards > 0 {
-            if let Some(stake_info) = self.stakes.get_mut(user) {
-                stake_info.last_reward_claim = js_sys::Date::now();
-                stake_info.total_rewards_claimed += rewards;
-                
-                console_log!("Claimed {} BTN rewards for {}", rewards, user);
-                return rewards;
-            }
+            stake_info.last_reward_claim = runtime::now_millis();
+            stake_info.total_rewards_claimed += rewards;
+
+            console_log!("Claimed {} BTN rewards for {}", rewards, user);
+            return Ok(rewards);
         }
 
         console_log!("No rewards to claim for {}", user);
-        0
+        Ok(0)
     }
 
+    #[cfg(feature = "wasm")]
     #[wasm_bindgen]
     pub fn get_stake_info(&self, user: &str) -> JsValue {
-        if let Some(stake_info) = self.stakes.get(user) {
-            let info = UserStakeInfo {
-                amount: stake_info.amount,
-                staked_at: stake_info.staked_at,
-                pending_rewards: self.calculate_rewards(user),
-                total_rewards_claimed: stake_info.total_rewards_claimed,
-                can_unstake: js_sys::Date::now() - stake_info.staked_at >= self.lock_period,
-            };
-            serde_wasm_bindgen::to_value(&info).unwrap()
-        } else {
-            JsValue::NULL
+        match self.stake_info(user) {
+            Some(info) => serde_wasm_bindgen::to_value(&info).unwrap(),
+            None => JsValue::NULL,
         }
     }
 
+    #[cfg(feature = "wasm")]
     #[wasm_bindgen]
     pub fn get_pool_stats(&self) -> JsValue {
-        let stats = PoolStats {
+        serde_wasm_bindgen::to_value(&self.pool_stats()).unwrap()
+    }
+}
+
+impl StakingContract {
+    pub fn stake_info(&self, user: &str) -> Option<UserStakeInfo> {
+        self.stakes.get(user).map(|stake_info| UserStakeInfo {
+            amount: stake_info.amount,
+            staked_at: stake_info.staked_at,
+            pending_rewards: self.calculate_rewards(user),
+            total_rewards_claimed: stake_info.total_rewards_claimed,
+            can_unstake: runtime::now_millis() - stake_info.staked_at >= self.lock_period,
+        })
+    }
+
+    pub fn pool_stats(&self) -> PoolStats {
+        PoolStats {
             total_staked: self.total_staked,
             reward_rate: self.reward_rate,
             min_stake: self.min_stake,
             total_stakers: self.stakes.len(),
-        };
-
-        serde_wasm_bindgen::to_value(&stats).unwrap()
+        }
     }
 }
 
-#[derive(Serialize, Deserialize)]
-struct UserStakeInfo {
-    amount: u64,
-    staked_at: f64,
-    pending_rewards: u64,
-    total_rewards_claimed: u64,
-    can_unstake: bool,
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UserStakeInfo {
+    pub amount: u64,
+    pub staked_at: f64,
+    pub pending_rewards: u64,
+    pub total_rewards_claimed: u64,
+    pub can_unstake: bool,
 }
 
-#[derive(Serialize, Deserialize)]
-struct PoolStats {
-    total_staked: u64,
-    reward_rate: f64,
-    min_stake: u64,
-    total_stakers: usize,
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PoolStats {
+    pub total_staked: u64,
+    pub reward_rate: f64,
+    pub min_stake: u64,
+    pub total_stakers: usize,
 }
\ No newline at end of file
@@ -0,0 +1,336 @@
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+use std::fmt;
+use crate::blockchain::{ChainViolation, DecodeError};
+
+// Crate-wide error type. Each variant wraps the errors of one module, and
+// every error has a stable code that the JS side can match on.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BitnunError {
+    Chain(ChainError),
+    Key(KeyError),
+    Encoding(DecodeError),
+    Token(TokenError),
+    Staking(StakingError),
+    Nft(NftError),
+    Contract(ContractError),
+    Consensus(ConsensusError),
+    Network(NetworkError),
+    Config(ConfigError),
+    Store(StoreError),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChainError {
+    MalformedTransaction(String),
+    WrongChain { expected: String, found: String },
+    InvalidSignature,
+    InvalidNonce { expected: u64, found: u64 },
+    InsufficientBalance { available: u64, required: u64 },
+    UnknownParent(String),
+    InvalidBlock(ChainViolation),
+    SnapshotUnavailable(u64),
+    SnapshotMismatch { height: u64, state_height: u64 },
+    ForkBelowPrunedHeight { fork_height: u64, pruned_height: u64 },
+    DuplicateTransaction(String),
+    InvalidCursor(String),
+    InvalidPageLimit(usize),
+    StateRootMismatch { height: u64, expected: String, found: String },
+    MempoolFull { fee: u64 },
+    InvalidActionProof(String),
+    ActionAlreadyMinted(String),
+    InvalidKind(String),
+    NotMultisig(String),
+    GenesisMismatch { expected: String, found: String },
+    PartialSignatureMismatch(String),
+    PartialSignaturesFull(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum KeyError {
+    InvalidSecretKey,
+    InvalidPublicKey,
+    AddressMismatch { expected: String, found: String },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenError {
+    InsufficientBalance { available: u64, required: u64 },
+    InsufficientAllowance { available: u64, required: u64 },
+    MintingDisabled,
+    SupplyOverflow,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StakingError {
+    BelowMinimum { minimum: u64, amount: u64 },
+    StillLocked { unlocks_at: f64 },
+    InsufficientStake { staked: u64, requested: u64 },
+    NoStake,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum NftError {
+    TokenNotFound(u64),
+    NotOwner,
+    NotApprovedOrOwner,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ContractError {
+    FunctionNotFound(String),
+    InvalidParameters,
+    InsufficientBalance { available: u64, required: u64 },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConsensusError {
+    StakeBelowMinimum { minimum: u64, stake: u64 },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum NetworkError {
+    MaxPeersReached(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigError {
+    Parse(String),
+    UnknownPreset(String),
+    EmptyChainId,
+    InvalidTimestamp(String),
+    NoAllocations,
+    InvalidAllocation(String),
+    InvalidValidator(String),
+    SupplyOverflow,
+    SupplyAboveCap { supply: u64, max_supply: u64 },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StoreError {
+    Io(String),
+    Corrupt(String),
+    GenesisMismatch { expected: String, found: String },
+}
+
+impl BitnunError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            BitnunError::Chain(error) => match error {
+                ChainError::MalformedTransaction(_) => "CHAIN_MALFORMED_TRANSACTION",
+                ChainError::WrongChain { .. } => "CHAIN_WRONG_CHAIN",
+                ChainError::InvalidSignature => "CHAIN_INVALID_SIGNATURE",
+                ChainError::InvalidNonce { .. } => "CHAIN_INVALID_NONCE",
+                ChainError::InsufficientBalance { .. } => "CHAIN_INSUFFICIENT_BALANCE",
+                ChainError::UnknownParent(_) => "CHAIN_UNKNOWN_PARENT",
+                ChainError::InvalidBlock(_) => "CHAIN_INVALID_BLOCK",
+                ChainError::SnapshotUnavailable(_) => "CHAIN_SNAPSHOT_UNAVAILABLE",
+                ChainError::SnapshotMismatch { .. } => "CHAIN_SNAPSHOT_MISMATCH",
+                ChainError::ForkBelowPrunedHeight { .. } => "CHAIN_FORK_BELOW_PRUNED_HEIGHT",
+                ChainError::DuplicateTransaction(_) => "CHAIN_DUPLICATE_TRANSACTION",
+                ChainError::InvalidCursor(_) => "CHAIN_INVALID_CURSOR",
+                ChainError::InvalidPageLimit(_) => "CHAIN_INVALID_PAGE_LIMIT",
+                ChainError::StateRootMismatch { .. } => "CHAIN_STATE_ROOT_MISMATCH",
+                ChainError::MempoolFull { .. } => "CHAIN_MEMPOOL_FULL",
+                ChainError::InvalidActionProof(_) => "CHAIN_INVALID_ACTION_PROOF",
+                ChainError::ActionAlreadyMinted(_) => "CHAIN_ACTION_ALREADY_MINTED",
+                ChainError::InvalidKind(_) => "CHAIN_INVALID_KIND",
+                ChainError::NotMultisig(_) => "CHAIN_NOT_MULTISIG",
+                ChainError::GenesisMismatch { .. } => "CHAIN_GENESIS_MISMATCH",
+                ChainError::PartialSignatureMismatch(_) => "CHAIN_PARTIAL_SIGNATURE_MISMATCH",
+                ChainError::PartialSignaturesFull(_) => "CHAIN_PARTIAL_SIGNATURES_FULL",
+            },
+            BitnunError::Key(error) => match error {
+                KeyError::InvalidSecretKey => "KEY_INVALID_SECRET_KEY",
+                KeyError::InvalidPublicKey => "KEY_INVALID_PUBLIC_KEY",
+                KeyError::AddressMismatch { .. } => "KEY_ADDRESS_MISMATCH",
+            },
+            BitnunError::Encoding(_) => "ENCODING_INVALID_BYTES",
+            BitnunError::Token(error) => match error {
+                TokenError::InsufficientBalance { .. } => "TOKEN_INSUFFICIENT_BALANCE",
+                TokenError::InsufficientAllowance { .. } => "TOKEN_INSUFFICIENT_ALLOWANCE",
+                TokenError::MintingDisabled => "TOKEN_MINTING_DISABLED",
+                TokenError::SupplyOverflow => "TOKEN_SUPPLY_OVERFLOW",
+            },
+            BitnunError::Staking(error) => match error {
+                StakingError::BelowMinimum { .. } => "STAKING_BELOW_MINIMUM",
+                StakingError::StillLocked { .. } => "STAKING_STILL_LOCKED",
+                StakingError::InsufficientStake { .. } => "STAKING_INSUFFICIENT_STAKE",
+                StakingError::NoStake => "STAKING_NO_STAKE",
+            },
+            BitnunError::Nft(error) => match error {
+                NftError::TokenNotFound(_) => "NFT_TOKEN_NOT_FOUND",
+                NftError::NotOwner => "NFT_NOT_OWNER",
+                NftError::NotApprovedOrOwner => "NFT_NOT_APPROVED_OR_OWNER",
+            },
+            BitnunError::Contract(error) => match error {
+                ContractError::FunctionNotFound(_) => "CONTRACT_FUNCTION_NOT_FOUND",
+                ContractError::InvalidParameters => "CONTRACT_INVALID_PARAMETERS",
+                ContractError::InsufficientBalance { .. } => "CONTRACT_INSUFFICIENT_BALANCE",
+            },
+            BitnunError::Consensus(error) => match error {
+                ConsensusError::StakeBelowMinimum { .. } => "CONSENSUS_STAKE_BELOW_MINIMUM",
+            },
+            BitnunError::Network(error) => match error {
+                NetworkError::MaxPeersReached(_) => "NETWORK_MAX_PEERS_REACHED",
+            },
+            BitnunError::Config(error) => match error {
+                ConfigError::Parse(_) => "CONFIG_PARSE",
+                ConfigError::UnknownPreset(_) => "CONFIG_UNKNOWN_PRESET",
+                ConfigError::EmptyChainId => "CONFIG_EMPTY_CHAIN_ID",
+                ConfigError::InvalidTimestamp(_) => "CONFIG_INVALID_TIMESTAMP",
+                ConfigError::NoAllocations => "CONFIG_NO_ALLOCATIONS",
+                ConfigError::InvalidAllocation(_) => "CONFIG_INVALID_ALLOCATION",
+                ConfigError::InvalidValidator(_) => "CONFIG_INVALID_VALIDATOR",
+                ConfigError::SupplyOverflow => "CONFIG_SUPPLY_OVERFLOW",
+                ConfigError::SupplyAboveCap { .. } => "CONFIG_SUPPLY_ABOVE_CAP",
+            },
+            BitnunError::Store(error) => match error {
+                StoreError::Io(_) => "STORE_IO",
+                StoreError::Corrupt(_) => "STORE_CORRUPT",
+                StoreError::GenesisMismatch { .. } => "STORE_GENESIS_MISMATCH",
+            },
+        }
+    }
+}
+
+impl fmt::Display for BitnunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BitnunError::Chain(error) => match error {
+                ChainError::MalformedTransaction(reason) => write!(f, "Malformed transaction: {}", reason),
+                ChainError::WrongChain { expected, found } =>
+                    write!(f, "Transaction is for chain {}, expected {}", found, expected),
+                ChainError::InvalidSignature => write!(f, "Missing or invalid transaction signature"),
+                ChainError::InvalidNonce { expected, found } =>
+                    write!(f, "Invalid nonce {}, expected {}", found, expected),
+                ChainError::InsufficientBalance { available, required } =>
+                    write!(f, "Insufficient balance: {} available, {} required", available, required),
+                ChainError::UnknownParent(hash) => write!(f, "Parent block {} is unknown", hash),
+                ChainError::InvalidBlock(violation) =>
+                    write!(f, "Block {} rejected: {:?} ({})", violation.block_index, violation.rule, violation.detail),
+                ChainError::SnapshotUnavailable(height) => write!(f, "No snapshot available at height {}", height),
+                ChainError::SnapshotMismatch { height, state_height } =>
+                    write!(f, "Snapshot covers {} headers but its state is at height {}", height, state_height),
+                ChainError::ForkBelowPrunedHeight { fork_height, pruned_height } =>
+                    write!(f, "Branch forks at height {}, below the {} blocks held without bodies", fork_height, pruned_height),
+                ChainError::DuplicateTransaction(id) => write!(f, "Transaction {} is already in the chain", id),
+                ChainError::InvalidCursor(cursor) => write!(f, "Invalid history cursor '{}'", cursor),
+                ChainError::InvalidPageLimit(limit) => write!(f, "History page limit must be at least 1, got {}", limit),
+                ChainError::StateRootMismatch { height, expected, found } =>
+                    write!(f, "State at height {} has root {} but the header commits to {}", height, found, expected),
+                ChainError::MempoolFull { fee } =>
+                    write!(f, "Transaction pool is full and a fee of {} does not outbid any entry", fee),
+                ChainError::InvalidActionProof(reason) => write!(f, "Invalid action proof: {}", reason),
+                ChainError::ActionAlreadyMinted(action_id) => write!(f, "Action {} has already been minted", action_id),
+                ChainError::InvalidKind(reason) => write!(f, "Transaction not allowed: {}", reason),
+                ChainError::NotMultisig(address) => write!(f, "Account {} has no multisig policy", address),
+                ChainError::GenesisMismatch { expected, found } =>
+                    write!(f, "Genesis block {} does not match its parameters, which produce {}", found, expected),
+                ChainError::PartialSignatureMismatch(id) =>
+                    write!(f, "Signatures for transaction {} cover different contents than those already collected", id),
+                ChainError::PartialSignaturesFull(address) =>
+                    write!(f, "Too many transactions from {} are still gathering signatures", address),
+            },
+            BitnunError::Key(error) => match error {
+                KeyError::InvalidSecretKey => write!(f, "Secret key must be 32 hex-encoded bytes"),
+                KeyError::InvalidPublicKey => write!(f, "Public key is not a valid Ed25519 key"),
+                KeyError::AddressMismatch { expected, found } =>
+                    write!(f, "Key address {} does not match sender {}", found, expected),
+            },
+            BitnunError::Encoding(error) => write!(f, "Invalid canonical encoding: {:?}", error),
+            BitnunError::Token(error) => match error {
+                TokenError::InsufficientBalance { available, required } =>
+                    write!(f, "Insufficient balance: {} available, {} required", available, required),
+                TokenError::InsufficientAllowance { available, required } =>
+                    write!(f, "Insufficient allowance: {} available, {} required", available, required),
+                TokenError::MintingDisabled => write!(f, "Minting disabled"),
+                TokenError::SupplyOverflow => write!(f, "Total supply would overflow"),
+            },
+            BitnunError::Staking(error) => match error {
+                StakingError::BelowMinimum { minimum, amount } =>
+                    write!(f, "Stake of {} is below the minimum {}", amount, minimum),
+                StakingError::StillLocked { unlocks_at } => write!(f, "Stake is locked until {}", unlocks_at),
+                StakingError::InsufficientStake { staked, requested } =>
+                    write!(f, "Insufficient staked amount: {} staked, {} requested", staked, requested),
+                StakingError::NoStake => write!(f, "No stake found"),
+            },
+            BitnunError::Nft(error) => match error {
+                NftError::TokenNotFound(token_id) => write!(f, "NFT #{} does not exist", token_id),
+                NftError::NotOwner => write!(f, "Not the owner"),
+                NftError::NotApprovedOrOwner => write!(f, "Not approved or owner"),
+            },
+            BitnunError::Contract(error) => match error {
+                ContractError::FunctionNotFound(function) => write!(f, "Function not found: {}", function),
+                ContractError::InvalidParameters => write!(f, "Invalid parameters"),
+                ContractError::InsufficientBalance { available, required } =>
+                    write!(f, "Insufficient balance: {} available, {} required", available, required),
+            },
+            BitnunError::Consensus(error) => match error {
+                ConsensusError::StakeBelowMinimum { minimum, stake } =>
+                    write!(f, "Validator stake {} is below the minimum {}", stake, minimum),
+            },
+            BitnunError::Network(error) => match error {
+                NetworkError::MaxPeersReached(max_peers) => write!(f, "Max peers reached ({})", max_peers),
+            },
+            BitnunError::Config(error) => match error {
+                ConfigError::Parse(message) => write!(f, "Invalid genesis config: {}", message),
+                ConfigError::UnknownPreset(name) => write!(f, "Unknown genesis preset: {}", name),
+                ConfigError::EmptyChainId => write!(f, "Genesis config needs a chain id"),
+                ConfigError::InvalidTimestamp(timestamp) => write!(f, "Genesis timestamp is not RFC 3339: {}", timestamp),
+                ConfigError::NoAllocations => write!(f, "Genesis config needs at least one allocation"),
+                ConfigError::InvalidAllocation(address) => write!(f, "Invalid genesis allocation for '{}'", address),
+                ConfigError::InvalidValidator(address) => write!(f, "Invalid genesis validator '{}': needs an address and a stake", address),
+                ConfigError::SupplyOverflow => write!(f, "Genesis allocations overflow the supply"),
+                ConfigError::SupplyAboveCap { supply, max_supply } =>
+                    write!(f, "Genesis supply {} exceeds the maximum supply {}", supply, max_supply),
+            },
+            BitnunError::Store(error) => match error {
+                StoreError::Io(message) => write!(f, "Block store I/O failed: {}", message),
+                StoreError::Corrupt(message) => write!(f, "Block store is corrupt: {}", message),
+                StoreError::GenesisMismatch { expected, found } =>
+                    write!(f, "Block store holds genesis {}, expected {}", found, expected),
+            },
+        }
+    }
+}
+
+impl std::error::Error for BitnunError {}
+
+macro_rules! impl_from_module_error {
+    ($($error:ident => $variant:ident),* $(,)?) => {
+        $(
+            impl From<$error> for BitnunError {
+                fn from(error: $error) -> Self {
+                    BitnunError::$variant(error)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_module_error! {
+    ChainError => Chain,
+    KeyError => Key,
+    DecodeError => Encoding,
+    TokenError => Token,
+    StakingError => Staking,
+    NftError => Nft,
+    ContractError => Contract,
+    ConsensusError => Consensus,
+    NetworkError => Network,
+    ConfigError => Config,
+    StoreError => Store,
+}
+
+// Thrown into JS as an Error whose `code` property carries the stable code
+#[cfg(feature = "wasm")]
+impl From<BitnunError> for JsValue {
+    fn from(error: BitnunError) -> JsValue {
+        let js_error = js_sys::Error::new(&error.to_string());
+        let _ = js_sys::Reflect::set(&js_error, &JsValue::from_str("code"), &JsValue::from_str(error.code()));
+        js_error.into()
+    }
+}
+
+pub type BitnunResult<T> = Result<T, BitnunError>;
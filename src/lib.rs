@@ -1,12 +1,15 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
-use web_sys::console;
 
+pub mod runtime;
+mod error;
 mod blockchain;
 mod consensus;
 mod contracts;
 mod network;
 mod ai;
 
+pub use error::*;
 pub use blockchain::*;
 pub use consensus::*;
 pub use contracts::*;
@@ -14,12 +17,14 @@ pub use network::*;
 pub use ai::*;
 
 // Main WASM entry point
+#[cfg(feature = "wasm")]
 #[wasm_bindgen(start)]
 pub fn main() {
-    console::log_1(&"Bitnun WASM Node initialized".into());
+    runtime::log("Bitnun WASM Node initialized");
 }
 
 // Panic hook for better error messages
+#[cfg(feature = "wasm")]
 #[wasm_bindgen]
 extern "C" {
     #[wasm_bindgen(js_namespace = console)]
@@ -27,11 +32,7 @@ extern "C" {
 }
 
 macro_rules! console_log {
-    ($($t:tt)*) => (console::log_1(&format!($($t)*).into()))
+    ($($t:tt)*) => ($crate::runtime::log(&format!($($t)*)))
 }
 
-pub(crate) use console_log;
-
-#[cfg(feature = "wee_alloc")]
-#[global_allocator]
-static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
\ No newline at end of file
+pub(crate) use console_log;
\ No newline at end of file
@@ -1,19 +1,16 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
-use web_sys::{WebSocket, MessageEvent, CloseEvent, Event};
 use std::collections::HashMap;
 use crate::console_log;
+use crate::error::{BitnunResult, NetworkError};
+use crate::runtime;
 
-pub mod peer_manager;
-pub mod message_handler;
-
-pub use peer_manager::*;
-pub use message_handler::*;
-
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen(getter_with_clone))]
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct P2PNetwork {
     pub node_id: String,
+    #[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
     pub peers: HashMap<String, PeerConnection>,
     pub max_peers: usize,
     pub is_bootstrapping: bool,
@@ -29,9 +26,9 @@ pub struct PeerConnection {
     pub is_validator: bool,
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl P2PNetwork {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new(node_id: &str) -> P2PNetwork {
         P2PNetwork {
             node_id: node_id.to_string(),
@@ -45,7 +42,7 @@ impl P2PNetwork {
         }
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn start_networking(&mut self) {
         console_log!("Starting P2P networking for node: {}", self.node_id);
         self.is_bootstrapping = true;
@@ -55,17 +52,16 @@ impl P2PNetwork {
         self.simulate_bootstrap();
     }
 
-    #[wasm_bindgen]
-    pub fn connect_to_peer(&mut self, peer_url: &str, peer_id: &str) -> bool {
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn connect_to_peer(&mut self, peer_url: &str, peer_id: &str) -> BitnunResult<()> {
         if self.peers.len() >= self.max_peers {
-            console_log!("Max peers reached, rejecting connection to {}", peer_id);
-            return false;
+            return Err(NetworkError::MaxPeersReached(self.max_peers).into());
         }
 
         let peer = PeerConnection {
             peer_id: peer_id.to_string(),
             websocket: Some(peer_url.to_string()),
-            last_seen: js_sys::Date::now(),
+            last_seen: runtime::now_millis(),
             reputation: 1.0,
             is_validator: false,
         };
@@ -75,38 +71,38 @@ impl P2PNetwork {
         
         // Send handshake message
         self.send_handshake(peer_id);
-        true
+        Ok(())
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn broadcast_message(&self, message_type: &str, data: &str) {
         let message = NetworkMessage {
             message_type: message_type.to_string(),
             from: self.node_id.clone(),
             to: "broadcast".to_string(),
             data: data.to_string(),
-            timestamp: js_sys::Date::now(),
+            timestamp: runtime::now_millis(),
         };
 
         console_log!("Broadcasting {} message to {} peers", message_type, self.peers.len());
         
         // In a real implementation, this would send to all connected WebSockets
-        for (peer_id, _) in &self.peers {
+        for peer_id in self.peers.keys() {
             self.send_message_to_peer(peer_id, &message);
         }
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn send_transaction(&self, transaction_data: &str) {
         self.broadcast_message("transaction", transaction_data);
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn send_block(&self, block_data: &str) {
         self.broadcast_message("block", block_data);
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn request_sync(&self) {
         self.broadcast_message("sync_request", &self.node_id);
     }
@@ -129,7 +125,7 @@ impl P2PNetwork {
             from: self.node_id.clone(),
             to: peer_id.to_string(),
             data,
-            timestamp: js_sys::Date::now(),
+            timestamp: runtime::now_millis(),
         };
 
         self.send_message_to_peer(peer_id, &message);
@@ -155,7 +151,7 @@ impl P2PNetwork {
             let peer = PeerConnection {
                 peer_id: peer_id.clone(),
                 websocket: Some(format!("wss://peer-{}.bitnun.network/ws", i)),
-                last_seen: js_sys::Date::now(),
+                last_seen: runtime::now_millis(),
                 reputation: 0.8 + (i as f64 * 0.05),
                 is_validator: i < 3, // First 3 peers are validators
             };
@@ -167,9 +163,16 @@ impl P2PNetwork {
         console_log!("Bootstrap completed with {} peers", self.peers.len());
     }
 
+    #[cfg(feature = "wasm")]
     #[wasm_bindgen]
     pub fn get_network_stats(&self) -> JsValue {
-        let stats = NetworkStats {
+        serde_wasm_bindgen::to_value(&self.network_stats()).unwrap()
+    }
+}
+
+impl P2PNetwork {
+    pub fn network_stats(&self) -> NetworkStats {
+        NetworkStats {
             node_id: self.node_id.clone(),
             connected_peers: self.peers.len(),
             validator_peers: self.peers.values().filter(|p| p.is_validator).count(),
@@ -179,9 +182,7 @@ impl P2PNetwork {
             } else {
                 0.0
             },
-        };
-
-        serde_wasm_bindgen::to_value(&stats).unwrap()
+        }
     }
 }
 
@@ -202,11 +203,11 @@ struct HandshakeMessage {
     best_block: u64,
 }
 
-#[derive(Serialize, Deserialize)]
-struct NetworkStats {
-    node_id: String,
-    connected_peers: usize,
-    validator_peers: usize,
-    is_bootstrapping: bool,
-    average_reputation: f64,
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NetworkStats {
+    pub node_id: String,
+    pub connected_peers: usize,
+    pub validator_peers: usize,
+    pub is_bootstrapping: bool,
+    pub average_reputation: f64,
 }
\ No newline at end of file
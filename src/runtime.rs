@@ -0,0 +1,122 @@
+use chrono::{TimeZone, Utc};
+use std::sync::{Arc, RwLock};
+
+// Time and logging go through these hooks so the same chain logic runs in
+// the browser and in a native server process. Hosts can inject their own
+// backends with set_clock / set_log_sink; otherwise the defaults for the
+// current build target are used.
+pub trait Clock: Send + Sync {
+    fn now_millis(&self) -> f64; // Milliseconds since the Unix epoch
+}
+
+pub trait LogSink: Send + Sync {
+    fn log(&self, message: &str);
+}
+
+#[cfg(feature = "wasm")]
+pub struct JsClock;
+
+#[cfg(feature = "wasm")]
+impl Clock for JsClock {
+    fn now_millis(&self) -> f64 {
+        js_sys::Date::now()
+    }
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_millis(&self) -> f64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs_f64() * 1000.0)
+            .unwrap_or(0.0)
+    }
+}
+
+// Always reports the same instant; useful for reproducible runs
+pub struct FixedClock(pub f64);
+
+impl Clock for FixedClock {
+    fn now_millis(&self) -> f64 {
+        self.0
+    }
+}
+
+#[cfg(feature = "wasm")]
+pub struct ConsoleLogSink;
+
+#[cfg(feature = "wasm")]
+impl LogSink for ConsoleLogSink {
+    fn log(&self, message: &str) {
+        web_sys::console::log_1(&message.into());
+    }
+}
+
+pub struct StderrLogSink;
+
+impl LogSink for StderrLogSink {
+    fn log(&self, message: &str) {
+        eprintln!("{}", message);
+    }
+}
+
+pub struct NullLogSink;
+
+impl LogSink for NullLogSink {
+    fn log(&self, _message: &str) {}
+}
+
+static CLOCK: RwLock<Option<Arc<dyn Clock>>> = RwLock::new(None);
+static LOG_SINK: RwLock<Option<Arc<dyn LogSink>>> = RwLock::new(None);
+
+pub fn set_clock(clock: Arc<dyn Clock>) {
+    *CLOCK.write().unwrap() = Some(clock);
+}
+
+pub fn set_log_sink(sink: Arc<dyn LogSink>) {
+    *LOG_SINK.write().unwrap() = Some(sink);
+}
+
+pub fn now_millis() -> f64 {
+    match CLOCK.read().unwrap().as_ref() {
+        Some(clock) => clock.now_millis(),
+        None => default_clock().now_millis(),
+    }
+}
+
+pub fn now_rfc3339() -> String {
+    Utc.timestamp_millis_opt(now_millis() as i64)
+        .single()
+        .unwrap_or_default()
+        .to_rfc3339()
+}
+
+pub fn log(message: &str) {
+    match LOG_SINK.read().unwrap().as_ref() {
+        Some(sink) => sink.log(message),
+        None => default_log_sink().log(message),
+    }
+}
+
+// JS imports panic off wasm32, so a native build with the bindings
+// compiled in still defaults to the system clock and stderr
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+fn default_clock() -> impl Clock {
+    JsClock
+}
+
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+fn default_clock() -> impl Clock {
+    SystemClock
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+fn default_log_sink() -> impl LogSink {
+    ConsoleLogSink
+}
+
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+fn default_log_sink() -> impl LogSink {
+    StderrLogSink
+}